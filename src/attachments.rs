@@ -0,0 +1,123 @@
+// Files attached to items, like the org-attach files of notes, which sources
+// list as enclosures with file:// URLs. Readers can't open those, so feeds can
+// have them copied to a directory that is served and the URLs rewritten to
+// point there.
+
+use std::{fs, path};
+use anyhow::{anyhow, Context, Result};
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::{Enclosure, NewsItem};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AttachmentsConfig {
+    // Directory the files are copied to, resolved against output_dir
+    pub path: path::PathBuf,
+    // Public URL the directory is served at
+    pub url: String,
+}
+
+// Guess the type of a file from its extension
+pub fn mime_type(file_path: &path::Path) -> &'static str {
+    let extension = file_path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase());
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("pdf") => "application/pdf",
+        Some("epub") => "application/epub+zip",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("mp4") => "video/mp4",
+        Some("txt") | Some("org") => "text/plain",
+        Some("html") => "text/html",
+        _ => "application/octet-stream",
+    }
+}
+
+// An enclosure for a local file
+pub fn file_enclosure(file_path: &path::Path) -> Option<Enclosure> {
+    Some(Enclosure {
+        url: Url::from_file_path(file_path).ok()?.to_string(),
+        mime_type: mime_type(file_path).to_string(),
+        length: fs::metadata(file_path).ok().map(|metadata| metadata.len()),
+    })
+}
+
+// Ids can have characters that don't belong in file names
+fn dir_name(id: &str) -> String {
+    id.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+// Point the local enclosures of items at their copies under the public URL,
+// at <url>/<item id>/<file name>, copying them over unless only the URLs are
+// wanted. Files already copied with the same size are left alone.
+pub fn publish(config: &AttachmentsConfig, dir: &path::Path, items: &mut [NewsItem], copy: bool) -> Result<()> {
+    for item in items.iter_mut() {
+        for enclosure in item.enclosures.iter_mut() {
+            let Some(source) = Url::parse(&enclosure.url).ok().filter(|url| url.scheme() == "file") else {
+                continue;
+            };
+            let source = source.to_file_path().map_err(|_| anyhow!("Bad attachment URL {}", enclosure.url))?;
+            let name = source.file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| anyhow!("Bad attachment name {}", source.display()))?;
+            let item_dir = dir_name(&item.id);
+
+            if copy {
+                let target = dir.join(&item_dir).join(name);
+                let copied = fs::metadata(&target).ok().map(|metadata| metadata.len());
+                if copied.is_none() || copied != fs::metadata(&source).ok().map(|metadata| metadata.len()) {
+                    fs::create_dir_all(dir.join(&item_dir)).context("Unable to create attachments directory")?;
+                    fs::copy(&source, &target)
+                        .with_context(|| format!("Unable to copy attachment {}", source.display()))?;
+                }
+            }
+
+            let mut url = Url::parse(&config.url).context("Bad attachments URL")?;
+            url.path_segments_mut()
+                .map_err(|_| anyhow!("Bad attachments URL {}", config.url))?
+                .pop_if_empty()
+                .push(&item_dir)
+                .push(name);
+            enclosure.url = url.to_string();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_attachments_are_copied_and_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("a plot.png");
+        fs::write(&file_path, "png").unwrap();
+
+        let mut item = NewsItem {
+            id: "ab:cd".to_string(),
+            link: "https://example.com".to_string(),
+            title: "Note".to_string(),
+            published: chrono::Utc::now(),
+            updated: chrono::Utc::now(),
+            enclosures: vec![
+                file_enclosure(&file_path).unwrap(),
+                Enclosure { url: "https://example.com/a.mp3".to_string(), mime_type: "audio/mpeg".to_string(), length: None },
+            ],
+            ..Default::default()
+        };
+        let config = AttachmentsConfig { path: "public".into(), url: "https://example.com/files/".to_string() };
+        publish(&config, &dir.path().join("public"), std::slice::from_mut(&mut item), true).unwrap();
+
+        assert_eq!(item.enclosures[0].url, "https://example.com/files/ab_cd/a%20plot.png");
+        assert_eq!(item.enclosures[0].mime_type, "image/png");
+        assert_eq!(item.enclosures[0].length, Some(3));
+        assert_eq!(item.enclosures[1].url, "https://example.com/a.mp3");
+        assert_eq!(fs::read_to_string(dir.path().join("public/ab_cd/a plot.png")).unwrap(), "png");
+    }
+}
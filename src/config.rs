@@ -0,0 +1,732 @@
+use std::collections::HashMap;
+use std::{fs, path};
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::attachments::AttachmentsConfig;
+use crate::filters::BookmarkConfig;
+use crate::notifications::NotifyConfig;
+use crate::paging::PagingConfig;
+use crate::publishers::PublishConfig;
+use crate::sinks::email::SmtpConfig;
+use crate::podcast::PodcastConfig;
+use crate::sinks::activitypub::ActivityPubConfig;
+use crate::sinks::linkding::LinkdingConfig;
+use crate::sinks::micropub::MicropubConfig;
+use crate::sinks::nostr::{NostrConfig, NostrKind};
+use crate::sinks::push::PushMode;
+use crate::sinks::telegram::TelegramConfig;
+use crate::sinks::wallabag::WallabagConfig;
+use crate::sinks::{Compression, Format, OutputConfig};
+use crate::{utils, NewsAuthor};
+
+// Top level configuration read from journalist.toml. Keys at the top level
+// form the default profile and named profiles under [profiles.<name>] override
+// them selectively. A minimal file looks like this:
+//
+//   notes_dir_path = "/home/me/notes"
+//   output_dir = "/srv/feeds"
+//
+//   [author]
+//   name = "Me"
+//   email = "me@example.com"
+//   uri = "example.com"
+//
+//   [profiles.partner]
+//   notes_dir_path = "/home/partner/notes"
+//   output_dir = "/srv/feeds/partner"
+//
+//   [http]
+//   timeout = 30
+//
+//   [sources.hf.http]
+//   timeout = 120
+//
+//   [[feeds]]
+//   id = "pile-bookmarks"
+//   source = "pile"
+//   title = "General Bookmarks"
+//   filters = ["unread", "!project"]
+//   order = "random"
+//   count = 2
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Config {
+    #[serde(flatten)]
+    pub defaults: Profile,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+    #[serde(default)]
+    pub http: HttpOptions,
+    #[serde(default)]
+    pub sources: HashMap<String, SourceConfig>,
+    #[serde(default)]
+    pub publish: PublishConfig,
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    #[serde(default)]
+    pub bookmarks: BookmarkConfig,
+    // Server to send feeds with email recipients through
+    pub smtp: Option<SmtpConfig>,
+    // Key and relays for feeds posting to Nostr
+    pub nostr: Option<NostrConfig>,
+    // Site for feeds posting to Micropub
+    pub micropub: Option<MicropubConfig>,
+    // Bot for feeds sending to Telegram
+    pub telegram: Option<TelegramConfig>,
+    // Instance for feeds saving to Wallabag
+    pub wallabag: Option<WallabagConfig>,
+    // Instance for feeds saving to linkding
+    pub linkding: Option<LinkdingConfig>,
+    #[serde(default)]
+    pub feeds: Vec<FeedConfig>,
+}
+
+// Definition of one generated feed
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedConfig {
+    pub id: String,
+    // Name of the source to read items from, `pile`, `hf`, or `exec`
+    pub source: String,
+    pub title: String,
+    #[serde(default)]
+    pub subtitle: String,
+    // Defaults to /<id>
+    pub link: Option<String>,
+    // Number of items to pick, all if not set
+    pub count: Option<usize>,
+    // Filters items from the source must pass, see `filters::Filter` for the
+    // syntax
+    #[serde(default)]
+    pub filters: Vec<String>,
+    // Program and its arguments for the `exec` source
+    #[serde(default)]
+    pub command: Vec<String>,
+    // Make an item of each link of `pile` notes with many ROAM_REFS, instead
+    // of one with the others as related links
+    #[serde(default)]
+    pub split_refs: bool,
+    // Keep `pile` notes tagged ARCHIVE or noexport, or under a heading that is
+    #[serde(default)]
+    pub include_archived: bool,
+    // Only take links under headings with this title for `pile-daily`
+    pub daily_heading: Option<String>,
+    // Directory of bookmarks for the `markdown` source
+    pub markdown_dir: Option<path::PathBuf>,
+    // Leave `hf` papers the papers API has no summary for without one, instead
+    // of reading their abstracts from arXiv
+    #[serde(default)]
+    pub skip_abstracts: bool,
+    // Only take `hf` papers with at least these many upvotes
+    pub min_votes: Option<usize>,
+    // ISO weeks like 2024-W37, or ranges of them, to read `hf` papers of
+    // instead of the current week
+    #[serde(default)]
+    pub weeks: Vec<String>,
+    #[serde(default)]
+    pub order: FeedOrder,
+    // Only keep items not seen in earlier runs
+    #[serde(default)]
+    pub delta: bool,
+    // Leave out items the feed has published in earlier runs
+    #[serde(default)]
+    pub skip_emitted: bool,
+    // Cron expression for when `journalist daemon` regenerates the feed, like
+    // `0 */6 * * *` or `@weekly`
+    pub schedule: Option<String>,
+    // Add new items to the existing output file instead of replacing it
+    #[serde(default)]
+    pub incremental: bool,
+    // Number of entries an incremental feed keeps, 50 if not set
+    pub keep: Option<usize>,
+    // Spread a weekly pick of these many items over the week
+    pub drip: Option<usize>,
+    pub max_summary_length: Option<usize>,
+    #[serde(default)]
+    pub drop_full_text: bool,
+    // Convert org markup in summaries to HTML content
+    #[serde(default)]
+    pub html_content: bool,
+    // Defaults to <id>.xml, resolved against the profile's output_dir
+    pub output: Option<path::PathBuf>,
+    // Multiple outputs with their formats, used instead of `output` when set
+    #[serde(default)]
+    pub outputs: Vec<OutputConfig>,
+    // Write all outputs without indentation
+    #[serde(default)]
+    pub compact: bool,
+    // Also write compressed copies of all file outputs
+    #[serde(default)]
+    pub compress: Vec<Compression>,
+    // Split file outputs into linked documents once they grow large
+    pub paging: Option<PagingConfig>,
+    // Addresses to also send the feed to as an email, via [smtp]
+    #[serde(default)]
+    pub email: Vec<String>,
+    // Also write the feed as static ActivityPub documents
+    pub activitypub: Option<ActivityPubConfig>,
+    // Post new items to Nostr as this kind of event, via [nostr]
+    pub nostr: Option<NostrKind>,
+    // Post new items as bookmarks to the site in [micropub]
+    #[serde(default)]
+    pub micropub: bool,
+    // Send new items to the chat in [telegram]
+    #[serde(default)]
+    pub telegram: bool,
+    // Post new items to this Discord webhook
+    pub discord: Option<String>,
+    // Post new items to this Slack incoming webhook
+    pub slack: Option<String>,
+    // Push a notification per new item, or a digest of them, via [notify]
+    pub push: Option<PushMode>,
+    // Save new items to the instance in [wallabag]
+    #[serde(default)]
+    pub wallabag: bool,
+    // Save new items as bookmarks to the instance in [linkding]
+    #[serde(default)]
+    pub linkding: bool,
+    // Also read items out into a podcast
+    pub podcast: Option<PodcastConfig>,
+    // Copy local attachments of items, like the org-attach files of notes,
+    // to a served directory
+    pub attachments: Option<AttachmentsConfig>,
+    // Defaults to the profile's author
+    pub author: Option<NewsAuthor>,
+    // Image URLs readers show for the feed, a small square icon and a larger
+    // logo
+    pub icon: Option<String>,
+    pub logo: Option<String>,
+    // Language tag of the feed, like en
+    pub language: Option<String>,
+    // Guess the language of each item from its title and summary
+    #[serde(default)]
+    pub detect_language: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeedOrder {
+    // Keep the order the source returned items in
+    #[default]
+    Source,
+    Random,
+    Newest,
+    // Random, but favoring `pile` bookmarks that more notes link to
+    Linked,
+}
+
+impl FeedConfig {
+    // Feed with the given metadata and defaults for everything else
+    pub fn new(id: &str, source: &str, title: &str, subtitle: &str) -> Self {
+        FeedConfig {
+            id: id.to_string(),
+            source: source.to_string(),
+            title: title.to_string(),
+            subtitle: subtitle.to_string(),
+            link: None,
+            count: None,
+            filters: Vec::new(),
+            command: Vec::new(),
+            split_refs: false,
+            include_archived: false,
+            daily_heading: None,
+            markdown_dir: None,
+            skip_abstracts: false,
+            min_votes: None,
+            weeks: Vec::new(),
+            order: FeedOrder::Source,
+            delta: false,
+            skip_emitted: false,
+            schedule: None,
+            incremental: false,
+            keep: None,
+            drip: None,
+            max_summary_length: None,
+            drop_full_text: false,
+            html_content: false,
+            output: None,
+            outputs: Vec::new(),
+            compact: false,
+            compress: Vec::new(),
+            paging: None,
+            email: Vec::new(),
+            activitypub: None,
+            nostr: None,
+            micropub: false,
+            telegram: false,
+            discord: None,
+            slack: None,
+            push: None,
+            wallabag: false,
+            linkding: false,
+            podcast: None,
+            attachments: None,
+            author: None,
+            icon: None,
+            logo: None,
+            language: None,
+            detect_language: false,
+        }
+    }
+
+    pub fn link(&self) -> String {
+        self.link.clone().unwrap_or_else(|| format!("/{}", self.id))
+    }
+
+    pub fn output(&self) -> path::PathBuf {
+        self.output.clone().unwrap_or_else(|| path::PathBuf::from(format!("{}.xml", self.id)))
+    }
+
+    // All destinations the feed should be emitted to
+    pub fn outputs(&self) -> Vec<OutputConfig> {
+        let outputs = if self.outputs.is_empty() {
+            vec![OutputConfig::new(self.output(), Format::default())]
+        } else {
+            self.outputs.clone()
+        };
+        outputs.into_iter()
+            .map(|output| {
+                let mut compress = output.compress.clone();
+                compress.extend(self.compress.iter().filter(|compression| !output.compress.contains(compression)));
+                OutputConfig { compact: output.compact || self.compact, compress, ..output }
+            })
+            .collect()
+    }
+
+    // Where readers can subscribe to the feed: its link if that is a full
+    // URL, else its first file output in a feed format under base_url
+    pub fn public_url(&self, base_url: Option<&str>) -> Option<String> {
+        if let Some(link) = self.link.as_ref().filter(|link| link.starts_with("http://") || link.starts_with("https://")) {
+            return Some(link.clone());
+        }
+
+        let output = self.outputs().into_iter()
+            .find(|output| !output.is_stdout() && output.path.is_relative() && output.format.is_subscribable())?;
+        Some(format!("{}/{}", base_url?.trim_end_matches('/'), output.path.to_str()?))
+    }
+}
+
+// Feeds available without any config. A [[feeds]] entry with the same id in
+// the config replaces these.
+pub fn builtin_feeds() -> Vec<FeedConfig> {
+    vec![
+        FeedConfig {
+            filters: vec!["unread".to_string(), "!project".to_string()],
+            order: FeedOrder::Random,
+            count: Some(2),
+            ..FeedConfig::new("pile-bookmarks", "pile", "General Bookmarks", "Unread picks from saved bookmarks.")
+        },
+        FeedConfig {
+            filters: vec!["unread".to_string(), "project".to_string()],
+            order: FeedOrder::Random,
+            count: Some(1),
+            ..FeedConfig::new("pile-bookmarks-projects", "pile", "Unsorted Projects", "Unsorted projects from saved bookmarks.")
+        },
+        FeedConfig {
+            count: Some(5),
+            ..FeedConfig::new("hf-papers", "hf", "Huggingface papers", "Papers from Huggingface Daily Papers.")
+        },
+        FeedConfig {
+            filters: vec!["recommended".to_string()],
+            order: FeedOrder::Newest,
+            ..FeedConfig::new("recommended-links", "pile", "Recommended links",
+                              "Recommendations from read articles and bookmarks.")
+        },
+    ]
+}
+
+// Used when neither the profile nor the feed sets an author. Atom needs
+// feeds to have one, so this falls back to the user running journalist.
+pub fn default_author() -> NewsAuthor {
+    NewsAuthor {
+        name: std::env::var("USER").unwrap_or_else(|_| "journalist".to_string()),
+        email: String::new(),
+        uri: String::new(),
+    }
+}
+
+// Limits for network requests. Durations are in seconds and the size is in
+// bytes. robots.txt is honored unless ignore_robots is set, which is mostly
+// meant for sources that talk to an API rather than scrape pages.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct HttpOptions {
+    pub timeout: Option<u64>,
+    pub connect_timeout: Option<u64>,
+    pub max_response_size: Option<u64>,
+    pub ignore_robots: Option<bool>,
+    // Sent with requests and matched against robots.txt groups
+    pub user_agent: Option<String>,
+}
+
+impl HttpOptions {
+    fn merge(self, other: HttpOptions) -> HttpOptions {
+        HttpOptions {
+            timeout: other.timeout.or(self.timeout),
+            connect_timeout: other.connect_timeout.or(self.connect_timeout),
+            max_response_size: other.max_response_size.or(self.max_response_size),
+            ignore_robots: other.ignore_robots.or(self.ignore_robots),
+            user_agent: other.user_agent.or(self.user_agent),
+        }
+    }
+}
+
+// Settings specific to one source, keyed by source name in [sources.<name>]
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct SourceConfig {
+    #[serde(default)]
+    pub http: HttpOptions,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Profile {
+    pub author: Option<NewsAuthor>,
+    pub roam_db_path: Option<path::PathBuf>,
+    pub notes_dir_path: Option<path::PathBuf>,
+    // How deep to look for notes under notes_dir_path, 1 being only the
+    // directory itself. Unlimited if not set.
+    pub notes_max_depth: Option<usize>,
+    // Glob patterns of files and directories under notes_dir_path to skip,
+    // like daily/* or *.archive.org
+    pub notes_exclude: Option<Vec<String>>,
+    // Timezone of the times in notes and their file names, like
+    // Asia/Kolkata. Defaults to the one in $TZ, else the system's.
+    pub timezone: Option<String>,
+    // Obsidian vault with web clippings, for the `obsidian` source
+    pub obsidian_vault_path: Option<path::PathBuf>,
+    pub output_dir: Option<path::PathBuf>,
+    pub state_db_path: Option<path::PathBuf>,
+    // URL the output directory is served at, like https://example.com/feeds
+    pub base_url: Option<String>,
+    // WebSub hub that feeds advertise and get announced to
+    pub websub_hub: Option<String>,
+}
+
+impl Profile {
+    // Values set in `other` take precedence over the ones in self
+    fn merge(self, other: Profile) -> Profile {
+        Profile {
+            author: other.author.or(self.author),
+            roam_db_path: other.roam_db_path.or(self.roam_db_path),
+            notes_dir_path: other.notes_dir_path.or(self.notes_dir_path),
+            notes_max_depth: other.notes_max_depth.or(self.notes_max_depth),
+            notes_exclude: other.notes_exclude.or(self.notes_exclude),
+            timezone: other.timezone.or(self.timezone),
+            obsidian_vault_path: other.obsidian_vault_path.or(self.obsidian_vault_path),
+            output_dir: other.output_dir.or(self.output_dir),
+            state_db_path: other.state_db_path.or(self.state_db_path),
+            base_url: other.base_url.or(self.base_url),
+            websub_hub: other.websub_hub.or(self.websub_hub),
+        }
+    }
+
+    // Resolve a relative output file against the profile's output directory
+    pub fn output_path(&self, output_file: &path::Path) -> path::PathBuf {
+        match &self.output_dir {
+            Some(dir) if output_file.is_relative() => dir.join(output_file),
+            _ => output_file.to_path_buf(),
+        }
+    }
+}
+
+// Prefix for environment variables that override config keys
+const ENV_PREFIX: &str = "JOURNALIST_";
+
+impl Config {
+    fn read_table(file_path: &path::Path) -> Result<toml::Table> {
+        let body = fs::read_to_string(file_path)
+            .with_context(|| format!("Unable to read config file {}", file_path.display()))?;
+        toml::from_str(&body).with_context(|| format!("Unable to parse config file {}", file_path.display()))
+    }
+
+    // Load config from the given path, or from the default location if that
+    // exists. Running without any config file is fine and gives an empty one.
+    // JOURNALIST_* environment variables are applied on top in both cases.
+    pub fn load(file_path: Option<&path::Path>) -> Result<Self> {
+        let mut table = if let Some(file_path) = file_path {
+            Config::read_table(file_path)?
+        } else {
+            match default_config_path() {
+                Some(file_path) if file_path.exists() => Config::read_table(&file_path)?,
+                _ => toml::Table::new(),
+            }
+        };
+
+        let overrides = apply_env_overrides(&mut table, std::env::vars())?;
+        from_table(table, overrides)
+    }
+
+    // HTTP options for the given source, merged over the global ones
+    pub fn http_options(&self, source: &str) -> HttpOptions {
+        match self.sources.get(source) {
+            Some(source_config) => self.http.clone().merge(source_config.http.clone()),
+            None => self.http.clone(),
+        }
+    }
+
+    // Feed with the given id from the config, falling back to the builtin ones
+    pub fn feed(&self, id: &str) -> Option<FeedConfig> {
+        self.feeds.iter()
+            .find(|feed| feed.id == id)
+            .cloned()
+            .or_else(|| builtin_feeds().into_iter().find(|feed| feed.id == id))
+    }
+
+    // Return the named profile merged over the defaults. No name means only
+    // the defaults.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        match name {
+            None => Ok(self.defaults.clone()),
+            Some(name) => {
+                let profile = self.profiles
+                    .get(name)
+                    .ok_or_else(|| anyhow!("Profile {} not found in config", name))?;
+                Ok(self.defaults.clone().merge(profile.clone()))
+            }
+        }
+    }
+}
+
+// Apply overrides from variables like JOURNALIST_OUTPUT_DIR or
+// JOURNALIST_PROFILES__PARTNER__NOTES_DIR_PATH. Double underscores separate
+// nested keys and values are read as TOML scalars when they parse as one,
+// falling back to plain strings, and numbers index into lists like feeds.
+// Returns the keys and raw values of the ones read as something other than a
+// string, which might be meant as strings.
+fn apply_env_overrides(table: &mut toml::Table, vars: impl Iterator<Item = (String, String)>) -> Result<Vec<(Vec<String>, String)>> {
+    let mut typed = Vec::new();
+    for (name, raw_value) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+
+        let keys: Vec<String> = key.split("__").map(|k| k.to_lowercase()).collect();
+        if keys.iter().any(|k| k.is_empty()) {
+            continue;
+        }
+
+        let value = toml::from_str::<toml::Table>(&format!("value = {}", raw_value))
+            .ok()
+            .and_then(|mut t| t.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(raw_value.clone()));
+
+        set_value(table, &keys, value.clone()).with_context(|| format!("Unable to apply {}", name))?;
+        if !value.is_str() {
+            typed.push((keys, raw_value));
+        }
+    }
+    Ok(typed)
+}
+
+// Set the value at the keys, adding tables on the way. Values that aren't
+// tables or lists are never replaced by tables, and lists are only indexed
+// into, as there's no telling what the rest of a new item would be.
+fn set_value(table: &mut toml::Table, keys: &[String], value: toml::Value) -> Result<()> {
+    let (key, rest) = keys.split_first().unwrap();
+    if rest.is_empty() {
+        table.insert(key.clone(), value);
+        return Ok(());
+    }
+    if !table.contains_key(key) && rest[0].parse::<usize>().is_ok() {
+        return Err(anyhow!("There is no list {} to set item {} of", key, rest[0]));
+    }
+    let entry = table.entry(key.clone()).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    set_nested(entry, key, rest, value)
+}
+
+// Like set_value, inside the value at the key
+fn set_nested(current: &mut toml::Value, key: &str, keys: &[String], value: toml::Value) -> Result<()> {
+    match current {
+        toml::Value::Table(table) => set_value(table, keys, value),
+        toml::Value::Array(items) => {
+            let (index, rest) = keys.split_first().unwrap();
+            let count = items.len();
+            let item = index.parse::<usize>().ok()
+                .and_then(|index| items.get_mut(index))
+                .ok_or_else(|| anyhow!("{} is a list of {} items, which has no item {}", key, count, index))?;
+            if rest.is_empty() {
+                *item = value;
+                Ok(())
+            } else {
+                set_nested(item, index, rest, value)
+            }
+        },
+        _ => Err(anyhow!("{} is a {}, which has no {}", key, current.type_str(), keys[0])),
+    }
+}
+
+// Where in the table deserializing it as T fails, like notify.ntfy.topic
+fn error_path<T: serde::de::DeserializeOwned>(table: &toml::Table) -> Option<String> {
+    serde_path_to_error::deserialize::<_, T>(toml::Value::Table(table.clone())).err()
+        .map(|err| err.path().to_string())
+}
+
+// Parse the config, turning overrides that were read as numbers or booleans
+// back into strings where the field they set wants a string, like a Telegram
+// chat_id
+fn from_table(mut table: toml::Table, mut typed: Vec<(Vec<String>, String)>) -> Result<Config> {
+    loop {
+        let err = match serde_path_to_error::deserialize(toml::Value::Table(table.clone())) {
+            Ok(config) => return Ok(config),
+            Err(err) => err,
+        };
+        // Fields of the default profile are flattened into the top level,
+        // which loses the path to them
+        let paths = [Some(err.path().to_string()), error_path::<Profile>(&table)];
+        match typed.iter().position(|(keys, _)| paths.contains(&Some(keys.join(".")))) {
+            Some(index) => {
+                let (keys, raw_value) = typed.remove(index);
+                set_value(&mut table, &keys, toml::Value::String(raw_value))?;
+            },
+            None => return Err(err.into_inner()).context("Unable to parse config"),
+        }
+    }
+}
+
+// ~/.config/journalist/journalist.toml, honoring XDG_CONFIG_HOME
+fn default_config_path() -> Option<path::PathBuf> {
+    Some(utils::xdg_dir("XDG_CONFIG_HOME", ".config")?.join("journalist").join("journalist.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_override_defaults() {
+        let config: Config = toml::from_str(r#"
+notes_dir_path = "/notes"
+output_dir = "/feeds"
+
+[author]
+name = "Me"
+email = "me@example.com"
+uri = "example.com"
+
+[profiles.partner]
+notes_dir_path = "/partner/notes"
+"#).unwrap();
+
+        let profile = config.profile(Some("partner")).unwrap();
+        assert_eq!(profile.notes_dir_path, Some(path::PathBuf::from("/partner/notes")));
+        assert_eq!(profile.output_dir, Some(path::PathBuf::from("/feeds")));
+        assert_eq!(profile.author.unwrap().name, "Me");
+
+        assert!(config.profile(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn public_urls_come_from_links_or_outputs() {
+        let mut feed = FeedConfig::new("links", "pile", "Links", "");
+        assert_eq!(feed.public_url(None), None);
+        assert_eq!(feed.public_url(Some("https://example.com/feeds/")).unwrap(), "https://example.com/feeds/links.xml");
+
+        feed.outputs = vec![OutputConfig::new("-".into(), Format::Atom), OutputConfig::new("links.gmi".into(), Format::Gemtext)];
+        assert_eq!(feed.public_url(Some("https://example.com")), None);
+
+        feed.link = Some("https://example.com/links".to_string());
+        assert_eq!(feed.public_url(None).unwrap(), "https://example.com/links");
+    }
+
+    #[test]
+    fn configured_feeds_replace_builtin_ones() {
+        let config: Config = toml::from_str(r#"
+[[feeds]]
+id = "recommended-links"
+source = "pile"
+title = "My links"
+filters = ["recommended"]
+order = "newest"
+"#).unwrap();
+
+        let feed = config.feed("recommended-links").unwrap();
+        assert_eq!(feed.title, "My links");
+        assert_eq!(feed.order, FeedOrder::Newest);
+        assert_eq!(feed.output(), path::PathBuf::from("recommended-links.xml"));
+
+        assert_eq!(config.feed("hf-papers").unwrap().count, Some(5));
+        assert!(config.feed("missing").is_none());
+    }
+
+    #[test]
+    fn env_overrides_nested_keys() {
+        let mut table: toml::Table = toml::from_str(r#"
+output_dir = "/feeds"
+
+[profiles.partner]
+output_dir = "/partner/feeds"
+"#).unwrap();
+
+        let vars = vec![
+            ("JOURNALIST_OUTPUT_DIR".to_string(), "/srv/feeds".to_string()),
+            ("JOURNALIST_PROFILES__PARTNER__NOTES_DIR_PATH".to_string(), "/partner/notes".to_string()),
+            ("JOURNALIST_AUTHOR__NAME".to_string(), "Me".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ];
+        apply_env_overrides(&mut table, vars.into_iter()).unwrap();
+
+        assert_eq!(table["output_dir"].as_str(), Some("/srv/feeds"));
+        assert_eq!(table["profiles"]["partner"]["notes_dir_path"].as_str(), Some("/partner/notes"));
+        assert_eq!(table["profiles"]["partner"]["output_dir"].as_str(), Some("/partner/feeds"));
+        assert_eq!(table["author"]["name"].as_str(), Some("Me"));
+        assert!(!table.contains_key("home"));
+    }
+
+    #[test]
+    fn numeric_env_overrides_of_strings_stay_strings() {
+        let mut table: toml::Table = toml::from_str(r#"
+[author]
+name = "Me"
+email = "me@example.com"
+uri = "example.com"
+
+[telegram]
+bot_token = "token"
+chat_id = "@channel"
+"#).unwrap();
+
+        let vars = vec![
+            ("JOURNALIST_TELEGRAM__CHAT_ID".to_string(), "-1001234".to_string()),
+            ("JOURNALIST_AUTHOR__NAME".to_string(), "1984".to_string()),
+            ("JOURNALIST_HTTP__TIMEOUT".to_string(), "30".to_string()),
+        ];
+        let typed = apply_env_overrides(&mut table, vars.into_iter()).unwrap();
+        assert_eq!(typed.len(), 3);
+
+        let config = from_table(table, typed).unwrap();
+        assert_eq!(config.telegram.unwrap().chat_id, "-1001234");
+        assert_eq!(config.defaults.author.unwrap().name, "1984");
+        assert_eq!(config.http.timeout, Some(30));
+    }
+    #[test]
+    fn env_overrides_index_into_lists() {
+        let mut table: toml::Table = toml::from_str(r#"
+output_dir = "/feeds"
+
+[[feeds]]
+id = "links"
+source = "pile"
+title = "Links"
+"#).unwrap();
+
+        let vars = vec![("JOURNALIST_FEEDS__0__TITLE".to_string(), "My links".to_string())];
+        let typed = apply_env_overrides(&mut table, vars.into_iter()).unwrap();
+        let config = from_table(table.clone(), typed).unwrap();
+        assert_eq!(config.feeds[0].title, "My links");
+        assert_eq!(config.feeds[0].id, "links");
+
+        for name in ["JOURNALIST_FEEDS__1__TITLE", "JOURNALIST_FEEDS__FIRST__TITLE", "JOURNALIST_PROFILES__0__OUTPUT_DIR"] {
+            let err = apply_env_overrides(&mut table, vec![(name.to_string(), "x".to_string())].into_iter()).unwrap_err();
+            assert!(err.to_string().contains(name));
+        }
+        assert!(table["feeds"].is_array());
+    }
+
+    #[test]
+    fn env_overrides_keep_scalars() {
+        let mut table: toml::Table = toml::from_str(r#"output_dir = "/feeds""#).unwrap();
+
+        let vars = vec![("JOURNALIST_OUTPUT_DIR__NAME".to_string(), "x".to_string())];
+        let err = apply_env_overrides(&mut table, vars.into_iter()).unwrap_err();
+        assert!(err.to_string().contains("JOURNALIST_OUTPUT_DIR__NAME"));
+        assert_eq!(table["output_dir"].as_str(), Some("/feeds"));
+    }
+}
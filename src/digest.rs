@@ -0,0 +1,191 @@
+// Digests bundle the recent items of a feed into one document to read
+// offline, like an EPUB to put on an e-reader.
+
+use std::io::{Cursor, Write};
+use std::path;
+use anyhow::{anyhow, Context, Result};
+use chrono::TimeDelta;
+use clap::ValueEnum;
+use futures::future::join_all;
+use log::warn;
+use once_cell::sync::Lazy;
+use scraper::{Html, Selector};
+use tera::Tera;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::http::Fetcher;
+use crate::{html, sinks, NewsFeed, NewsItem};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Epub,
+    Html,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Epub => "epub",
+            Format::Html => "html",
+        }
+    }
+
+    pub fn write(&self, feed: &NewsFeed, file_path: &path::Path) -> Result<()> {
+        let content = match self {
+            Format::Epub => to_epub(feed)?,
+            Format::Html => html::to_html_string(feed).into_bytes(),
+        };
+        sinks::write_atomic(file_path, &content)
+    }
+}
+
+// A span of time like 48h, 7d, or 2w
+pub fn parse_since(since: &str) -> Result<TimeDelta> {
+    let invalid = || anyhow!("Invalid duration {}, expected something like 48h, 7d, or 2w", since);
+
+    let split = since.len().checked_sub(1).filter(|&split| since.is_char_boundary(split)).ok_or_else(invalid)?;
+    let count: i64 = since[..split].parse().map_err(|_| invalid())?;
+    match &since[split..] {
+        "h" => TimeDelta::try_hours(count),
+        "d" => TimeDelta::try_days(count),
+        "w" => TimeDelta::try_weeks(count),
+        _ => None,
+    }.ok_or_else(invalid)
+}
+
+// Replace the content of items with the text of the pages they link to.
+// Items whose page can't be fetched keep what they had.
+pub async fn fetch_full_text(fetcher: &Fetcher, items: &mut [NewsItem]) {
+    let pages = join_all(items.iter().map(|item| fetcher.get_text(&item.link))).await;
+
+    for (item, page) in items.iter_mut().zip(pages) {
+        match page {
+            Ok(page) => {
+                if let Some(text) = extract_text(&page) {
+                    item.content = Some(text);
+                }
+            },
+            Err(err) => warn!("Unable to fetch full text of {}: {:#}", item.link, err),
+        }
+    }
+}
+
+// Paragraphs of the main text of a page, preferring the ones in an article
+fn extract_text(page: &str) -> Option<String> {
+    let document = Html::parse_document(page);
+
+    ["article p", "main p", "p"].iter().find_map(|selector| {
+        let selector = Selector::parse(selector).unwrap();
+        let paragraphs: Vec<String> = document.select(&selector)
+            .map(|paragraph| paragraph.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|paragraph| !paragraph.is_empty())
+            .collect();
+        (!paragraphs.is_empty()).then(|| paragraphs.join("\n\n"))
+    })
+}
+
+static EPUB_TEMPLATES: Lazy<Tera> = Lazy::new(|| {
+    let mut tera = Tera::default();
+    tera.autoescape_on(vec![".opf", ".xhtml"]);
+    tera.set_escape_fn(crate::template::escape);
+    tera.add_raw_templates(vec![
+        ("content.opf", include_str!("templates/epub/content.opf")),
+        ("nav.xhtml", include_str!("templates/epub/nav.xhtml")),
+        ("item.xhtml", include_str!("templates/epub/item.xhtml")),
+    ]).unwrap();
+    tera
+});
+
+// An EPUB 3 book with a table of contents followed by a chapter per item
+pub fn to_epub(feed: &NewsFeed) -> Result<Vec<u8>> {
+    let mut context = tera::Context::new();
+    context.insert("feed", feed);
+    context.insert("uuid", &Uuid::new_v4().to_string());
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype has to come first and uncompressed for readers to
+    // recognize the file
+    zip.start_file("mimetype", SimpleFileOptions::default().compression_method(CompressionMethod::Stored))?;
+    zip.write_all(b"application/epub+zip")?;
+
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(include_bytes!("templates/epub/container.xml"))?;
+
+    for name in ["content.opf", "nav.xhtml"] {
+        zip.start_file(format!("OEBPS/{}", name), deflated)?;
+        zip.write_all(EPUB_TEMPLATES.render(name, &context).with_context(|| format!("Unable to render {}", name))?.as_bytes())?;
+    }
+
+    for (index, item) in feed.items.iter().enumerate() {
+        let text = item.content.as_ref().or(item.summary.as_ref()).map(String::as_str).unwrap_or_default();
+        let mut context = tera::Context::new();
+        context.insert("item", item);
+        context.insert("language", feed.language.as_deref().unwrap_or("en"));
+        context.insert("paragraphs", &text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()).collect::<Vec<_>>());
+
+        zip.start_file(format!("OEBPS/item-{}.xhtml", index + 1), deflated)?;
+        zip.write_all(EPUB_TEMPLATES.render("item.xhtml", &context)?.as_bytes())?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use chrono::Utc;
+
+    #[test]
+    fn durations_are_parsed() {
+        assert_eq!(parse_since("7d").unwrap(), TimeDelta::days(7));
+        assert_eq!(parse_since("48h").unwrap(), TimeDelta::hours(48));
+        assert_eq!(parse_since("2w").unwrap(), TimeDelta::weeks(2));
+        assert!(parse_since("7").is_err());
+        assert!(parse_since("d").is_err());
+        assert!(parse_since("1y").is_err());
+    }
+
+    #[test]
+    fn article_text_is_preferred() {
+        let page = "<html><body><p>Menu</p><article><p>First  line</p><p>Second <b>line</b></p></article></body></html>";
+        assert_eq!(extract_text(page).unwrap(), "First line\n\nSecond line");
+    }
+
+    #[test]
+    fn epub_has_a_chapter_per_item() {
+        let feed = NewsFeed {
+            id: "digest".to_string(),
+            updated: Utc::now(),
+            link: "/digest".to_string(),
+            title: "Week of reading".to_string(),
+            subtitle: String::new(),
+            items: vec![NewsItem {
+                id: "a".to_string(),
+                link: "https://example.com/a".to_string(),
+                title: "Fish & chips".to_string(),
+                summary: Some("Short".to_string()),
+                content: Some("One\n\nTwo".to_string()),
+                published: Utc::now(),
+                updated: Utc::now(),
+                ..Default::default()
+            }],
+            authors: Vec::new(),
+            categories: Vec::new(),
+            generator: "journalist".to_string(),
+            ..Default::default()
+        };
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(to_epub(&feed).unwrap())).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+
+        let mut chapter = String::new();
+        archive.by_name("OEBPS/item-1.xhtml").unwrap().read_to_string(&mut chapter).unwrap();
+        assert!(chapter.contains("<h1>Fish &amp; chips</h1>"));
+        assert!(chapter.contains("<p>Two</p>"));
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::state::State;
+use crate::NewsItem;
+
+// ISO week the date falls in, like 2024-W07
+fn week_key(date: NaiveDate) -> String {
+    let week = date.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+// Day offsets (from the first day) to release n items spread over the given
+// number of days.
+fn schedule_offsets(n: usize, days: usize) -> Vec<i64> {
+    (0..n).map(|i| (i * days / n) as i64).collect()
+}
+
+// Return the items of this week's drip schedule that are due by today. The
+// schedule is created on the first run of a week by picking weekly_count items
+// from the candidates and spreading them over the remaining days of the week.
+pub fn release(
+    state: &State,
+    feed_id: &str,
+    candidates: Vec<NewsItem>,
+    weekly_count: usize,
+    today: NaiveDate,
+) -> Result<Vec<NewsItem>> {
+    let week = week_key(today);
+    let mut scheduled = state.drip_items(feed_id, &week)?;
+
+    if scheduled.is_empty() {
+        let picked: Vec<NewsItem> = candidates.into_iter().take(weekly_count).collect();
+        let days_left = 7 - today.weekday().num_days_from_monday() as usize;
+
+        scheduled = picked.iter()
+            .zip(schedule_offsets(picked.len(), days_left))
+            .map(|(item, offset)| Ok((today + Duration::days(offset), serde_json::to_string(item)?)))
+            .collect::<Result<_>>()?;
+        state.save_drip_items(feed_id, &week, &scheduled)?;
+    }
+
+    scheduled.into_iter()
+        .filter(|(release_on, _)| *release_on <= today)
+        .map(|(_, item)| Ok(serde_json::from_str(&item)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_are_spread_over_days() {
+        assert_eq!(schedule_offsets(7, 7), vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(schedule_offsets(3, 7), vec![0, 2, 4]);
+        assert_eq!(schedule_offsets(4, 2), vec![0, 0, 1, 1]);
+        assert_eq!(week_key(NaiveDate::from_ymd_opt(2024, 2, 14).unwrap()), "2024-W07");
+    }
+}
@@ -0,0 +1,394 @@
+use std::str::FromStr;
+use anyhow::{anyhow, Context, Result};
+use chrono::NaiveDate;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::NewsItem;
+
+// What marks bookmarks as unread, projects, or recommended, for the filters of
+// the same names, in [bookmarks]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BookmarkConfig {
+    // Bookmarks start out tagged with one of these
+    pub unread_tags: Vec<String>,
+    pub project_tags: Vec<String>,
+    // Links starting with these are projects whatever their tags
+    pub project_links: Vec<String>,
+    // Read bookmarks with one of these are recommended
+    pub recommended_tags: Vec<String>,
+}
+
+impl Default for BookmarkConfig {
+    fn default() -> Self {
+        BookmarkConfig {
+            unread_tags: vec!["unsorted".to_string()],
+            project_tags: vec!["project".to_string()],
+            project_links: vec!["https://github.com".to_string()],
+            recommended_tags: vec!["recommend".to_string()],
+        }
+    }
+}
+
+// A predicate on items, written in the config or on the command line as
+//
+//   unread, read, project, recommended   bookmark states, from tags and links
+//                                        as set in BookmarkConfig
+//   tag:<name>                           has the tag
+//   url:<regex>, ref~<regex>             link matches the pattern
+//   title~<regex>                        title matches the pattern
+//   after:<YYYY-MM-DD>                   published on or after the date
+//   before:<YYYY-MM-DD>                  published before the date
+//   created<op><YYYY-MM-DD>              published compared to the date, with
+//                                        op one of > >= < <= =
+//   lang:<code>                          in the language, like en or de
+//
+// A `!` prefix negates any of these. They can be combined into expressions
+// with AND, OR, NOT, and parentheses, like
+//
+//   tag:unsorted AND NOT tag:project AND ref~"arxiv.org" AND created>2024-01-01
+//
+// with double quotes around values that have spaces or parentheses in them.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Tag(String),
+    AnyTag(Vec<String>),
+    LinkPrefix(Vec<String>),
+    Url(Regex),
+    Title(Regex),
+    After(NaiveDate),
+    Before(NaiveDate),
+    Language(String),
+    Not(Box<Filter>),
+    Any(Vec<Filter>),
+    All(Vec<Filter>),
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(filter: &str) -> Result<Self> {
+        Filter::parse(filter, &BookmarkConfig::default())
+    }
+}
+
+impl Filter {
+    pub fn unread(bookmarks: &BookmarkConfig) -> Self {
+        Filter::AnyTag(bookmarks.unread_tags.clone())
+    }
+
+    pub fn project(bookmarks: &BookmarkConfig) -> Self {
+        Filter::Any(vec![Filter::AnyTag(bookmarks.project_tags.clone()), Filter::LinkPrefix(bookmarks.project_links.clone())])
+    }
+
+    pub fn recommended(bookmarks: &BookmarkConfig) -> Self {
+        Filter::All(vec![Filter::AnyTag(bookmarks.recommended_tags.clone()), Filter::Not(Box::new(Filter::unread(bookmarks)))])
+    }
+
+    // Parse a filter or an expression of them, with bookmark states as
+    // defined in the config
+    pub fn parse(filter: &str, bookmarks: &BookmarkConfig) -> Result<Self> {
+        let mut parser = ExpressionParser { tokens: tokenize(filter)?, position: 0, bookmarks };
+        let expression = parser.or()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(expression),
+            Some(token) => Err(anyhow!("Unexpected {} in filter {}", token.text, filter)),
+        }
+    }
+
+    fn parse_single(filter: &str, bookmarks: &BookmarkConfig) -> Result<Self> {
+        if let Some(rest) = filter.strip_prefix('!') {
+            return Ok(Filter::Not(Box::new(Filter::parse_single(rest, bookmarks)?)));
+        }
+
+        let parse_date = |date: &str| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .with_context(|| format!("Invalid date in filter {}, expected YYYY-MM-DD", filter))
+        };
+
+        if let Some(captures) = COMPARISON_REGEX.captures(filter) {
+            let value = &captures[3];
+            return match (&captures[1], &captures[2]) {
+                ("ref" | "link" | "url", "~") => Ok(Filter::Url(Regex::new(value)?)),
+                ("title", "~") => Ok(Filter::Title(Regex::new(value)?)),
+                ("created" | "published", op) => {
+                    let date = parse_date(value)?;
+                    let next_day = date.succ_opt().ok_or_else(|| anyhow!("Invalid date in filter {}", filter))?;
+                    Ok(match op {
+                        ">" => Filter::After(next_day),
+                        ">=" => Filter::After(date),
+                        "<" => Filter::Before(date),
+                        "<=" => Filter::Before(next_day),
+                        "=" => Filter::All(vec![Filter::After(date), Filter::Before(next_day)]),
+                        _ => return Err(anyhow!("Dates can't be compared with {} in filter {}", op, filter)),
+                    })
+                },
+                (field, op) => Err(anyhow!("{} can't be compared with {} in filter {}", field, op, filter)),
+            };
+        }
+
+        match filter.split_once(':') {
+            None => match filter {
+                "unread" => Ok(Filter::unread(bookmarks)),
+                "read" => Ok(Filter::Not(Box::new(Filter::unread(bookmarks)))),
+                "project" => Ok(Filter::project(bookmarks)),
+                "recommended" => Ok(Filter::recommended(bookmarks)),
+                _ => Err(anyhow!("Unknown filter {}", filter)),
+            },
+            Some(("tag", tag)) => Ok(Filter::Tag(tag.to_string())),
+            Some(("url", pattern)) => Ok(Filter::Url(Regex::new(pattern)?)),
+            Some(("after", date)) => Ok(Filter::After(parse_date(date)?)),
+            Some(("before", date)) => Ok(Filter::Before(parse_date(date)?)),
+            Some(("lang", language)) => Ok(Filter::Language(language.to_string())),
+            Some((kind, _)) => Err(anyhow!("Unknown filter kind {} in {}", kind, filter)),
+        }
+    }
+}
+
+// A field, an operator, and the value to compare with, like created>2024-01-01
+static COMPARISON_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(ref|link|url|title|created|published)(~|>=|<=|>|<|=)(.*)$").unwrap()
+});
+
+struct Token {
+    text: String,
+    // Parentheses and operators are only what they look like when not quoted
+    quoted: bool,
+}
+
+// Split an expression into filters, operators, and parentheses. A parenthesis
+// inside a filter, like in url:(a|b), is part of the filter.
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(Token { text: c.to_string(), quoted: false });
+        } else {
+            let mut text = String::new();
+            let mut quoted = false;
+            // Parentheses opened within the filter
+            let mut depth = 0;
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || (c == ')' && depth == 0) {
+                    break;
+                }
+                chars.next();
+                match c {
+                    '"' => {
+                        quoted = true;
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some(c) => text.push(c),
+                                None => return Err(anyhow!("Unclosed quote in filter {}", expression)),
+                            }
+                        }
+                    },
+                    '(' => {
+                        depth += 1;
+                        text.push(c);
+                    },
+                    ')' => {
+                        depth -= 1;
+                        text.push(c);
+                    },
+                    _ => text.push(c),
+                }
+            }
+            tokens.push(Token { text, quoted });
+        }
+    }
+    Ok(tokens)
+}
+
+// Recursive descent over the tokens, with NOT binding tighter than AND, and
+// AND tighter than OR
+struct ExpressionParser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    bookmarks: &'a BookmarkConfig,
+}
+
+impl ExpressionParser<'_> {
+    fn next_is(&self, text: &str) -> bool {
+        self.tokens.get(self.position).is_some_and(|token| !token.quoted && token.text == text)
+    }
+
+    fn or(&mut self) -> Result<Filter> {
+        let mut filters = vec![self.and()?];
+        while self.next_is("OR") {
+            self.position += 1;
+            filters.push(self.and()?);
+        }
+        Ok(if filters.len() == 1 { filters.remove(0) } else { Filter::Any(filters) })
+    }
+
+    fn and(&mut self) -> Result<Filter> {
+        let mut filters = vec![self.not()?];
+        while self.next_is("AND") {
+            self.position += 1;
+            filters.push(self.not()?);
+        }
+        Ok(if filters.len() == 1 { filters.remove(0) } else { Filter::All(filters) })
+    }
+
+    fn not(&mut self) -> Result<Filter> {
+        if self.next_is("NOT") {
+            self.position += 1;
+            return Ok(Filter::Not(Box::new(self.not()?)));
+        }
+        if self.next_is("(") {
+            self.position += 1;
+            let filter = self.or()?;
+            if !self.next_is(")") {
+                return Err(anyhow!("Missing ) in filter"));
+            }
+            self.position += 1;
+            return Ok(filter);
+        }
+
+        match self.tokens.get(self.position) {
+            Some(token) if token.quoted || !["AND", "OR", ")"].contains(&token.text.as_str()) => {
+                self.position += 1;
+                Filter::parse_single(&token.text, self.bookmarks)
+            },
+            Some(token) => Err(anyhow!("Expected a filter before {}", token.text)),
+            None => Err(anyhow!("Expected a filter at the end")),
+        }
+    }
+}
+
+fn has_tag(item: &NewsItem, tag: &str) -> bool {
+    item.categories.iter().any(|category| category == tag)
+}
+
+impl Filter {
+    pub fn matches(&self, item: &NewsItem) -> bool {
+        match self {
+            Filter::Tag(tag) => has_tag(item, tag),
+            Filter::AnyTag(tags) => tags.iter().any(|tag| has_tag(item, tag)),
+            Filter::LinkPrefix(prefixes) => prefixes.iter().any(|prefix| item.link.starts_with(prefix.as_str())),
+            Filter::Url(pattern) => pattern.is_match(&item.link),
+            Filter::Title(pattern) => pattern.is_match(&item.title),
+            Filter::After(date) => item.published.date_naive() >= *date,
+            Filter::Before(date) => item.published.date_naive() < *date,
+            // Items of unknown language don't match
+            Filter::Language(language) => item.language.as_deref().is_some_and(|tag| crate::language::matches(tag, language)),
+            Filter::Not(filter) => !filter.matches(item),
+            Filter::Any(filters) => filters.iter().any(|filter| filter.matches(item)),
+            Filter::All(filters) => filters.iter().all(|filter| filter.matches(item)),
+        }
+    }
+}
+
+// Filters that items need to pass all of
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    filters: Vec<Filter>,
+}
+
+impl Pipeline {
+    pub fn parse(filters: &[String], bookmarks: &BookmarkConfig) -> Result<Self> {
+        Ok(Pipeline {
+            filters: filters.iter().map(|filter| Filter::parse(filter, bookmarks)).collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn matches(&self, item: &NewsItem) -> bool {
+        self.filters.iter().all(|filter| filter.matches(item))
+    }
+
+    pub fn apply(&self, items: Vec<NewsItem>) -> Vec<NewsItem> {
+        items.into_iter().filter(|item| self.matches(item)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn item(link: &str, tags: &[&str], year: i32) -> NewsItem {
+        let published = Utc.with_ymd_and_hms(year, 6, 1, 0, 0, 0).unwrap();
+        NewsItem {
+            id: link.to_string(),
+            link: link.to_string(),
+            title: link.to_string(),
+            published,
+            updated: published,
+            categories: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn expressions_combine_filters() {
+        let items = [
+            item("https://arxiv.org/abs/1", &["unsorted", "ml"], 2024),
+            item("https://arxiv.org/abs/2", &["unsorted", "project"], 2024),
+            item("https://example.com/(a)", &["unsorted"], 2023),
+            item("https://github.com/a/b", &["machine learning"], 2024),
+        ];
+        let ids = |expression: &str| {
+            let filter: Filter = expression.parse().unwrap();
+            items.iter().filter(|item| filter.matches(item)).map(|item| item.id.as_str()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(r#"tag:unsorted AND NOT tag:project AND ref~"arxiv.org" AND created>2024-01-01"#), vec!["https://arxiv.org/abs/1"]);
+        assert_eq!(ids("created<2024-01-01 OR (tag:ml AND NOT project)"), vec!["https://arxiv.org/abs/1", "https://example.com/(a)"]);
+        assert_eq!(ids(r#"tag:"machine learning" OR url:\(a\)"#), vec!["https://example.com/(a)", "https://github.com/a/b"]);
+        assert_eq!(ids("created=2023-06-01"), vec!["https://example.com/(a)"]);
+        assert_eq!(ids("NOT unread"), vec!["https://github.com/a/b"]);
+    }
+
+    #[test]
+    fn filters_compose() {
+        let items = vec![
+            item("https://github.com/a/b", &["unsorted"], 2024),
+            item("https://example.com/post", &["unsorted", "speech"], 2023),
+            item("https://example.com/old", &["recommend"], 2022),
+        ];
+
+        let ids = |filters: &[&str]| {
+            let filters: Vec<String> = filters.iter().map(|f| f.to_string()).collect();
+            Pipeline::parse(&filters, &BookmarkConfig::default()).unwrap()
+                .apply(items.clone())
+                .into_iter()
+                .map(|it| it.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(&["unread", "!project"]), vec!["https://example.com/post"]);
+        assert_eq!(ids(&["recommended"]), vec!["https://example.com/old"]);
+        assert_eq!(ids(&["!tag:speech", "url:example\\.com"]), vec!["https://example.com/old"]);
+        assert_eq!(ids(&["after:2023-01-01", "before:2024-01-01"]), vec!["https://example.com/post"]);
+        assert_eq!(ids(&["read"]).len(), 1);
+
+        let mut german = item("https://example.com/de", &[], 2024);
+        german.language = Some("de".to_string());
+        assert!("lang:de".parse::<Filter>().unwrap().matches(&german));
+        assert!(!"lang:de".parse::<Filter>().unwrap().matches(&items[0]));
+
+        // Notes with other conventions
+        let bookmarks = BookmarkConfig {
+            unread_tags: vec!["inbox".to_string()],
+            project_links: vec!["https://codeberg.org".to_string()],
+            ..Default::default()
+        };
+        let codeberg = item("https://codeberg.org/a/b", &["inbox"], 2024);
+        assert!(Filter::parse("unread", &bookmarks).unwrap().matches(&codeberg));
+        assert!(Filter::parse("project", &bookmarks).unwrap().matches(&codeberg));
+        assert!(!Filter::parse("project", &bookmarks).unwrap().matches(&items[0]));
+
+        assert!("size:10".parse::<Filter>().is_err());
+        assert!("tag:a AND".parse::<Filter>().is_err());
+        assert!("(tag:a OR tag:b".parse::<Filter>().is_err());
+        assert!("after:yesterday".parse::<Filter>().is_err());
+    }
+}
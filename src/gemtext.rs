@@ -0,0 +1,74 @@
+// Gemtext (text/gemini) pages for NewsFeeds, for publishing on a Gemini
+// capsule
+
+use crate::NewsFeed;
+
+// Text lines starting with these would be read as links, headings, and so on
+const LINE_MARKERS: [&str; 5] = ["=>", "#", "*", ">", "```"];
+
+fn text_line(line: &str) -> String {
+    let line = line.trim_end();
+    if LINE_MARKERS.iter().any(|marker| line.starts_with(marker)) {
+        format!(" {}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+// Each item becomes a link line labelled with its date and title, followed by
+// its summary
+pub fn to_gemtext_string(feed: &NewsFeed) -> String {
+    let mut text = format!("# {}\n", feed.title.trim());
+    if !feed.subtitle.is_empty() {
+        text.push_str(&format!("\n{}\n", feed.subtitle.lines().map(text_line).collect::<Vec<_>>().join("\n")));
+    }
+
+    for item in &feed.items {
+        text.push_str(&format!("\n=> {} {} {}\n", item.link, item.published.format("%Y-%m-%d"), item.title.trim()));
+        if let Some(summary) = &item.summary {
+            for line in summary.lines() {
+                text.push_str(&text_line(line));
+                text.push('\n');
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use crate::NewsItem;
+
+    #[test]
+    fn items_are_link_lines() {
+        let published = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let feed = NewsFeed {
+            id: "links".to_string(),
+            updated: published,
+            link: "/links.gmi".to_string(),
+            title: "Links".to_string(),
+            subtitle: String::new(),
+            items: vec![NewsItem {
+                id: "a".to_string(),
+                link: "https://example.com/a".to_string(),
+                title: "An article".to_string(),
+                summary: Some("Worth reading\n# not a heading".to_string()),
+                published,
+                updated: published,
+                ..Default::default()
+            }],
+            authors: Vec::new(),
+            categories: Vec::new(),
+            generator: "journalist".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            to_gemtext_string(&feed),
+            "# Links\n\n=> https://example.com/a 2024-03-01 An article\nWorth reading\n # not a heading\n"
+        );
+    }
+}
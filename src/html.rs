@@ -0,0 +1,63 @@
+// Standalone HTML pages for NewsFeeds, for reading a feed in a browser. They
+// are marked up as an h-feed of h-entries (microformats2) so that IndieWeb
+// readers and webmention tools can use them as they are.
+
+use once_cell::sync::Lazy;
+use tera::{Context, Tera};
+
+use crate::{template, NewsFeed};
+
+// Templates named *.html get their variables escaped by Tera, though without
+// turning slashes in links into entities
+static TEMPLATES: Lazy<Tera> = Lazy::new(|| {
+    let mut tera = Tera::default();
+    tera.set_escape_fn(template::escape);
+    tera.add_raw_template("digest.html", include_str!("templates/digest.html")).unwrap();
+    tera
+});
+
+pub fn to_html_string(feed: &NewsFeed) -> String {
+    let mut context = Context::new();
+    context.insert("feed", feed);
+    TEMPLATES.render("digest.html", &context).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::NewsItem;
+
+    #[test]
+    fn entries_are_escaped() {
+        let feed = NewsFeed {
+            id: "test".to_string(),
+            updated: Utc::now(),
+            link: "/test.html".to_string(),
+            title: "Links & such".to_string(),
+            subtitle: String::new(),
+            items: vec![NewsItem {
+                id: "a".to_string(),
+                link: "https://example.com/a".to_string(),
+                title: "<script>".to_string(),
+                summary: Some("Worth a read".to_string()),
+                published: Utc::now(),
+                updated: Utc::now(),
+                categories: vec!["ml".to_string()],
+                ..Default::default()
+            }],
+            authors: Vec::new(),
+            categories: Vec::new(),
+            generator: "journalist".to_string(),
+            ..Default::default()
+        };
+
+        let html = to_html_string(&feed);
+        assert!(html.contains("<title>Links &amp; such</title>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("Worth a read"));
+        assert!(html.contains(r#"<span class="tag p-category">ml</span>"#));
+        assert!(html.contains(r#"<article class="h-entry">"#));
+        assert!(html.contains(r#"<h2 class="p-name"><a class="u-url" href="https://example.com/a">"#));
+    }
+}
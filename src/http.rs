@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::{fmt, fs, path};
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use reqwest::Client;
+use reqwest::{header, Url};
+use uuid::Uuid;
+
+use crate::config::HttpOptions;
+use crate::robots::{self, Robots};
+use crate::utils;
+
+const DEFAULT_TIMEOUT: u64 = 60;
+const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+// Some sites only serve browsers, so that is who we are unless configured
+// otherwise
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36";
+
+// Error returned in offline mode when a URL has never been fetched before.
+// Callers can downcast to this to degrade softly instead of failing the run.
+#[derive(Debug, Clone)]
+pub struct NotCached {
+    pub url: String,
+}
+
+impl fmt::Display for NotCached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not available in the offline cache", self.url)
+    }
+}
+
+impl std::error::Error for NotCached {}
+
+// Error returned when robots.txt of the host disallows fetching a URL
+#[derive(Debug)]
+pub struct Disallowed {
+    pub url: String,
+}
+
+impl fmt::Display for Disallowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "robots.txt disallows fetching {}", self.url)
+    }
+}
+
+impl std::error::Error for Disallowed {}
+
+// Shared HTTP client for network sources. Every successful response is kept
+// in the cache directory so that a later run with --offline can be served
+// entirely from disk. robots.txt of each host is fetched once per run and
+// kept in memory. The client is async so that sources can fetch
+// concurrently.
+pub struct Fetcher {
+    client: Client,
+    cache_dir: Option<path::PathBuf>,
+    offline: bool,
+    max_response_size: Option<u64>,
+    respect_robots: bool,
+    // Agent to pick robots.txt groups for
+    robots_agent: String,
+    robots: Mutex<HashMap<String, Robots>>,
+}
+
+impl Fetcher {
+    pub fn new(offline: bool, options: &HttpOptions) -> Result<Self> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert("Accept", header::HeaderValue::from_static("text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7"));
+        headers.insert("Accept-Language", header::HeaderValue::from_static("en-US,en;q=0.9"));
+        headers.insert("Cache-Control", header::HeaderValue::from_static("no-cache"));
+        headers.insert("Pragma", header::HeaderValue::from_static("no-cache"));
+
+        let client = Client::builder()
+            .user_agent(options.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT))
+            .default_headers(headers)
+            .timeout(Duration::from_secs(options.timeout.unwrap_or(DEFAULT_TIMEOUT)))
+            .connect_timeout(Duration::from_secs(options.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT)))
+            .build()?;
+
+        Ok(Fetcher {
+            client,
+            cache_dir: utils::xdg_dir("XDG_CACHE_HOME", ".cache").map(|dir| dir.join("journalist").join("http")),
+            offline,
+            max_response_size: options.max_response_size,
+            respect_robots: !options.ignore_robots.unwrap_or(false),
+            robots_agent: options.user_agent.clone().unwrap_or_else(|| robots::AGENT.to_string()),
+            robots: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn cache_path(&self, url: &str) -> Option<path::PathBuf> {
+        let key = Uuid::new_v3(&Uuid::NAMESPACE_URL, url.as_bytes());
+        self.cache_dir.as_ref().map(|dir| dir.join(key.to_string()))
+    }
+
+    // GET the url and return the body as text. In offline mode this only
+    // reads from the cache and returns a NotCached error on a miss.
+    pub async fn get_text(&self, url: &str) -> Result<String> {
+        let cache_path = self.cache_path(url);
+
+        if self.offline {
+            return match cache_path {
+                Some(cache_path) if cache_path.exists() => {
+                    debug!("Reading {} from cache", url);
+                    Ok(fs::read_to_string(cache_path)?)
+                },
+                _ => Err(NotCached { url: url.to_string() }.into()),
+            };
+        }
+
+        if self.respect_robots && !self.is_allowed(url).await? {
+            return Err(Disallowed { url: url.to_string() }.into());
+        }
+
+        let response = self.client.get(url)
+            .send()
+            .await?
+            .error_for_status()?;
+        let body = self.read_body(url, response).await?;
+
+        if let Some(cache_path) = cache_path {
+            if let Err(err) = write_cache(&cache_path, &body) {
+                warn!("Unable to cache response for {}: {}", url, err);
+            }
+        }
+
+        Ok(body)
+    }
+
+    // Check the url against robots.txt of its host. A missing robots.txt is
+    // taken as allowing everything. One that can't be read because of server
+    // or network errors disallows everything until a later check reads it, as
+    // RFC 9309 asks.
+    async fn is_allowed(&self, url: &str) -> Result<bool> {
+        let parsed = Url::parse(url)?;
+        let origin = parsed.origin().ascii_serialization();
+
+        let cached = self.robots.lock().unwrap().get(&origin).cloned();
+        let robots = match cached {
+            Some(robots) => robots,
+            None => match self.fetch_robots(&origin).await {
+                Some(robots) => {
+                    self.robots.lock().unwrap().insert(origin, robots.clone());
+                    robots
+                },
+                None => return Ok(false),
+            },
+        };
+
+        let path = match parsed.query() {
+            Some(query) => format!("{}?{}", parsed.path(), query),
+            None => parsed.path().to_string(),
+        };
+        Ok(robots.is_allowed(&path))
+    }
+
+    // Rules of the origin, or None when they can't be known for now
+    async fn fetch_robots(&self, origin: &str) -> Option<Robots> {
+        let robots_url = format!("{}/robots.txt", origin);
+        let response = match self.client.get(&robots_url).send().await {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("Not able to read {}, assuming disallowed for now: {}", robots_url, err);
+                return None;
+            }
+        };
+
+        let status = response.status();
+        if status.is_server_error() {
+            warn!("Not able to read {}, assuming disallowed for now: {}", robots_url, status);
+            return None;
+        }
+        if !status.is_success() {
+            debug!("No usable {}, assuming allowed: {}", robots_url, status);
+            return Some(Robots::default());
+        }
+        match response.text().await {
+            Ok(body) => Some(Robots::parse(&body, &self.robots_agent)),
+            Err(err) => {
+                warn!("Not able to read {}, assuming disallowed for now: {}", robots_url, err);
+                None
+            },
+        }
+    }
+
+    // Read the response body while enforcing max_response_size. The declared
+    // content length is checked upfront, but since servers can lie or stream,
+    // the read itself is also capped.
+    async fn read_body(&self, url: &str, mut response: reqwest::Response) -> Result<String> {
+        let Some(limit) = self.max_response_size else {
+            return Ok(response.text().await?);
+        };
+
+        if response.content_length().is_some_and(|length| length > limit) {
+            return Err(anyhow!("Response from {} is larger than {} bytes", url, limit));
+        }
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+            if bytes.len() as u64 > limit {
+                return Err(anyhow!("Response from {} is larger than {} bytes", url, limit));
+            }
+        }
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+fn write_cache(cache_path: &path::Path, body: &str) -> Result<()> {
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent).context("Unable to create cache directory")?;
+    }
+    fs::write(cache_path, body)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn robots_server_errors_disallow_until_read() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let origin = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let response = match served.fetch_add(1, Ordering::SeqCst) {
+                    0 => tiny_http::Response::from_string("").with_status_code(503),
+                    _ => tiny_http::Response::from_string("User-agent: *\nDisallow: /\n\nUser-agent: reader\nDisallow: /private\n"),
+                };
+                let _ = request.respond(response);
+            }
+        });
+
+        let options = HttpOptions { user_agent: Some("reader/1.0".to_string()), ..Default::default() };
+        let fetcher = Fetcher::new(false, &options).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            assert!(!fetcher.is_allowed(&format!("{}/page", origin)).await.unwrap());
+            assert!(fetcher.is_allowed(&format!("{}/page", origin)).await.unwrap());
+            assert!(!fetcher.is_allowed(&format!("{}/private/page", origin)).await.unwrap());
+        });
+        // Rules are kept once read
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,105 @@
+// iCalendar (RFC 5545) files with an event per item, for scheduling reading
+// time in a calendar
+
+use chrono::{DateTime, Utc};
+
+use crate::NewsFeed;
+
+// Length of the events, as readers get to move them around anyway
+const DURATION: &str = "PT30M";
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// Lines longer than 75 bytes are folded onto continuation lines starting with
+// a space, without splitting characters
+fn push_line(ics: &mut String, line: &str) {
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            length = 1;
+        }
+        ics.push(c);
+        length += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+// Each item is an event starting when it was published, with its link and
+// summary in the description
+pub fn to_ics_string(feed: &NewsFeed) -> String {
+    let mut ics = String::new();
+    push_line(&mut ics, "BEGIN:VCALENDAR");
+    push_line(&mut ics, "VERSION:2.0");
+    push_line(&mut ics, &format!("PRODID:-//{}//{}//EN", feed.generator, escape(&feed.id)));
+    push_line(&mut ics, &format!("X-WR-CALNAME:{}", escape(&feed.title)));
+
+    for item in &feed.items {
+        let description = match &item.summary {
+            Some(summary) => format!("{}\n\n{}", item.link, summary),
+            None => item.link.clone(),
+        };
+
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(&mut ics, &format!("UID:{}", escape(&item.id)));
+        push_line(&mut ics, &format!("DTSTAMP:{}", timestamp(&item.updated)));
+        push_line(&mut ics, &format!("DTSTART:{}", timestamp(&item.published)));
+        push_line(&mut ics, &format!("DURATION:{}", DURATION));
+        push_line(&mut ics, &format!("SUMMARY:{}", escape(&item.title)));
+        push_line(&mut ics, &format!("URL:{}", item.link));
+        push_line(&mut ics, &format!("DESCRIPTION:{}", escape(&description)));
+        if !item.categories.is_empty() {
+            push_line(&mut ics, &format!("CATEGORIES:{}", item.categories.iter().map(|category| escape(category)).collect::<Vec<_>>().join(",")));
+        }
+        push_line(&mut ics, "END:VEVENT");
+    }
+
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::NewsItem;
+
+    #[test]
+    fn items_become_events() {
+        let published = Utc.with_ymd_and_hms(2024, 6, 1, 9, 30, 0).unwrap();
+        let feed = NewsFeed {
+            id: "unread".to_string(),
+            title: "Unread".to_string(),
+            generator: "journalist".to_string(),
+            items: vec![NewsItem {
+                id: "a".to_string(),
+                link: "https://example.com/a".to_string(),
+                title: "Cats, dogs; and more".to_string(),
+                summary: Some("A long summary that goes on and on about everything there is to say about pets".to_string()),
+                published,
+                updated: published,
+                categories: vec!["pets".to_string(), "reading".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let ics = to_ics_string(&feed);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.contains("DTSTART:20240601T093000Z\r\n"));
+        assert!(ics.contains("SUMMARY:Cats\\, dogs\\; and more\r\n"));
+        assert!(ics.contains("CATEGORIES:pets,reading\r\n"));
+        assert!(ics.lines().all(|line| line.trim_end_matches('\r').len() <= 75));
+        assert!(ics.replace("\r\n ", "").contains("there is to say about pets\r\n"));
+    }
+}
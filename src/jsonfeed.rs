@@ -0,0 +1,177 @@
+// JSON Feed 1.1 (https://jsonfeed.org/version/1.1) documents for NewsFeeds
+
+use serde::Serialize;
+
+use crate::{NewsAuthor, NewsFeed, NewsItem};
+
+const VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+#[derive(Serialize)]
+pub struct Author {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Attachment {
+    pub url: String,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_in_bytes: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct Item {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    // Items need one of content_text or content_html
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    pub date_published: String,
+    pub date_modified: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<Author>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Feed {
+    pub version: &'static str,
+    pub title: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub feed_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    // Older entries of a paged feed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_url: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<Author>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub items: Vec<Item>,
+}
+
+impl From<&NewsAuthor> for Author {
+    fn from(author: &NewsAuthor) -> Self {
+        Author {
+            name: author.name.clone(),
+            url: Some(author.uri.clone()).filter(|uri| !uri.is_empty()),
+        }
+    }
+}
+
+impl From<&NewsItem> for Item {
+    fn from(item: &NewsItem) -> Self {
+        // The full text goes in content_text when there is one, with the
+        // summary next to it. Otherwise the summary is all the content.
+        let (content, summary) = match (&item.content, &item.summary) {
+            (Some(content), summary) => (content.clone(), summary.clone()),
+            (None, summary) => (summary.clone().unwrap_or_default(), None),
+        };
+        let (content_text, content_html) = if item.content_html { (None, Some(content)) } else { (Some(content), None) };
+
+        Item {
+            id: item.id.clone(),
+            url: item.link.clone(),
+            title: item.title.clone(),
+            content_text,
+            content_html,
+            summary,
+            date_published: item.published.to_rfc3339(),
+            date_modified: item.updated.to_rfc3339(),
+            tags: item.categories.clone(),
+            authors: item.authors.iter().map(Author::from).collect(),
+            attachments: item.enclosures.iter()
+                .map(|enclosure| Attachment { url: enclosure.url.clone(), mime_type: enclosure.mime_type.clone(), size_in_bytes: enclosure.length })
+                .collect(),
+            language: item.language.clone(),
+        }
+    }
+}
+
+impl From<&NewsFeed> for Feed {
+    fn from(feed: &NewsFeed) -> Self {
+        Feed {
+            version: VERSION,
+            title: feed.title.clone(),
+            description: feed.subtitle.clone(),
+            feed_url: feed.link.clone(),
+            // Atom's icon is the small one, JSON Feed's the large one
+            icon: feed.logo.clone(),
+            favicon: feed.icon.clone(),
+            next_url: feed.links.iter()
+                .find(|link| link.rel == "next" || link.rel == "prev-archive")
+                .map(|link| link.href.clone()),
+            authors: feed.authors.iter().map(Author::from).collect(),
+            language: feed.language.clone(),
+            items: feed.items.iter().map(Item::from).collect(),
+        }
+    }
+}
+
+pub fn to_json_string(feed: &NewsFeed) -> String {
+    serde_json::to_string_pretty(&Feed::from(feed)).unwrap()
+}
+
+pub fn to_compact_json_string(feed: &NewsFeed) -> String {
+    serde_json::to_string(&Feed::from(feed)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn items_have_text_tags_and_authors() {
+        let author = NewsAuthor { name: "Ada".to_string(), email: String::new(), uri: "https://example.com/ada".to_string() };
+        let feed = NewsFeed {
+            id: "test".to_string(),
+            updated: Utc::now(),
+            link: "/test.json".to_string(),
+            title: "Test".to_string(),
+            subtitle: String::new(),
+            items: vec![NewsItem {
+                id: "a".to_string(),
+                link: "https://example.com/a".to_string(),
+                title: "A".to_string(),
+                summary: Some("Short".to_string()),
+                published: Utc::now(),
+                updated: Utc::now(),
+                authors: vec![author.clone()],
+                categories: vec!["ml".to_string()],
+                ..Default::default()
+            }],
+            authors: vec![author],
+            categories: Vec::new(),
+            generator: "journalist".to_string(),
+            ..Default::default()
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&to_json_string(&feed)).unwrap();
+        assert_eq!(json["version"], VERSION);
+        assert!(json.get("description").is_none());
+
+        let item = &json["items"][0];
+        assert_eq!(item["content_text"], "Short");
+        assert!(item.get("summary").is_none());
+        assert_eq!(item["tags"][0], "ml");
+        assert_eq!(item["authors"][0]["name"], "Ada");
+        assert_eq!(item["authors"][0]["url"], "https://example.com/ada");
+    }
+}
@@ -0,0 +1,70 @@
+// Guessing the language of items from their text, for readers and filters.
+// Detection only goes by the title and summary, so it is skipped when they
+// are too short to tell.
+
+use whatlang::Lang;
+
+use crate::NewsItem;
+
+// ISO 639-1 codes for the languages whatlang knows, as xml:lang and JSON
+// Feed expect two letter codes where there are some
+const CODES: &[(Lang, &str)] = &[
+    (Lang::Afr, "af"), (Lang::Aka, "ak"), (Lang::Amh, "am"), (Lang::Ara, "ar"),
+    (Lang::Aze, "az"), (Lang::Bel, "be"), (Lang::Ben, "bn"), (Lang::Bul, "bg"),
+    (Lang::Cat, "ca"), (Lang::Ces, "cs"), (Lang::Cmn, "zh"), (Lang::Dan, "da"),
+    (Lang::Deu, "de"), (Lang::Ell, "el"), (Lang::Eng, "en"), (Lang::Epo, "eo"),
+    (Lang::Est, "et"), (Lang::Fin, "fi"), (Lang::Fra, "fr"), (Lang::Guj, "gu"),
+    (Lang::Heb, "he"), (Lang::Hin, "hi"), (Lang::Hrv, "hr"), (Lang::Hun, "hu"),
+    (Lang::Hye, "hy"), (Lang::Ind, "id"), (Lang::Ita, "it"), (Lang::Jav, "jv"),
+    (Lang::Jpn, "ja"), (Lang::Kan, "kn"), (Lang::Kat, "ka"), (Lang::Khm, "km"),
+    (Lang::Kor, "ko"), (Lang::Lat, "la"), (Lang::Lav, "lv"), (Lang::Lit, "lt"),
+    (Lang::Mal, "ml"), (Lang::Mar, "mr"), (Lang::Mkd, "mk"), (Lang::Mya, "my"),
+    (Lang::Nep, "ne"), (Lang::Nld, "nl"), (Lang::Nob, "nb"), (Lang::Ori, "or"),
+    (Lang::Pan, "pa"), (Lang::Pes, "fa"), (Lang::Pol, "pl"), (Lang::Por, "pt"),
+    (Lang::Ron, "ro"), (Lang::Rus, "ru"), (Lang::Sin, "si"), (Lang::Slk, "sk"),
+    (Lang::Slv, "sl"), (Lang::Sna, "sn"), (Lang::Spa, "es"), (Lang::Srp, "sr"),
+    (Lang::Swe, "sv"), (Lang::Tam, "ta"), (Lang::Tel, "te"), (Lang::Tgl, "tl"),
+    (Lang::Tha, "th"), (Lang::Tuk, "tk"), (Lang::Tur, "tr"), (Lang::Ukr, "uk"),
+    (Lang::Urd, "ur"), (Lang::Uzb, "uz"), (Lang::Vie, "vi"), (Lang::Yid, "yi"),
+    (Lang::Zul, "zu"),
+];
+
+fn code(lang: Lang) -> &'static str {
+    CODES.iter().find(|(known, _)| *known == lang).map_or(lang.code(), |(_, code)| code)
+}
+
+// Language tag of the text, if it can be told reliably
+pub fn detect(text: &str) -> Option<String> {
+    whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| code(info.lang()).to_string())
+}
+
+// Fill in the language of an item that doesn't have one
+pub fn detect_item(item: &mut NewsItem) {
+    if item.language.is_none() {
+        let text = format!("{}\n{}", item.title, item.summary.as_deref().unwrap_or_default());
+        item.language = detect(&text);
+    }
+}
+
+// Whether a language tag is of the given language, ignoring regions so that
+// en matches en-GB
+pub fn matches(tag: &str, language: &str) -> bool {
+    let primary = tag.split(['-', '_']).next().unwrap_or_default();
+    primary.eq_ignore_ascii_case(language) || tag.eq_ignore_ascii_case(language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages_are_detected() {
+        assert_eq!(detect("Die Katze sitzt auf der Matte und schaut aus dem Fenster in den Garten.").as_deref(), Some("de"));
+        assert_eq!(detect("The cat sits on the mat and looks out of the window into the garden.").as_deref(), Some("en"));
+        assert_eq!(detect("ok"), None);
+        assert!(matches("en-GB", "en"));
+        assert!(!matches("de", "en"));
+    }
+}
@@ -0,0 +1,391 @@
+// Feed generation as a library. Sources in `sources` produce items that are
+// converted to `NewsItem`s via `ToNewsItem`, collected into a `NewsFeed`, and
+// rendered with `ToXmlString`. The journalist binary is a thin CLI over this.
+
+use chrono::{DateTime, Utc};
+use std::ops::Add;
+use anyhow::{anyhow, Result};
+use atom_syndication as atom;
+use uuid::Uuid;
+
+pub mod attachments;
+pub mod config;
+pub mod digest;
+pub mod drip;
+pub mod filters;
+pub mod gemtext;
+pub mod html;
+pub mod http;
+pub mod ics;
+pub mod jsonfeed;
+pub mod language;
+pub mod lock;
+pub mod merge;
+pub mod notifications;
+pub mod org;
+pub mod paging;
+pub mod podcast;
+pub mod opml;
+pub mod preview;
+pub mod publishers;
+mod robots;
+pub mod schedule;
+pub mod sinks;
+pub mod sitemap;
+pub mod sources;
+pub mod state;
+pub mod template;
+mod utils;
+pub mod websub;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct NewsAuthor {
+    pub name: String,
+    pub email: String,
+    pub uri: String,
+}
+
+// A link from a feed to a related document, like another page of it
+#[derive(Clone, serde::Serialize, Debug)]
+pub struct FeedLink {
+    pub rel: String,
+    pub href: String,
+}
+
+#[derive(Clone, serde::Serialize, Default)]
+pub struct NewsFeed {
+    pub id: String,
+    pub updated: DateTime<Utc>,
+    pub link: String,
+    pub title: String,
+    pub subtitle: String,
+    pub items: Vec<NewsItem>,
+    pub authors: Vec<NewsAuthor>,
+    pub categories: Vec<String>,
+    pub generator: String,
+    // URLs of a small square image and a larger banner identifying the feed
+    pub icon: Option<String>,
+    pub logo: Option<String>,
+    // Links besides the self link
+    pub links: Vec<FeedLink>,
+    // Whether this is an archive document of a paged feed (RFC 5005)
+    pub archive: bool,
+    // Language tag of the feed, items can override it
+    pub language: Option<String>,
+}
+
+// Media attached to an item, like a PDF or an audio file
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct Enclosure {
+    pub url: String,
+    pub mime_type: String,
+    // Size in bytes, if known
+    pub length: Option<u64>,
+}
+
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize, Debug)]
+pub struct NewsItem {
+    pub id: String,
+    pub link: String,
+    pub title: String,
+    pub summary: Option<String>,
+    // Full text of the item, mostly filled when the summary is truncated
+    pub content: Option<String>,
+    // Whether content is HTML rather than plain text
+    #[serde(default)]
+    pub content_html: bool,
+    pub published: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+    pub authors: Vec<NewsAuthor>,
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub enclosures: Vec<Enclosure>,
+    // Other links the item is about, besides the main one
+    #[serde(default)]
+    pub related: Vec<String>,
+    // How many of my notes link to it, which makes it more likely to be
+    // picked by `linked` feeds
+    #[serde(default)]
+    pub backlinks: usize,
+    // Language tag like en or de, when known
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+impl Add for NewsItem {
+    type Output = Result<Self>;
+
+    fn add(self, other: Self) -> Result<Self> {
+        if self.id != other.id {
+            Err(anyhow!("{:?} and {:?} have different IDs", self, other))
+        } else {
+            let item = NewsItem {
+                id: self.id,
+                link: self.link,
+                title: self.title,
+                summary: match (self.summary, other.summary) {
+                    (Some(a), Some(b)) => Some(format!("{}\n-----\n{}", a, b)),
+                    (a, b) => a.or(b),
+                },
+                content_html: if self.content.is_some() { self.content_html } else { other.content_html },
+                content: match (self.content, other.content) {
+                    (Some(a), Some(b)) => Some(format!("{}\n-----\n{}", a, b)),
+                    (a, b) => a.or(b),
+                },
+                published: self.published,
+                updated: std::cmp::max(self.updated, other.updated),
+                authors: self.authors,
+                categories: utils::union_strings(self.categories, other.categories),
+                enclosures: other.enclosures.into_iter().fold(self.enclosures, |mut enclosures, enclosure| {
+                    if !enclosures.contains(&enclosure) {
+                        enclosures.push(enclosure);
+                    }
+                    enclosures
+                }),
+                related: utils::union_strings(self.related, other.related),
+                backlinks: self.backlinks.max(other.backlinks),
+                language: self.language.or(other.language),
+            };
+            Ok(item)
+        }
+    }
+}
+
+impl NewsItem {
+    // Cut the summary down to max_length characters at a word boundary. The
+    // full text is kept as content if keep_full_text is set, else dropped.
+    pub fn truncate_summary(&mut self, max_length: usize, keep_full_text: bool) {
+        let Some(summary) = &self.summary else {
+            return;
+        };
+
+        if let Some(truncated) = utils::truncate_at_word(summary, max_length) {
+            let full_text = self.summary.replace(truncated);
+            if keep_full_text && self.content.is_none() {
+                self.content = full_text;
+            }
+        }
+    }
+}
+
+impl NewsItem {
+    // Turn org markup into HTML content. Without a separate full text the
+    // summary itself becomes the content, else the full text is converted and
+    // the short summary is kept.
+    pub fn convert_org_to_html(&mut self) {
+        if self.content_html {
+            return;
+        }
+        if self.content.is_none() {
+            self.content = self.summary.take();
+        }
+        if let Some(content) = &self.content {
+            self.content = Some(org::to_html(content));
+            self.content_html = true;
+        }
+    }
+
+    // Turn org markup in the summary, and in plain text content, into
+    // readable text
+    pub fn convert_org_to_text(&mut self) {
+        self.summary = self.summary.as_deref().map(org::to_text);
+        if !self.content_html {
+            self.content = self.content.as_deref().map(org::to_text);
+        }
+    }
+}
+
+// Namespace of the UUIDs derived from native ids of sources
+const ITEM_ID_NAMESPACE: Uuid = Uuid::from_u128(0x2bcecb56a1264f148a109b962e43daaa);
+
+impl NewsItem {
+    // Id for an item from a source whose own ids aren't UUIDs, like paths or
+    // URLs. It is derived (UUIDv5) from the source name and the native id so
+    // that it stays the same across runs. UUIDs are kept as they are.
+    pub fn stable_id(source: &str, id: &str) -> String {
+        match Uuid::try_parse(id) {
+            Ok(uuid) => uuid.hyphenated().to_string(),
+            Err(_) => Uuid::new_v5(&ITEM_ID_NAMESPACE, format!("{}:{}", source, id).as_bytes()).hyphenated().to_string(),
+        }
+    }
+
+    // The UUID the item is written out with. Items from sources of other
+    // programs might still have ids that aren't UUIDs, and items read back
+    // from written feeds already have it.
+    pub fn feed_id(&self) -> String {
+        NewsItem::stable_id("journalist", &self.id)
+    }
+}
+
+pub trait ToNewsItem {
+    fn to_newsitem(&self) -> NewsItem;
+}
+
+pub trait ToXmlString {
+    // Indented by two spaces per level
+    fn to_xml_string(&self) -> String;
+    // Without any whitespace between elements
+    fn to_compact_xml_string(&self) -> String;
+}
+
+impl From<&NewsAuthor> for atom::Person {
+    fn from(author: &NewsAuthor) -> Self {
+        atom::Person {
+            name: author.name.clone(),
+            email: Some(author.email.clone()).filter(|email| !email.is_empty()),
+            uri: Some(author.uri.clone()).filter(|uri| !uri.is_empty()),
+        }
+    }
+}
+
+impl From<&NewsItem> for atom::Entry {
+    fn from(item: &NewsItem) -> Self {
+        // Entries can't have a language of their own, so it goes on their
+        // text constructs
+        let text = |value: String| atom::Text { lang: item.language.clone(), ..atom::Text::plain(value) };
+
+        atom::Entry {
+            title: text(item.title.clone()),
+            id: format!("urn:uuid:{}", item.feed_id()),
+            updated: item.updated.fixed_offset(),
+            published: Some(item.published.fixed_offset()),
+            links: std::iter::once(atom::Link { href: item.link.clone(), ..Default::default() })
+                .chain(item.enclosures.iter().map(|enclosure| atom::Link {
+                    href: enclosure.url.clone(),
+                    rel: "enclosure".to_string(),
+                    mime_type: Some(enclosure.mime_type.clone()),
+                    length: enclosure.length.map(|length| length.to_string()),
+                    ..Default::default()
+                }))
+                .chain(item.related.iter().map(|href| atom::Link {
+                    href: href.clone(),
+                    rel: "related".to_string(),
+                    ..Default::default()
+                }))
+                .collect(),
+            summary: item.summary.clone().map(text),
+            content: item.content.clone().map(|content| atom::Content {
+                value: Some(content),
+                lang: item.language.clone(),
+                content_type: Some(if item.content_html { "html" } else { "text" }.to_string()),
+                ..Default::default()
+            }),
+            categories: item.categories.iter().map(|term| atom::Category { term: term.clone(), ..Default::default() }).collect(),
+            authors: item.authors.iter().map(atom::Person::from).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+// Namespace of the feed paging and archiving elements
+const FEED_HISTORY_NS: &str = "http://purl.org/syndication/history/1.0";
+
+impl From<&NewsFeed> for atom::Feed {
+    fn from(feed: &NewsFeed) -> Self {
+        let self_link = atom::Link { href: feed.link.clone(), rel: "self".to_string(), ..Default::default() };
+        let links = feed.links.iter().map(|link| atom::Link { href: link.href.clone(), rel: link.rel.clone(), ..Default::default() });
+
+        let mut namespaces = std::collections::BTreeMap::new();
+        let mut extensions = atom::extension::ExtensionMap::new();
+        if feed.archive {
+            namespaces.insert("fh".to_string(), FEED_HISTORY_NS.to_string());
+            let archive = atom::extension::Extension { name: "fh:archive".to_string(), ..Default::default() };
+            extensions.entry("fh".to_string()).or_default().insert("archive".to_string(), vec![archive]);
+        }
+
+        atom::Feed {
+            id: feed.id.clone(),
+            title: atom::Text::plain(feed.title.clone()),
+            subtitle: Some(atom::Text::plain(feed.subtitle.clone())),
+            updated: feed.updated.fixed_offset(),
+            links: std::iter::once(self_link).chain(links).collect(),
+            categories: feed.categories.iter().map(|term| atom::Category { term: term.clone(), ..Default::default() }).collect(),
+            authors: feed.authors.iter().map(atom::Person::from).collect(),
+            icon: feed.icon.clone(),
+            logo: feed.logo.clone(),
+            lang: feed.language.clone(),
+            namespaces,
+            extensions,
+            generator: Some(atom::Generator { value: feed.generator.clone(), ..Default::default() }),
+            entries: feed.items.iter().map(atom::Entry::from).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+// The document is written with an XML writer so that everything in it,
+// including attributes like links and category terms, is escaped properly.
+fn write_atom(feed: &NewsFeed, indent_size: Option<usize>) -> String {
+    let config = atom::WriteConfig { write_document_declaration: true, indent_size };
+    let output = atom::Feed::from(feed).write_with_config(Vec::new(), config).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+impl ToXmlString for NewsFeed {
+    fn to_xml_string(&self) -> String {
+        write_atom(self, Some(2))
+    }
+
+    fn to_compact_xml_string(&self) -> String {
+        write_atom(self, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn special_characters_are_escaped() {
+        let author = NewsAuthor { name: "Tom & Jerry".to_string(), email: "t@example.com".to_string(), uri: "example.com".to_string() };
+        let feed = NewsFeed {
+            id: "test".to_string(),
+            updated: Utc::now(),
+            link: "/test?a=1&b=2".to_string(),
+            title: "Q&A".to_string(),
+            subtitle: "<none>".to_string(),
+            items: vec![NewsItem {
+                id: "a".to_string(),
+                link: "https://example.com/?q=\"x\"&y=1".to_string(),
+                title: "Rock & roll".to_string(),
+                summary: Some("1 < 2".to_string()),
+                published: Utc::now(),
+                updated: Utc::now(),
+                authors: vec![author.clone()],
+                categories: vec!["r&d".to_string(), "\"quoted\"".to_string()],
+                ..Default::default()
+            }],
+            authors: vec![author],
+            categories: vec!["a&b".to_string()],
+            generator: "journalist".to_string(),
+            ..Default::default()
+        };
+
+        let compact = feed.to_compact_xml_string();
+        assert!(!compact.contains("\n  <"));
+        assert_eq!(compact.parse::<atom::Feed>().unwrap().title.value, "Q&A");
+
+        let parsed: atom::Feed = feed.to_xml_string().parse().unwrap();
+        assert_eq!(parsed.title.value, "Q&A");
+        assert_eq!(parsed.links[0].href, "/test?a=1&b=2");
+        assert_eq!(parsed.authors[0].name, "Tom & Jerry");
+        assert_eq!(parsed.categories[0].term, "a&b");
+
+        let entry = &parsed.entries[0];
+        assert_eq!(entry.links[0].href, "https://example.com/?q=\"x\"&y=1");
+        assert_eq!(entry.categories[1].term, "\"quoted\"");
+        assert_eq!(entry.summary.as_ref().unwrap().value, "1 < 2");
+        assert_eq!(entry.id, format!("urn:uuid:{}", NewsItem::stable_id("journalist", "a")));
+    }
+
+    #[test]
+    fn stable_ids_are_uuids() {
+        let id = NewsItem::stable_id("hf", "/papers/2406.12345");
+        assert!(Uuid::try_parse(&id).is_ok());
+        assert_eq!(id, NewsItem::stable_id("hf", "/papers/2406.12345"));
+        assert_ne!(id, NewsItem::stable_id("exec", "/papers/2406.12345"));
+
+        let uuid = "1b2c3d4e-0000-4000-8000-000000000001";
+        assert_eq!(NewsItem::stable_id("pile", uuid), uuid);
+    }
+}
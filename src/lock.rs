@@ -0,0 +1,62 @@
+use std::fs::{self, File, TryLockError};
+use std::path;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+
+use crate::state;
+
+// Advisory lock held for the duration of a run so that overlapping
+// invocations, like a slow cron job running into the next one, don't write
+// the same outputs at once. Released when dropped.
+pub struct RunLock {
+    _file: File,
+}
+
+// The lock lives next to the state db since both are per profile
+pub fn lock_path(state_db_path: Option<&path::Path>) -> Option<path::PathBuf> {
+    match state_db_path {
+        Some(state_db_path) => Some(state_db_path.with_extension("lock")),
+        None => state::default_path().map(|state_db_path| state_db_path.with_extension("lock")),
+    }
+}
+
+impl RunLock {
+    // Take the lock, waiting for the other run to finish if `wait` is set.
+    // Returns None if another run holds it and we are not waiting.
+    pub fn acquire(lock_path: &path::Path, wait: bool) -> Result<Option<Self>> {
+        if let Some(parent) = lock_path.parent() {
+            fs::create_dir_all(parent).context("Unable to create lock directory")?;
+        }
+        let file = File::create(lock_path)
+            .with_context(|| format!("Unable to open lock file {}", lock_path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Some(RunLock { _file: file })),
+            Err(TryLockError::WouldBlock) if wait => {
+                info!("Waiting for another run holding {}", lock_path.display());
+                file.lock()?;
+                Ok(Some(RunLock { _file: file }))
+            },
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(err)) => Err(anyhow!("Unable to lock {}: {}", lock_path.display(), err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_lock_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock_path = dir.path().join("run.lock");
+
+        let lock = RunLock::acquire(&lock_path, false).unwrap();
+        assert!(lock.is_some());
+        assert!(RunLock::acquire(&lock_path, false).unwrap().is_none());
+
+        drop(lock);
+        assert!(RunLock::acquire(&lock_path, false).unwrap().is_some());
+    }
+}
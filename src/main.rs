@@ -1,26 +1,209 @@
-use chrono::{DateTime, Utc};
-use clap::{Parser, Subcommand};
-use log::debug;
-use std::{cmp::Reverse, fs::File, io::Write, ops::Add, path};
+use chrono::{Datelike, Local, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
+use log::{error, info, warn};
+use notify::{RecursiveMode, Watcher};
+use std::{cmp::Reverse, collections::{BTreeMap, HashMap}, fmt, path};
+use std::time::{Duration, Instant};
 use anyhow::{anyhow, Result};
-use sources::{hf, pile};
 use rand::seq::SliceRandom;
-use htmlescape::encode_minimal;
-
-mod sources;
-mod utils;
+use rand::Rng;
+use futures::future::join_all;
+use journalist::sources::{pile, Registry, SourceContext};
+use journalist::sinks::{self, Sink};
+use journalist::{attachments, config, digest, drip, filters, http, language, lock, merge, notifications, opml, paging, podcast, preview, publishers, schedule, state, websub};
+use journalist::{FeedLink, NewsFeed, NewsItem, ToNewsItem, ToXmlString};
 
 #[derive(Parser)]
 struct Cli {
+    /// Config file, defaults to ~/.config/journalist/journalist.toml
+    #[arg(long, global = true)]
+    config: Option<path::PathBuf>,
+    /// Named profile from the config file to use
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Only read network sources from the local cache
+    #[arg(long, global = true)]
+    offline: bool,
+    /// org-roam database to read bookmarks from
+    #[arg(long, global = true)]
+    roam_db_path: Option<path::PathBuf>,
+    /// Notes directory to read bookmarks from
+    #[arg(long, global = true)]
+    notes_dir_path: Option<path::PathBuf>,
+    /// Only read notes this many directories deep, 1 being the notes
+    /// directory itself
+    #[arg(long, global = true)]
+    notes_max_depth: Option<usize>,
+    /// Glob pattern of notes to skip, relative to the notes directory, like
+    /// daily/*. Can be given more than once.
+    #[arg(long, global = true)]
+    exclude: Vec<String>,
+    /// Timezone of the times in notes, like Asia/Kolkata. Defaults to $TZ,
+    /// else the system's.
+    #[arg(long, global = true)]
+    timezone: Option<String>,
+    /// Directory with WASM source plugins, each `<name>.wasm` is available as
+    /// source `<name>`
+    #[cfg(feature = "plugins")]
+    #[arg(long, global = true)]
+    plugin_dir: Option<path::PathBuf>,
+    /// Wait for another run of the same profile to finish instead of
+    /// exiting
+    #[arg(long, global = true)]
+    wait_for_lock: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+// Parsed once at startup, so the size of the larger variants doesn't matter
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     Generate {
+        /// Also print the generated feed in a readable form
+        #[arg(long)]
+        preview: bool,
+        /// Print the selected items as tab separated feed id, title, link, and
+        /// tags instead of writing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Exit with an error when any feed fails, only when all of them do,
+        /// or never. Other feeds are generated either way.
+        #[arg(long, value_enum, default_value = "any")]
+        fail_on: FailOn,
+        /// Write a JSON report of the run with per feed outcomes, item counts,
+        /// fetch durations, and outputs
+        #[arg(long)]
+        report: Option<path::PathBuf>,
+        /// Truncate item summaries to these many characters
+        #[arg(long)]
+        max_summary_length: Option<usize>,
+        /// Drop the full text of truncated summaries instead of keeping it as content
+        #[arg(long)]
+        drop_full_text: bool,
+        /// Convert org markup in summaries to HTML content
+        #[arg(long)]
+        html_content: bool,
+        /// Write Atom and JSON Feed outputs without indentation
+        #[arg(long)]
+        compact: bool,
+        /// Also write a compressed copy next to each output file, `gzip` or
+        /// `br`. Can be repeated.
+        #[arg(long)]
+        compress: Vec<sinks::Compression>,
+        /// Pick these many items once a week and release them gradually over
+        /// the week's runs
+        #[arg(long)]
+        drip: Option<usize>,
+        /// Only include items the feed did not have in earlier runs
+        #[arg(long)]
+        delta: bool,
+        /// Only include items passing this filter, like `tag:ml` or
+        /// `!project`. Can be repeated and adds to the feed's own filters.
+        #[arg(long)]
+        filter: Vec<String>,
+        /// Only include items detected to be in this language, like `en`
+        #[arg(long)]
+        language: Option<String>,
+        /// Only include Huggingface papers with at least these many upvotes
+        #[arg(long)]
+        min_votes: Option<usize>,
+        /// Read Huggingface papers of this ISO week, like `2024-W37`, or range
+        /// of weeks, like `2024-W30..2024-W37`, instead of the current one.
+        /// Can be repeated.
+        #[arg(long)]
+        week: Vec<String>,
+        /// Id of the feed to generate, either from the config or one of the
+        /// builtin ones. All feeds from the config are generated if not given
+        /// or `all`.
+        feed: Option<String>,
+        /// Defaults to the output configured for the feed. `-` writes to
+        /// stdout.
+        output_file: Option<path::PathBuf>,
+        /// Additional outputs, can be repeated
+        #[arg(long)]
+        output: Vec<path::PathBuf>,
+        /// Format for outputs given on the command line
+        #[arg(long, default_value = "atom")]
+        format: sinks::Format,
+        /// Tera template to render outputs given on the command line with,
+        /// instead of the format
+        #[arg(long)]
+        template: Option<path::PathBuf>,
+        /// Tera template for each item, available to the feed template as
+        /// `entries`
+        #[arg(long, requires = "template")]
+        item_template: Option<path::PathBuf>,
+        #[command(flatten)]
+        metadata: FeedMetadata,
+        /// WebSub hub to advertise in feeds and notify after writing them
+        #[arg(long)]
+        websub_hub: Option<String>,
+    },
+    /// Generate monthly archive feeds from historical bookmarks
+    Backfill {
+        /// First month to generate, like 2022-01
+        #[arg(long)]
+        from: String,
+        /// Last month to generate, like 2024-12
+        #[arg(long)]
+        to: String,
+        /// Only include recommended bookmarks
+        #[arg(long)]
+        recommended: bool,
+        /// Only include bookmarks passing this filter, can be repeated
+        #[arg(long)]
+        filter: Vec<String>,
+        output_dir: path::PathBuf,
+    },
+    /// Bundle recent items of a feed into one document to read offline
+    Digest {
+        feed: String,
+        /// Only include items published within this long, like 48h, 7d, or 2w
+        #[arg(long, default_value = "7d")]
+        since: String,
+        #[arg(long, value_enum, default_value = "epub")]
+        format: digest::Format,
+        /// Fetch the text of the page each item links to
+        #[arg(long)]
+        full_text: bool,
+        /// Only include items passing this filter, can be repeated
+        #[arg(long)]
+        filter: Vec<String>,
+        /// Defaults to <feed>-digest.<format> in the output directory
+        #[arg(long)]
+        output: Option<path::PathBuf>,
+    },
+    /// Print a feed file in a readable form
+    Preview {
+        feed_file: path::PathBuf,
+    },
+    /// Stay running and regenerate feeds from the config on their schedules
+    Daemon,
+    /// Regenerate feeds reading bookmarks whenever the notes change
+    Watch {
+        /// Feeds to regenerate, defaults to the ones from the config using
+        /// the pile source
+        feeds: Vec<String>,
+    },
+    /// Serve feeds over HTTP at /<feed id>.xml, generating them on request
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
+        /// Regenerate a feed on request once its last generation is older
+        /// than these many seconds. 0 regenerates on every request.
+        #[arg(long, default_value_t = 3600)]
+        max_age: u64,
+    },
+    /// Write out information about the configured feeds
+    Export {
         #[command(subcommand)]
-        gen_command: GenCommands,
+        format: ExportFormat,
+    },
+    /// Inspect or reset what feeds have recorded across runs
+    State {
+        #[command(subcommand)]
+        action: StateAction,
     },
     Merge {
         #[arg(long)]
@@ -29,319 +212,1136 @@ enum Commands {
     },
 }
 
+// Feed metadata given on the command line, taking precedence over the config
+#[derive(Args)]
+struct FeedMetadata {
+    /// Title of the feed, when generating one
+    #[arg(long)]
+    title: Option<String>,
+    /// Subtitle of the feed, when generating one
+    #[arg(long)]
+    subtitle: Option<String>,
+    /// Link of the feed, when generating one
+    #[arg(long)]
+    link: Option<String>,
+    /// Author name, overriding the one from the feed or profile
+    #[arg(long)]
+    author_name: Option<String>,
+    #[arg(long)]
+    author_email: Option<String>,
+    #[arg(long)]
+    author_uri: Option<String>,
+    /// URL of a small square image for readers to show with the feed
+    #[arg(long)]
+    icon: Option<String>,
+    /// URL of a larger image identifying the feed
+    #[arg(long)]
+    logo: Option<String>,
+}
+
+impl FeedMetadata {
+    fn apply(&self, feed_config: &mut config::FeedConfig, profile: &config::Profile) {
+        if let Some(title) = &self.title {
+            feed_config.title = title.clone();
+        }
+        if let Some(subtitle) = &self.subtitle {
+            feed_config.subtitle = subtitle.clone();
+        }
+        if self.link.is_some() {
+            feed_config.link = self.link.clone();
+        }
+        if self.author_name.is_some() || self.author_email.is_some() || self.author_uri.is_some() {
+            let mut author = feed_config.author.clone()
+                .or(profile.author.clone())
+                .unwrap_or_else(config::default_author);
+            author.name = self.author_name.clone().unwrap_or(author.name);
+            author.email = self.author_email.clone().unwrap_or(author.email);
+            author.uri = self.author_uri.clone().unwrap_or(author.uri);
+            feed_config.author = Some(author);
+        }
+        if self.icon.is_some() {
+            feed_config.icon = self.icon.clone();
+        }
+        if self.logo.is_some() {
+            feed_config.logo = self.logo.clone();
+        }
+    }
+}
+
 #[derive(Subcommand)]
-enum GenCommands {
-    PileBookmarks {
-        #[arg(long)]
-        roam_db_path: Option<path::PathBuf>,
-        #[arg(long)]
-        notes_dir_path: Option<path::PathBuf>,
-        output_file: path::PathBuf,
+enum ExportFormat {
+    /// OPML list of the feeds with their public URLs, for importing into
+    /// readers
+    Opml {
+        /// Defaults to stdout
+        output_file: Option<path::PathBuf>,
     },
-    PileBookmarksProjects {
-        #[arg(long)]
-        roam_db_path: Option<path::PathBuf>,
-        #[arg(long)]
-        notes_dir_path: Option<path::PathBuf>,
-        output_file: path::PathBuf,
-    },
-    HfPapers {
-        output_file: path::PathBuf,
+}
+
+#[derive(Subcommand)]
+enum StateAction {
+    /// List feeds with the number of items they published, or the items of
+    /// one feed
+    Show {
+        feed: Option<String>,
     },
-    RecommendedLinks {
+    /// Forget everything recorded for a feed, or for all feeds
+    Reset {
+        feed: Option<String>,
+        /// Needed to reset all feeds
         #[arg(long)]
-        roam_db_path: Option<path::PathBuf>,
-        #[arg(long)]
-        notes_dir_path: Option<path::PathBuf>,
-        output_file: path::PathBuf,
+        all: bool,
     },
 }
 
-#[derive(Clone, serde::Serialize, Debug)]
-struct NewsAuthor {
-    name: String,
-    email: String,
-    uri: String,
+// Entries kept by incremental feeds unless configured otherwise
+const DEFAULT_KEEP: usize = 50;
+
+// Parse a month like 2024-01 into its first day
+fn parse_month(month: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| anyhow!("Invalid month {}, expected YYYY-MM", month))
 }
 
-#[derive(serde::Serialize)]
-struct NewsFeed {
-    id: String,
-    updated: DateTime<Utc>,
-    link: String,
-    title: String,
-    subtitle: String,
-    items: Vec<NewsItem>,
-    authors: Vec<NewsAuthor>,
-    categories: Vec<String>,
-    generator: String
-}
-
-#[derive(Clone, serde::Serialize, Debug)]
-struct NewsItem {
+// Write the feed to disk and then hand it over to configured publishers
+fn write_feed(feed: &NewsFeed, output_path: &path::Path, config: &config::Config) -> Result<()> {
+    let output = sinks::OutputConfig::new(output_path.to_path_buf(), sinks::Format::Atom);
+    emit_feed(feed, &[output], None, &config::Profile::default(), config)?;
+    Ok(())
+}
+
+// Emit the feed to all outputs. Files are published after being written,
+// and ones that didn't change are skipped. Returns whether anything was
+// written.
+fn emit_feed(feed: &NewsFeed, outputs: &[sinks::OutputConfig], paging: Option<&paging::PagingConfig>, profile: &config::Profile, config: &config::Config) -> Result<bool> {
+    let mut changed = false;
+    for output in outputs {
+        if output.is_stdout() {
+            sinks::StdoutSink { renderer: output.renderer()? }.emit(feed)?;
+            changed = true;
+            continue;
+        }
+
+        let output_path = profile.output_path(&output.path);
+        let documents = match (paging, output_path.file_name().and_then(|name| name.to_str())) {
+            (Some(paging), Some(file_name)) => paging::paginate(feed.clone(), file_name, paging),
+            _ => vec![(String::new(), feed.clone())],
+        };
+        for (file_name, document) in documents {
+            let document_path = if file_name.is_empty() { output_path.clone() } else { output_path.with_file_name(file_name) };
+            let sink = sinks::FileSink { path: document_path.clone(), renderer: output.renderer()?, compress: output.compress.clone() };
+            if sink.write(&document)? {
+                publishers::publish_all(&config.publish, &document_path)?;
+                changed = true;
+            } else {
+                info!("Leaving {} as it is, nothing changed", document_path.display());
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+// Tell the WebSub hub about a written feed. The feed is out already, so
+// failures only get logged.
+fn announce(hub: &str, feed: &NewsFeed) {
+    if !(feed.link.starts_with("http://") || feed.link.starts_with("https://")) {
+        warn!("Not announcing {} to {} as its URL is not known, set base_url or its link", feed.id, hub);
+        return;
+    }
+    if let Err(err) = websub::publish(hub, &feed.link) {
+        warn!("Unable to announce {}: {:#}", feed.id, err);
+    }
+}
+
+// What happened to a feed in a run
+enum Outcome {
+    Generated { items: usize, outputs: Vec<path::PathBuf> },
+    // The source had nothing to offer and the existing output was left as is
+    Skipped { reason: String },
+    Failed { error: anyhow::Error },
+}
+
+struct FeedRun {
     id: String,
-    link: String,
-    title: String,
-    summary: Option<String>,
-    published: DateTime<Utc>,
-    updated: DateTime<Utc>,
-    authors: Vec<NewsAuthor>,
-    categories: Vec<String>,
+    outcome: Outcome,
+    fetch_duration: Duration,
+    issues: Vec<String>,
+}
+
+// Items a feed's source returned along with how the fetch went
+struct Fetched {
+    items: Result<Vec<NewsItem>>,
+    duration: Duration,
+    issues: Vec<String>,
 }
 
-impl Add for NewsItem {
-    type Output = Result<Self>;
+// When a run with failing feeds should exit with an error
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+enum FailOn {
+    Any,
+    All,
+    Never,
+}
 
-    fn add(self, other: Self) -> Result<Self> {
-        if self.id != other.id {
-            Err(anyhow!("{:?} and {:?} have different IDs", self, other))
-        } else {
-            let item = NewsItem {
-                id: self.id,
-                link: self.link,
-                title: self.title,
-                summary: if self.summary.is_some() {
-                    if other.summary.is_some() {
-                        Some(format!("{}\n-----\n{}", self.summary.unwrap(), other.summary.unwrap()))
-                    } else {
-                        self.summary
+// Print how each feed went, one line per feed
+fn print_summary(runs: &[FeedRun]) {
+    let width = runs.iter().map(|run| run.id.len()).max().unwrap_or(0);
+    for run in runs {
+        let status = match &run.outcome {
+            Outcome::Generated { items, .. } => format!("generated  {} items", items),
+            Outcome::Skipped { reason } => format!("skipped    {}", reason),
+            Outcome::Failed { error } => format!("failed     {:#}", error),
+        };
+        eprintln!("{:width$}  {}", run.id, status, width = width);
+    }
+}
+
+// Write a JSON report of the run for monitoring
+fn write_report(report_path: &path::Path, runs: &[FeedRun], started: chrono::DateTime<Utc>) -> Result<()> {
+    let feeds: Vec<serde_json::Value> = runs.iter().map(|run| {
+        let mut feed = serde_json::json!({
+            "id": run.id,
+            "fetch_seconds": run.fetch_duration.as_secs_f64(),
+            "issues": run.issues,
+        });
+        let details = match &run.outcome {
+            Outcome::Generated { items, outputs } => serde_json::json!({ "status": "generated", "items": items, "outputs": outputs }),
+            Outcome::Skipped { reason } => serde_json::json!({ "status": "skipped", "reason": reason }),
+            Outcome::Failed { error } => serde_json::json!({ "status": "failed", "error": format!("{:#}", error) }),
+        };
+        feed.as_object_mut().unwrap().extend(details.as_object().unwrap().clone());
+        feed
+    }).collect();
+
+    let report = serde_json::json!({
+        "started": started.to_rfc3339(),
+        "finished": Utc::now().to_rfc3339(),
+        "feeds": feeds,
+    });
+    sinks::write_atomic(report_path, serde_json::to_string_pretty(&report)?.as_bytes())
+}
+
+// Error for a run failing because of its feeds, which have been notified
+// about one by one already
+#[derive(Debug)]
+struct FeedsFailed {
+    failed: Vec<String>,
+    total: usize,
+}
+
+impl fmt::Display for FeedsFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} feeds failed: {}", self.failed.len(), self.total, self.failed.join(", "))
+    }
+}
+
+impl std::error::Error for FeedsFailed {}
+
+// Turn failed feeds into an error according to the policy
+fn check_failures(runs: &[FeedRun], fail_on: FailOn) -> Result<()> {
+    let failed: Vec<String> = runs.iter()
+        .filter(|run| matches!(run.outcome, Outcome::Failed { .. }))
+        .map(|run| run.id.clone())
+        .collect();
+
+    let should_fail = match fail_on {
+        FailOn::Any => !failed.is_empty(),
+        FailOn::All => !failed.is_empty() && failed.len() == runs.len(),
+        FailOn::Never => false,
+    };
+
+    if should_fail {
+        Err(FeedsFailed { failed, total: runs.len() }.into())
+    } else {
+        Ok(())
+    }
+}
+
+// Log and notify about failures in long running modes, which keep going
+fn report_failures(runs: Result<Vec<FeedRun>>, config: &config::Config) {
+    let runs = match runs {
+        Ok(runs) => runs,
+        Err(err) => {
+            error!("Generation failed: {:?}", err);
+            notifications::notify_failure(&config.notify, &err);
+            return;
+        },
+    };
+
+    for error in feed_errors(runs) {
+        error!("{:?}", error);
+        notifications::notify_failure(&config.notify, &error);
+    }
+}
+
+// Errors of the feeds that failed, saying which feed it was
+fn feed_errors(runs: Vec<FeedRun>) -> impl Iterator<Item = anyhow::Error> {
+    runs.into_iter().filter_map(|run| match run.outcome {
+        Outcome::Failed { error } => Some(error.context(format!("Generating {} failed", run.id))),
+        _ => None,
+    })
+}
+
+// Take the run lock of the profile. None if another run holds it and we are
+// not waiting.
+fn acquire_lock(profile: &config::Profile, wait: bool) -> Result<Option<lock::RunLock>> {
+    let lock_path = lock::lock_path(profile.state_db_path.as_deref())
+        .ok_or_else(|| anyhow!("Not able to find a location for the lock file"))?;
+    lock::RunLock::acquire(&lock_path, wait)
+}
+
+// Builtin sources plus plugins from --plugin-dir
+fn build_registry(#[allow(unused_variables)] args: &Cli) -> Result<Registry> {
+    #[allow(unused_mut)]
+    let mut registry = Registry::default();
+    #[cfg(feature = "plugins")]
+    if let Some(plugin_dir) = &args.plugin_dir {
+        journalist::sources::plugin::register_dir(&mut registry, plugin_dir)?;
+    }
+    Ok(registry)
+}
+
+// Regenerate every feed that has a schedule whenever it's due, forever.
+// Failures are reported and the feed is tried again at its next run.
+fn run_daemon(generator: &Generator, config: &config::Config) -> Result<()> {
+    let mut scheduled = Vec::new();
+    for feed_config in &config.feeds {
+        match &feed_config.schedule {
+            Some(expression) => scheduled.push((feed_config.clone(), schedule::parse(expression)?)),
+            None => warn!("Feed {} has no schedule, the daemon will not generate it", feed_config.id),
+        }
+    }
+    if scheduled.is_empty() {
+        return Err(anyhow!("No feeds with a schedule defined in the config"));
+    }
+
+    let now = Local::now();
+    let mut next_runs = scheduled.iter()
+        .map(|(_, cron)| schedule::next_run(cron, &now))
+        .collect::<Result<Vec<_>>>()?;
+
+    loop {
+        let next = *next_runs.iter().min().unwrap();
+        if let Ok(wait) = (next - Local::now()).to_std() {
+            info!("Sleeping until {}", next);
+            std::thread::sleep(wait);
+        }
+
+        let now = Local::now();
+        let due: Vec<usize> = (0..scheduled.len()).filter(|&i| next_runs[i] <= now).collect();
+        let feeds: Vec<config::FeedConfig> = due.iter().map(|&i| scheduled[i].0.clone()).collect();
+
+        info!("Generating {}", feeds.iter().map(|f| f.id.as_str()).collect::<Vec<_>>().join(", "));
+        // A lock that can't be taken only costs this run, not the daemon
+        match acquire_lock(generator.ctx.profile, true) {
+            Ok(_lock) => report_failures(generator.generate(&feeds, false), config),
+            Err(err) => error!("Skipping this run, unable to take the run lock: {:#}", err),
+        }
+
+        for i in due {
+            next_runs[i] = schedule::next_run(&scheduled[i].1, &now)?;
+        }
+    }
+}
+
+// Time to wait for more changes before regenerating, so that editors writing
+// a file in several steps only trigger one run
+const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_secs(2);
+
+// Regenerate the feeds every time something changes in the notes directory
+// or the roam db, forever.
+fn run_watch(generator: &Generator, feeds: &[config::FeedConfig], config: &config::Config) -> Result<()> {
+    let profile = generator.ctx.profile;
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+
+    let mut watching = false;
+    if let Some(notes_dir_path) = &profile.notes_dir_path {
+        watcher.watch(notes_dir_path, RecursiveMode::Recursive)?;
+        watching = true;
+    }
+    if let Some(roam_db_path) = &profile.roam_db_path {
+        watcher.watch(roam_db_path, RecursiveMode::NonRecursive)?;
+        watching = true;
+    }
+    if !watching {
+        return Err(anyhow!("Need either --notes-dir-path or --roam-db-path to watch"));
+    }
+
+    loop {
+        info!("Generating {}", feeds.iter().map(|f| f.id.as_str()).collect::<Vec<_>>().join(", "));
+        match acquire_lock(generator.ctx.profile, true) {
+            Ok(_lock) => report_failures(generator.generate(feeds, false), config),
+            Err(err) => error!("Skipping this run, unable to take the run lock: {:#}", err),
+        }
+
+        // Block for the first change, then drain until things settle
+        wait_for_change(&receiver, None)?;
+        while wait_for_change(&receiver, Some(WATCH_DEBOUNCE))? {}
+    }
+}
+
+// Wait for a change that modifies files, returning false on timeout
+fn wait_for_change(receiver: &std::sync::mpsc::Receiver<notify::Result<notify::Event>>, timeout: Option<std::time::Duration>) -> Result<bool> {
+    loop {
+        let event = match timeout {
+            Some(timeout) => match receiver.recv_timeout(timeout) {
+                Ok(event) => event,
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => return Ok(false),
+                Err(err) => return Err(err.into()),
+            },
+            None => receiver.recv()?,
+        };
+
+        if !event?.kind.is_access() {
+            return Ok(true);
+        }
+    }
+}
+
+// Serve feeds from memory, regenerating one when it's requested and older
+// than max_age. A stale copy is served if regeneration fails.
+fn run_server(generator: &Generator, config: &config::Config, address: &str, max_age: std::time::Duration) -> Result<()> {
+    let server = tiny_http::Server::http(address).map_err(|err| anyhow!("Unable to listen on {}: {}", address, err))?;
+    let mut cache: HashMap<String, (std::time::Instant, String)> = HashMap::new();
+    info!("Serving feeds on http://{}", address);
+
+    for request in server.incoming_requests() {
+        let path = request.url().split('?').next().unwrap_or("").trim_start_matches('/').to_string();
+        let response = match path.as_str() {
+            "" => {
+                let feeds = if config.feeds.is_empty() { config::builtin_feeds() } else { config.feeds.clone() };
+                tiny_http::Response::from_string(feeds.iter().map(|f| format!("/{}.xml\n", f.id)).collect::<String>())
+            },
+            path => match path.strip_suffix(".xml").and_then(|id| config.feed(id)) {
+                None => tiny_http::Response::from_string("Not found").with_status_code(404),
+                Some(feed_config) => {
+                    let is_fresh = cache.get(&feed_config.id).is_some_and(|(generated, _)| generated.elapsed() < max_age);
+                    if !is_fresh {
+                        // Without the lock, whatever is cached gets served
+                        match acquire_lock(generator.ctx.profile, true) {
+                            Ok(_lock) => match serve_feed(generator, &feed_config) {
+                                Ok(Some(xml)) => {
+                                    cache.insert(feed_config.id.clone(), (std::time::Instant::now(), xml));
+                                },
+                                Ok(None) => (),
+                                Err(err) => error!("Generating {} failed: {:?}", feed_config.id, err),
+                            },
+                            Err(err) => error!("Not regenerating {}, unable to take the run lock: {:#}", feed_config.id, err),
+                        }
                     }
-                } else {
-                    other.summary
+
+                    match cache.get(&feed_config.id) {
+                        Some((_, xml)) => tiny_http::Response::from_string(xml.clone())
+                            .with_header(tiny_http::Header::from_bytes("Content-Type", "application/atom+xml; charset=utf-8").unwrap()),
+                        None => tiny_http::Response::from_string("Feed not available").with_status_code(503),
+                    }
+                },
+            },
+        };
+
+        if let Err(err) = request.respond(response) {
+            warn!("Unable to respond: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+fn serve_feed(generator: &Generator, feed_config: &config::FeedConfig) -> Result<Option<String>> {
+    let items = match generator.fetch_all(std::slice::from_ref(feed_config))?.remove(0).items {
+        Err(err) if err.is::<http::NotCached>() => {
+            warn!("Skipping {}: {}", feed_config.id, err);
+            return Ok(None);
+        },
+        fetched => fetched?,
+    };
+    let (feed, seen) = generator.build_feed(feed_config, items)?;
+    let content = sinks::Format::Atom.render(&feed, feed_config.compact);
+    generator.record_seen(feed_config, seen)?;
+    Ok(Some(content))
+}
+
+// Copy of an error for each feed sharing a failed fetch, keeping NotCached
+// so that those feeds are still skipped softly
+fn duplicate_error(err: &anyhow::Error) -> anyhow::Error {
+    match err.downcast_ref::<http::NotCached>() {
+        Some(not_cached) => not_cached.clone().into(),
+        None => anyhow!("{:#}", err),
+    }
+}
+
+// Random order where items more notes link to tend to come first. Each item
+// gets a key of u^(1/weight) for a uniform u, with one more weight than it
+// has backlinks, and items go by their keys, largest first.
+fn shuffle_by_backlinks(items: Vec<NewsItem>) -> Vec<NewsItem> {
+    let mut rng = rand::thread_rng();
+    let mut keyed: Vec<(f64, NewsItem)> = items.into_iter()
+        .map(|item| (rng.gen::<f64>().powf(1.0 / (item.backlinks + 1) as f64), item))
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    keyed.into_iter().map(|(_, item)| item).collect()
+}
+
+// Builds feeds from their definitions, reading items through the source
+// registry.
+struct Generator<'a> {
+    ctx: SourceContext<'a>,
+    registry: Registry,
+    // Print the selected items instead of emitting feeds, recording nothing
+    dry_run: bool,
+}
+
+impl Generator<'_> {
+    fn open_state(&self) -> Result<state::State> {
+        let mut state = state::State::open_or_default(self.ctx.profile.state_db_path.as_deref())?;
+        state.set_read_only(self.dry_run);
+        Ok(state)
+    }
+
+    // Fetch items for all the feeds concurrently, returning results in the
+    // same order as the feeds. Feeds reading from the same source share one
+    // fetch.
+    fn fetch_all(&self, feed_configs: &[config::FeedConfig]) -> Result<Vec<Fetched>> {
+        let mut sources = Vec::new();
+        let mut shared_keys: Vec<Option<String>> = Vec::new();
+        let mut source_indices = Vec::new();
+
+        for feed_config in feed_configs {
+            // A feed whose source can't be set up fails on its own
+            let source = match self.registry.build(&self.ctx, feed_config) {
+                Ok(source) => source,
+                Err(err) => {
+                    source_indices.push(Err(err));
+                    continue;
                 },
-                published: self.published,
-                updated: std::cmp::max(self.updated, other.updated),
-                authors: self.authors,
-                categories: utils::union_strings(self.categories, other.categories),
             };
-            Ok(item)
-        }
-    }
-}
-
-trait ToNewsItem {
-    fn to_newsitem(&self) -> NewsItem;
-}
-
-trait ToXmlString {
-    fn to_xml_string(&self) -> String;
-}
-
-impl ToXmlString for NewsAuthor {
-    fn to_xml_string(&self) -> String {
-        format!(r#"<author>
-  <name>{}</name>
-  <email>{}</email>
-  <uri>{}</uri>
-</author>"#,
-                self.name,
-                self.email,
-                self.uri)
-    }
-}
-
-impl ToXmlString for NewsItem {
-    fn to_xml_string(&self) -> String {
-        let template = r#"<entry>
-  <title>{{ item.title }}</title>
-  <link href="{{ item.link }}" />
-  <id>urn:uuid:{{ item.id }}</id>
-  <updated>{{ item.updated }}</updated>
-  <published>{{ item.published }}</published>
-  {%- if item.summary %}
-  <summary type="text">{{ item.summary }}</summary>
-  {%- endif %}
-  {%- for category in item.categories %}
-  <category term="{{ category }}" />
-  {%- endfor %}
-  {%- for author in authors %}
-  {{ author }}
-  {%- endfor %}
-</entry>"#;
-        let mut tera = tera::Tera::default();
-        tera.add_raw_template("news-item", template).unwrap();
-        let mut context = tera::Context::new();
-        context.insert("item", &NewsItem {
-            id: self.id.clone(),
-            title: encode_minimal(&self.title),
-            link: self.link.clone(),
-            published: self.published,
-            updated: self.updated,
-            summary: self.summary.as_ref().map(|s| encode_minimal(s)),
-            categories: self.categories.clone(),
-            authors: self.authors.clone(),
-        });
-        context.insert("authors", &self.authors.clone().into_iter().map(|a| a.to_xml_string()).collect::<Vec<_>>());
-        tera.render("news-item", &context).unwrap()
-    }
-}
-
-impl ToXmlString for NewsFeed {
-    fn to_xml_string(&self) -> String {
-        let template = r#"<?xml version="1.0" encoding="utf-8"?>
-<feed xmlns="http://www.w3.org/2005/Atom">
-  <id>{{ item.id }}</id>
-  <title>{{ item.title }}</title>
-  <subtitle>{{ item.subtitle }}</subtitle>
-  <updated>{{ item.updated }}</updated>
-  <link rel="self" href="{{ item.link }}" />
-  {%- for category in item.categories %}
-  <category term="{{ category }}" />
-  {%- endfor %}
-  {%- for author in authors %}
-  {{ author }}
-  {%- endfor %}
-  <generator>{{ item.generator }}</generator>
-{%- for entry in entries %}
-{{ entry }}
-{%- endfor %}
-</feed>"#;
-        let mut tera = tera::Tera::default();
-        tera.add_raw_template("news-feed", template).unwrap();
-        let mut context = tera::Context::new();
-        context.insert("item", &self);
-        context.insert("authors", &self.authors.clone().into_iter().map(|a| a.to_xml_string()).collect::<Vec<_>>());
-        context.insert("entries", &self.items.clone().into_iter().map(|it| it.to_xml_string()).collect::<Vec<_>>());
-        tera.render("news-feed", &context).unwrap()
+
+            let key = source.shared_key();
+            match key.as_ref().and_then(|key| shared_keys.iter().position(|k| k.as_ref() == Some(key))) {
+                Some(index) => source_indices.push(Ok(index)),
+                None => {
+                    source_indices.push(Ok(sources.len()));
+                    sources.push(source);
+                    shared_keys.push(key);
+                },
+            }
+        }
+
+        let runtime = tokio::runtime::Runtime::new()?;
+        let fetched = runtime.block_on(join_all(sources.iter().map(|source| async move {
+            let start = Instant::now();
+            let items = source.fetch().await;
+            (items, start.elapsed())
+        })));
+
+        Ok(source_indices.into_iter().map(|index| match index {
+            Ok(index) => {
+                let (items, duration) = &fetched[index];
+                Fetched {
+                    items: match items {
+                        Ok(items) => Ok(items.clone()),
+                        Err(err) => Err(duplicate_error(err)),
+                    },
+                    duration: *duration,
+                    issues: sources[index].issues(),
+                }
+            },
+            Err(err) => Fetched { items: Err(err), duration: Duration::ZERO, issues: Vec::new() },
+        }).collect())
+    }
+
+    // Build the feed from items its source returned. Delta feeds also get the
+    // ids to record as seen, which only happens once the feed is out so that
+    // a failed write doesn't lose its new items.
+    fn build_feed(&self, feed_config: &config::FeedConfig, mut items: Vec<NewsItem>) -> Result<(NewsFeed, Option<Vec<String>>)> {
+        // Before filtering, so that languages can be filtered on
+        if feed_config.detect_language {
+            items.iter_mut().for_each(language::detect_item);
+        }
+        let mut items = filters::Pipeline::parse(&feed_config.filters, &self.ctx.config.bookmarks)?.apply(items);
+        let profile = self.ctx.profile;
+
+        let mut seen = None;
+        if feed_config.delta {
+            let state = self.open_state()?;
+            let first_run = !state.has_seen_source(&feed_config.id)?;
+            let item_ids: Vec<String> = items.iter().map(|it| it.id.clone()).collect();
+            let new_ids = state.unseen_ids(&feed_config.id, &item_ids)?;
+            seen = Some(item_ids);
+
+            // On the very first run everything is new, which is not
+            // useful. That run only establishes the baseline.
+            items = if first_run {
+                Vec::new()
+            } else {
+                items.into_iter().filter(|it| new_ids.contains(&it.id)).collect()
+            };
+        }
+
+        if feed_config.skip_emitted {
+            let state = self.open_state()?;
+            let emitted = state.emitted_ids(&feed_config.id)?;
+            items.retain(|it| !emitted.contains(&it.id));
+        }
+
+        match feed_config.order {
+            config::FeedOrder::Source => (),
+            config::FeedOrder::Random => items.shuffle(&mut rand::thread_rng()),
+            config::FeedOrder::Newest => items.sort_by_key(|it| Reverse(it.updated)),
+            config::FeedOrder::Linked => items = shuffle_by_backlinks(items),
+        }
+
+        if let Some(weekly_count) = feed_config.drip {
+            let state = self.open_state()?;
+            items = drip::release(&state, &feed_config.id, items, weekly_count, chrono::Local::now().date_naive())?;
+        } else if let Some(count) = feed_config.count {
+            items.truncate(count);
+        }
+
+        // Plain text is made readable before its length is counted, while
+        // HTML content is made from the markup that is left as it is
+        if !feed_config.html_content {
+            items.iter_mut().for_each(NewsItem::convert_org_to_text);
+        }
+
+        if let Some(max_length) = feed_config.max_summary_length {
+            for item in items.iter_mut() {
+                item.truncate_summary(max_length, !feed_config.drop_full_text);
+            }
+        }
+
+        if feed_config.html_content {
+            for item in items.iter_mut() {
+                item.convert_org_to_html();
+                item.convert_org_to_text();
+            }
+        }
+
+        // Before merging, as items kept from earlier runs point at their
+        // copies already
+        if let Some(attachments) = &feed_config.attachments {
+            attachments::publish(attachments, &profile.output_path(&attachments.path), &mut items, !self.dry_run)?;
+        }
+
+        if feed_config.incremental {
+            items = self.merge_existing(feed_config, items)?;
+        }
+
+        let author = feed_config.author.clone()
+            .or(profile.author.clone())
+            .unwrap_or_else(config::default_author);
+
+        // The self link is the feed's public URL when that is known, which
+        // WebSub hubs need to identify it
+        let link = feed_config.link.clone()
+            .or_else(|| feed_config.public_url(profile.base_url.as_deref()))
+            .unwrap_or_else(|| feed_config.link());
+        let links = profile.websub_hub.iter()
+            .map(|hub| FeedLink { rel: "hub".to_string(), href: hub.clone() })
+            .collect();
+
+        Ok((NewsFeed {
+            id: feed_config.id.clone(),
+            title: feed_config.title.clone(),
+            items,
+            authors: vec![author],
+            categories: Vec::new(),
+            generator: "journalist".to_string(),
+            link,
+            updated: Utc::now(),
+            subtitle: feed_config.subtitle.clone(),
+            icon: feed_config.icon.clone(),
+            logo: feed_config.logo.clone(),
+            language: feed_config.language.clone(),
+            links,
+            ..Default::default()
+        }, seen))
+    }
+
+    // Remember the items of a delta feed as seen, once the feed is out
+    fn record_seen(&self, feed_config: &config::FeedConfig, seen: Option<Vec<String>>) -> Result<()> {
+        if let Some(item_ids) = seen {
+            self.open_state()?.record_seen(&feed_config.id, &item_ids)?;
+        }
+        Ok(())
+    }
+
+    // Build the feeds and emit them to their outputs, recording what each
+    // published. A failing feed doesn't stop the others.
+    fn generate(&self, feed_configs: &[config::FeedConfig], preview: bool) -> Result<Vec<FeedRun>> {
+        let fetched = self.fetch_all(feed_configs)?;
+
+        let runs = feed_configs.iter().zip(fetched).map(|(feed_config, fetched)| {
+            let outcome = self.generate_feed(feed_config, fetched.items, preview)
+                .unwrap_or_else(|error| Outcome::Failed { error });
+            FeedRun {
+                id: feed_config.id.clone(),
+                outcome,
+                fetch_duration: fetched.duration,
+                issues: fetched.issues,
+            }
+        }).collect();
+
+        if !self.dry_run {
+            publishers::finish_all(&self.ctx.config.publish)?;
+        }
+        Ok(runs)
+    }
+
+    fn generate_feed(&self, feed_config: &config::FeedConfig, fetched: Result<Vec<NewsItem>>, preview: bool) -> Result<Outcome> {
+        let items = match fetched {
+            Err(err) if err.is::<http::NotCached>() => {
+                // Keep whatever was generated last time instead of
+                // clobbering it with an empty feed.
+                warn!("Skipping {}: {}", feed_config.id, err);
+                return Ok(Outcome::Skipped { reason: err.to_string() });
+            },
+            fetched => fetched?,
+        };
+        let (feed, seen) = self.build_feed(feed_config, items)?;
+
+        let mut outputs = Vec::new();
+        if self.dry_run {
+            for item in &feed.items {
+                println!("{}\t{}\t{}\t{}", feed.id, item.title, item.link, item.categories.join(","));
+            }
+        } else {
+            let feed_outputs = feed_config.outputs();
+            // Nobody needs to hear about a feed that didn't change
+            let changed = emit_feed(&feed, &feed_outputs, feed_config.paging.as_ref(), self.ctx.profile, self.ctx.config)?;
+            self.record_seen(feed_config, seen)?;
+            if changed && !feed_config.email.is_empty() {
+                self.send_email(feed_config, &feed)?;
+            }
+            if let Some(activitypub) = &feed_config.activitypub {
+                let dir = self.ctx.profile.output_path(&activitypub.path);
+                sinks::activitypub::ActivityPubSink { config: activitypub, dir }.emit(&feed)?;
+            }
+            if let Some(podcast) = &feed_config.podcast {
+                podcast::write(podcast, &self.ctx.profile.output_path(&podcast.path), &feed)?;
+            }
+            if let Some(kind) = feed_config.nostr {
+                let config = self.ctx.config.nostr.as_ref()
+                    .ok_or_else(|| anyhow!("Feed {} posts to Nostr but there is no [nostr] config", feed_config.id))?;
+                self.post_new(&feed, "nostr", &sinks::nostr::NostrSink { config, kind })?;
+            }
+            if feed_config.micropub {
+                let config = self.ctx.config.micropub.as_ref()
+                    .ok_or_else(|| anyhow!("Feed {} posts to Micropub but there is no [micropub] config", feed_config.id))?;
+                self.post_new(&feed, "micropub", &sinks::micropub::MicropubSink { config })?;
+            }
+            if feed_config.telegram {
+                let config = self.ctx.config.telegram.as_ref()
+                    .ok_or_else(|| anyhow!("Feed {} sends to Telegram but there is no [telegram] config", feed_config.id))?;
+                self.post_new(&feed, "telegram", &sinks::telegram::TelegramSink { config })?;
+            }
+            if let Some(webhook_url) = &feed_config.discord {
+                self.post_new(&feed, "discord", &sinks::discord::DiscordSink { webhook_url })?;
+            }
+            if let Some(webhook_url) = &feed_config.slack {
+                self.post_new(&feed, "slack", &sinks::slack::SlackSink { webhook_url })?;
+            }
+            if feed_config.wallabag {
+                let config = self.ctx.config.wallabag.as_ref()
+                    .ok_or_else(|| anyhow!("Feed {} saves to Wallabag but there is no [wallabag] config", feed_config.id))?;
+                self.post_new(&feed, "wallabag", &sinks::wallabag::WallabagSink::new(config))?;
+            }
+            if feed_config.linkding {
+                let config = self.ctx.config.linkding.as_ref()
+                    .ok_or_else(|| anyhow!("Feed {} saves to linkding but there is no [linkding] config", feed_config.id))?;
+                self.post_new(&feed, "linkding", &sinks::linkding::LinkdingSink { config })?;
+            }
+            if let Some(mode) = feed_config.push {
+                let config = &self.ctx.config.notify;
+                if config.is_empty() {
+                    return Err(anyhow!("Feed {} pushes notifications but there is nothing under [notify]", feed_config.id));
+                }
+                let sink = sinks::push::PushSink { config, mode };
+                match mode {
+                    sinks::push::PushMode::Item => self.post_new(&feed, "push", &sink)?,
+                    sinks::push::PushMode::Digest => self.post_new_at_once(&feed, "push", &sink)?,
+                }
+            }
+            if let Some(hub) = self.ctx.profile.websub_hub.as_ref().filter(|_| changed) {
+                announce(hub, &feed);
+            }
+            self.open_state()?
+                .record_emitted(&feed.id, &feed.items.iter().map(|it| it.id.clone()).collect::<Vec<_>>())?;
+            if preview {
+                print!("{}", preview::render_xml(&feed.to_xml_string())?);
+            }
+
+            outputs = feed_outputs.iter()
+                .map(|output| if output.is_stdout() { output.path.clone() } else { self.ctx.profile.output_path(&output.path) })
+                .collect();
+        }
+
+        Ok(Outcome::Generated { items: feed.items.len(), outputs })
+    }
+
+    // Hand the items of the feed that weren't posted to the sink before over
+    // to it. Sinks like Nostr keep what they get, so every item goes once.
+    // Items are posted one at a time so that a failure midway doesn't get the
+    // earlier ones posted again on the next run.
+    fn post_new(&self, feed: &NewsFeed, sink_name: &str, sink: &dyn Sink) -> Result<()> {
+        let state = self.open_state()?;
+        let posted = state.posted_ids(&feed.id, sink_name)?;
+
+        for item in feed.items.iter().filter(|it| !posted.contains(&it.id)) {
+            sink.emit(&NewsFeed { items: vec![item.clone()], ..feed.clone() })?;
+            state.record_posted(&feed.id, sink_name, std::slice::from_ref(&item.id))?;
+        }
+        Ok(())
+    }
+
+    // Like post_new, but with all the new items handed over together, for
+    // sinks that sum them up
+    fn post_new_at_once(&self, feed: &NewsFeed, sink_name: &str, sink: &dyn Sink) -> Result<()> {
+        let state = self.open_state()?;
+        let posted = state.posted_ids(&feed.id, sink_name)?;
+
+        let items: Vec<NewsItem> = feed.items.iter().filter(|it| !posted.contains(&it.id)).cloned().collect();
+        if items.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<String> = items.iter().map(|it| it.id.clone()).collect();
+        sink.emit(&NewsFeed { items, ..feed.clone() })?;
+        state.record_posted(&feed.id, sink_name, &ids)
+    }
+
+    // Mail the feed to its recipients, unless there is nothing in it
+    fn send_email(&self, feed_config: &config::FeedConfig, feed: &NewsFeed) -> Result<()> {
+        if feed.items.is_empty() {
+            info!("Not emailing {} as it has no items", feed.id);
+            return Ok(());
+        }
+
+        let smtp = self.ctx.config.smtp.as_ref()
+            .ok_or_else(|| anyhow!("Feed {} has email recipients but there is no [smtp] config", feed_config.id))?;
+        sinks::email::EmailSink { smtp, to: &feed_config.email }.emit(feed)
+    }
+
+    // Merge items into the ones already in the feed's first Atom output file,
+    // if there is one
+    fn merge_existing(&self, feed_config: &config::FeedConfig, items: Vec<NewsItem>) -> Result<Vec<NewsItem>> {
+        let existing_path = feed_config.outputs().into_iter()
+            .find(|output| !output.is_stdout() && output.format == sinks::Format::Atom && output.template.is_none())
+            .map(|output| self.ctx.profile.output_path(&output.path))
+            .filter(|path| path.exists());
+
+        let existing = match existing_path {
+            Some(path) => merge::read_items(&path)?,
+            None => Vec::new(),
+        };
+
+        Ok(merge::merge_items(items, existing, feed_config.keep.unwrap_or(DEFAULT_KEEP)))
     }
 }
 
 fn main() -> Result<()> {
     let args = Cli::parse();
-    let mut rng = rand::thread_rng();
     env_logger::init();
 
-    let author: NewsAuthor = NewsAuthor {
-        name: "Abhinav Tushar".to_string(),
-        email: "abhinav@lepisma.xyz".to_string(),
-        uri: "lepisma.xyz".to_string(),
-    };
+    let config = config::Config::load(args.config.as_deref())?;
+    let result = run(args, &config);
 
+    if let Err(err) = &result {
+        if err.downcast_ref::<FeedsFailed>().is_none() {
+            notifications::notify_failure(&config.notify, err);
+        }
+    }
+
+    result
+}
+
+fn run(args: Cli, config: &config::Config) -> Result<()> {
+    let mut profile = config.profile(args.profile.as_deref())?;
+    let registry = build_registry(&args)?;
+
+    // Paths from the command line take precedence over the profile's
+    if args.roam_db_path.is_some() || args.notes_dir_path.is_some() {
+        profile.roam_db_path = args.roam_db_path;
+        profile.notes_dir_path = args.notes_dir_path;
+    }
+    if args.notes_max_depth.is_some() {
+        profile.notes_max_depth = args.notes_max_depth;
+    }
+    if !args.exclude.is_empty() {
+        profile.notes_exclude = Some(args.exclude);
+    }
+    if args.timezone.is_some() {
+        profile.timezone = args.timezone;
+    }
 
     match args.command {
         Commands::Merge { input: _, output_file: _ } => {
             return Err(anyhow!("Merge operation not implemented yet!"));
         },
-        Commands::Generate { gen_command } => {
-            let bookmarks: Vec<_>;
-            let feed: NewsFeed;
-
-            match gen_command {
-                GenCommands::PileBookmarks { roam_db_path, notes_dir_path, output_file } => {
-                    if let Some(db_path) = roam_db_path {
-                        bookmarks = pile::read_bookmarks(db_path.as_path());
-                    } else if let Some(dir_path) = notes_dir_path {
-                        bookmarks = pile::read_bookmarks_from_dir(dir_path.as_path());
-                    } else {
-                        panic!("Need either --notes-dir-path or --roam-db-path to be set!");
-                    }
+        Commands::Backfill { from, to, recommended, mut filter, output_dir } => {
+            let Some(_lock) = acquire_lock(&profile, args.wait_for_lock)? else {
+                eprintln!("Another run is in progress, exiting");
+                return Ok(());
+            };
+            let from = parse_month(&from)?;
+            let to = parse_month(&to)?;
+            let output_dir = profile.output_path(&output_dir);
+            std::fs::create_dir_all(&output_dir)?;
+            let author = profile.author.clone().unwrap_or_else(config::default_author);
 
-                    let mut general_bookmarks: Vec<_> = bookmarks
-                        .iter()
-                        .filter(|bm| bm.is_unread())
-                        .filter(|bm| !bm.is_project())
-                        .collect();
-
-                    general_bookmarks.shuffle(&mut rng);
-
-                    feed = NewsFeed {
-                        id: "pile-bookmarks".to_string(),
-                        title: "General Bookmarks".to_string(),
-                        items: general_bookmarks.iter().map(|bm| bm.to_newsitem()).take(2).collect(),
-                        authors: vec![author.clone()],
-                        categories: Vec::new(),
-                        generator: "journalist".to_string(),
-                        link: "/pile-bookmarks".to_string(),
-                        updated: Utc::now(),
-                        subtitle: "Unread picks from saved bookmarks.".to_string(),
-                    };
-
-                    let mut feed_file = File::create(output_file)?;
-                    feed_file.write_all(feed.to_xml_string().as_bytes())?;
+            if recommended {
+                filter.push("recommended".to_string());
+            }
+            let pipeline = filters::Pipeline::parse(&filter, &config.bookmarks)?;
+
+            let bookmarks = pile::PileSource::from_profile(&profile)?.read_bookmarks();
+            let mut by_month: BTreeMap<NaiveDate, Vec<NewsItem>> = BTreeMap::new();
+            for item in pipeline.apply(bookmarks.iter().map(|bm| bm.to_newsitem()).collect()) {
+                let month = item.published.date_naive().with_day(1).unwrap();
+                if month >= from && month <= to {
+                    by_month.entry(month).or_default().push(item);
+                }
+            }
+
+            for (month, mut items) in by_month {
+                items.sort_by_key(|it| Reverse(it.published));
+                let id = format!("archive-{}", month.format("%Y-%m"));
+
+                let feed = NewsFeed {
+                    id: id.clone(),
+                    title: format!("Bookmarks from {}", month.format("%B %Y")),
+                    items,
+                    authors: vec![author.clone()],
+                    categories: Vec::new(),
+                    generator: "journalist".to_string(),
+                    link: format!("/{}", id),
+                    updated: Utc::now(),
+                    subtitle: format!("Archive of bookmarks saved in {}.", month.format("%B %Y")),
+                    ..Default::default()
+                };
+
+                write_feed(&feed, &output_dir.join(format!("{}.xml", id)), config)?;
+            }
+        },
+        Commands::Digest { feed, since, format, full_text, filter, output } => {
+            let since = digest::parse_since(&since)?;
+            let mut feed_config = config.feed(&feed).ok_or_else(|| anyhow!("Feed {} not defined", feed))?;
+            feed_config.filters.extend(filter);
+
+            let generator = Generator {
+                ctx: SourceContext { config, profile: &profile, offline: args.offline },
+                registry,
+                dry_run: false,
+            };
+            let fetched = generator.fetch_all(std::slice::from_ref(&feed_config))?.remove(0);
+
+            let now = Utc::now();
+            let mut items = filters::Pipeline::parse(&feed_config.filters, &config.bookmarks)?.apply(fetched.items?);
+            items.retain(|it| it.published >= now - since);
+            items.sort_by_key(|it| Reverse(it.published));
+
+            if full_text {
+                let fetcher = http::Fetcher::new(args.offline, &config.http_options("digest"))?;
+                tokio::runtime::Runtime::new()?.block_on(digest::fetch_full_text(&fetcher, &mut items));
+            }
+
+            let feed = NewsFeed {
+                id: format!("{}-digest", feed_config.id),
+                title: format!("{}, {} to {}", feed_config.title, (now - since).format("%B %-d, %Y"), now.format("%B %-d, %Y")),
+                items,
+                authors: vec![feed_config.author.clone().or(profile.author.clone()).unwrap_or_else(config::default_author)],
+                categories: Vec::new(),
+                generator: "journalist".to_string(),
+                link: feed_config.link(),
+                updated: now,
+                subtitle: feed_config.subtitle.clone(),
+                ..Default::default()
+            };
+
+            let output = output.unwrap_or_else(|| format!("{}.{}", feed.id, format.extension()).into());
+            let output = profile.output_path(&output);
+            format.write(&feed, &output)?;
+            eprintln!("Wrote {} items to {}", feed.items.len(), output.display());
+            publishers::publish_all(&config.publish, &output)?;
+            publishers::finish_all(&config.publish)?;
+        },
+        Commands::Daemon => {
+            let generator = Generator {
+                ctx: SourceContext { config, profile: &profile, offline: args.offline },
+                registry,
+                dry_run: false,
+            };
+            run_daemon(&generator, config)?;
+        },
+        Commands::Watch { feeds } => {
+            let feeds: Vec<config::FeedConfig> = if feeds.is_empty() {
+                config.feeds.iter().filter(|f| f.source == "pile").cloned().collect()
+            } else {
+                feeds.iter()
+                    .map(|id| config.feed(id).ok_or_else(|| anyhow!("Feed {} not defined", id)))
+                    .collect::<Result<_>>()?
+            };
+            if feeds.is_empty() {
+                return Err(anyhow!("No pile feeds defined in the config, pass the feeds to regenerate"));
+            }
+
+            let generator = Generator {
+                ctx: SourceContext { config, profile: &profile, offline: args.offline },
+                registry,
+                dry_run: false,
+            };
+            run_watch(&generator, &feeds, config)?;
+        },
+        Commands::Serve { address, max_age } => {
+            let generator = Generator {
+                ctx: SourceContext { config, profile: &profile, offline: args.offline },
+                registry,
+                dry_run: false,
+            };
+            run_server(&generator, config, &address, std::time::Duration::from_secs(max_age))?;
+        },
+        Commands::Export { format: ExportFormat::Opml { output_file } } => {
+            let outlines: Vec<opml::Outline> = config.feeds.iter()
+                .filter_map(|feed| match feed.public_url(profile.base_url.as_deref()) {
+                    Some(xml_url) => Some(opml::Outline { title: feed.title.clone(), description: feed.subtitle.clone(), xml_url }),
+                    None => {
+                        warn!("Leaving out {} as it has no public URL", feed.id);
+                        None
+                    },
+                })
+                .collect();
+            if outlines.is_empty() {
+                return Err(anyhow!("No feeds with a public URL, set base_url in the config"));
+            }
+
+            let opml = opml::to_opml_string("journalist feeds", &outlines)?;
+            match output_file {
+                Some(output_file) => sinks::write_atomic(&output_file, opml.as_bytes())?,
+                None => print!("{}", opml),
+            }
+        },
+        Commands::State { action } => {
+            let state = state::State::open_or_default(profile.state_db_path.as_deref())?;
+            match action {
+                StateAction::Show { feed: None } => {
+                    for summary in state.feed_summaries()? {
+                        println!("{}\t{} items\tlast emitted {}", summary.feed, summary.items, summary.last_emitted);
+                    }
                 },
-                GenCommands::PileBookmarksProjects { roam_db_path, notes_dir_path, output_file } => {
-                    if let Some(db_path) = roam_db_path {
-                        bookmarks = pile::read_bookmarks(db_path.as_path());
-                    } else if let Some(dir_path) = notes_dir_path {
-                        bookmarks = pile::read_bookmarks_from_dir(dir_path.as_path());
-                    } else {
-                        panic!("Need either --notes-dir-path or --roam-db-path to be set!");
+                StateAction::Show { feed: Some(feed) } => {
+                    for (item_id, first_emitted, last_emitted) in state.emitted_items(&feed)? {
+                        println!("{}\tfirst emitted {}\tlast emitted {}", item_id, first_emitted, last_emitted);
                     }
-
-                    let mut project_bookmarks: Vec<_> = bookmarks
-                        .iter()
-                        .filter(|bm| bm.is_unread())
-                        .filter(|bm| bm.is_project())
-                        .collect();
-
-                    project_bookmarks.shuffle(&mut rng);
-
-                    feed = NewsFeed {
-                        id: "pile-bookmarks-projects".to_string(),
-                        title: "Unsorted Projects".to_string(),
-                        items: project_bookmarks.iter().map(|bm| bm.to_newsitem()).take(1).collect(),
-                        authors: vec![author.clone()],
-                        categories: Vec::new(),
-                        generator: "journalist".to_string(),
-                        link: "/pile-bookmarks-projects".to_string(),
-                        updated: Utc::now(),
-                        subtitle: "Unsorted projects from saved bookmarks.".to_string(),
-                    };
-
-                    let mut feed_file = File::create(output_file)?;
-                    feed_file.write_all(feed.to_xml_string().as_bytes())?;
                 },
-                GenCommands::HfPapers { output_file } => {
-                    let papers = hf::read_weekly_papers(hf::get_current_week())?;
-
-                    feed = NewsFeed {
-                        id: "hf-papers".to_string(),
-                        title: "Huggingface papers".to_string(),
-                        items: papers.iter().map(|p| p.to_newsitem()).take(5).collect(),
-                        authors: vec![author.clone()],
-                        categories: Vec::new(),
-                        generator: "journalist".to_string(),
-                        link: "/hf-papers".to_string(),
-                        updated: Utc::now(),
-                        subtitle: "Papers from Huggingface Daily Papers.".to_string(),
-                    };
-
-                    let mut feed_file = File::create(output_file)?;
-                    feed_file.write_all(feed.to_xml_string().as_bytes())?;
+                StateAction::Reset { feed: None, all: false } => {
+                    return Err(anyhow!("Pass a feed to reset, or --all to reset every feed"));
+                },
+                StateAction::Reset { feed, all: _ } => state.reset(feed.as_deref())?,
+            }
+        },
+        Commands::Preview { feed_file } => {
+            let xml = std::fs::read_to_string(&feed_file)?;
+            print!("{}", preview::render_xml(&xml)?);
+        },
+        Commands::Generate { preview, dry_run, fail_on, report, max_summary_length, drop_full_text, html_content, compact, compress, drip, delta, filter, language, min_votes, week, feed, output_file, output, format, template, item_template, metadata, websub_hub } => {
+            if websub_hub.is_some() {
+                profile.websub_hub = websub_hub;
+            }
+            let generator = Generator {
+                ctx: SourceContext { config, profile: &profile, offline: args.offline },
+                registry,
+                dry_run,
+            };
+
+            // Outputs from the command line replace the configured ones
+            let cli_outputs: Vec<sinks::OutputConfig> = output_file.into_iter()
+                .chain(output)
+                .map(|path| sinks::OutputConfig { template: template.clone(), item_template: item_template.clone(), ..sinks::OutputConfig::new(path, format) })
+                .collect();
+
+            let mut feeds: Vec<config::FeedConfig> = match feed.filter(|id| id != "all") {
+                None => {
+                    if config.feeds.is_empty() {
+                        return Err(anyhow!("No feeds defined in the config, pass a feed to generate"));
+                    }
+                    config.feeds.clone()
                 },
-                GenCommands::RecommendedLinks { roam_db_path, notes_dir_path, output_file } => {
-                    if let Some(db_path) = roam_db_path {
-                        bookmarks = pile::read_bookmarks(db_path.as_path());
-                    } else if let Some(dir_path) = notes_dir_path {
-                        bookmarks = pile::read_bookmarks_from_dir(dir_path.as_path());
-                    } else {
-                        panic!("Need either --notes-dir-path or --roam-db-path to be set!");
+                Some(id) => {
+                    let mut feed_config = config.feed(&id).ok_or_else(|| anyhow!("Feed {} not defined", id))?;
+                    if !cli_outputs.is_empty() {
+                        feed_config.outputs = cli_outputs;
                     }
+                    vec![feed_config]
+                },
+            };
+
+            if feeds.len() > 1 && (metadata.title.is_some() || metadata.subtitle.is_some() || metadata.link.is_some()) {
+                return Err(anyhow!("--title, --subtitle, and --link need a single feed to generate"));
+            }
 
-                    let mut recommended_items: Vec<_> = bookmarks
-                        .iter()
-                        .filter(|bm| bm.is_recommended())
-                        .map(|bm| bm.to_newsitem())
-                        .collect();
-
-                    recommended_items.sort_by_key(|it| Reverse(it.updated));
-
-                    feed = NewsFeed {
-                        id: "recommended-links".to_string(),
-                        title: "lepisma's recommended links".to_string(),
-                        items: recommended_items,
-                        authors: vec![author.clone()],
-                        categories: Vec::new(),
-                        generator: "journalist".to_string(),
-                        link: "/recommended-links".to_string(),
-                        updated: Utc::now(),
-                        subtitle: "Recommendations from lepisma's list of read articles and bookmarks".to_string()
-                    };
-
-                    let mut feed_file = File::create(output_file)?;
-                    feed_file.write_all(feed.to_xml_string().as_bytes())?;
+            for feed_config in feeds.iter_mut() {
+                // Command line flags take precedence over the config
+                metadata.apply(feed_config, &profile);
+                if drip.is_some() {
+                    feed_config.drip = drip;
+                }
+                if max_summary_length.is_some() {
+                    feed_config.max_summary_length = max_summary_length;
+                }
+                if drop_full_text {
+                    feed_config.drop_full_text = true;
+                }
+                if html_content {
+                    feed_config.html_content = true;
+                }
+                if compact {
+                    feed_config.compact = true;
                 }
+                feed_config.compress.extend(compress.iter().copied());
+                feed_config.filters.extend(filter.iter().cloned());
+                if let Some(language) = &language {
+                    feed_config.detect_language = true;
+                    feed_config.filters.push(format!("lang:{}", language));
+                }
+                if min_votes.is_some() {
+                    feed_config.min_votes = min_votes;
+                }
+                if !week.is_empty() {
+                    feed_config.weeks = week.clone();
+                }
+                if delta {
+                    feed_config.id = format!("{}-delta", feed_config.id);
+                    feed_config.delta = true;
+                }
+            }
+
+            // Dry runs write nothing and can overlap with real ones
+            let _lock = if dry_run {
+                None
+            } else {
+                let Some(lock) = acquire_lock(&profile, args.wait_for_lock)? else {
+                    eprintln!("Another run is in progress, exiting");
+                    return Ok(());
+                };
+                Some(lock)
+            };
+
+            let started = Utc::now();
+            let runs = generator.generate(&feeds, preview)?;
+            if let Some(report_path) = &report {
+                write_report(report_path, &runs, started)?;
+            }
+            if runs.len() > 1 || runs.iter().any(|run| !matches!(run.outcome, Outcome::Generated { .. })) {
+                print_summary(&runs);
+            }
+            // Every failed feed is notified about, whether or not it fails
+            // the run
+            let failures = check_failures(&runs, fail_on);
+            for error in feed_errors(runs) {
+                notifications::notify_failure(&config.notify, &error);
             }
+            failures?;
         }
     }
 
@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+use std::{fs, path};
+use anyhow::{Context, Result};
+use atom_syndication::{Entry, Feed, Person};
+use chrono::Utc;
+
+use crate::{Enclosure, NewsAuthor, NewsItem, ToNewsItem};
+
+fn to_author(person: &Person) -> NewsAuthor {
+    NewsAuthor {
+        name: person.name.clone(),
+        email: person.email.clone().unwrap_or_default(),
+        uri: person.uri.clone().unwrap_or_default(),
+    }
+}
+
+impl ToNewsItem for Entry {
+    fn to_newsitem(&self) -> NewsItem {
+        let updated = self.updated.with_timezone(&Utc);
+        NewsItem {
+            // Ids are written out as urn:uuid:<id>
+            id: self.id.strip_prefix("urn:uuid:").unwrap_or(&self.id).to_string(),
+            link: self.links.iter().find(|link| link.rel == "alternate").map(|link| link.href.clone()).unwrap_or_default(),
+            title: self.title.value.clone(),
+            summary: self.summary.as_ref().map(|text| text.value.clone()),
+            content: self.content.as_ref().and_then(|content| content.value.clone()),
+            content_html: self.content.as_ref().and_then(|content| content.content_type.as_deref()) == Some("html"),
+            published: self.published.map(|published| published.with_timezone(&Utc)).unwrap_or(updated),
+            updated,
+            authors: self.authors.iter().map(to_author).collect(),
+            categories: self.categories.iter().map(|category| category.term.clone()).collect(),
+            enclosures: self.links.iter()
+                .filter(|link| link.rel == "enclosure")
+                .map(|link| Enclosure {
+                    url: link.href.clone(),
+                    mime_type: link.mime_type.clone().unwrap_or_default(),
+                    length: link.length.as_ref().and_then(|length| length.parse().ok()),
+                })
+                .collect(),
+            related: self.links.iter()
+                .filter(|link| link.rel == "related")
+                .map(|link| link.href.clone())
+                .collect(),
+            language: self.title.lang.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+// Items of an Atom feed file, in the order they appear. Older documents of a
+// paged feed, linked via prev-archive or next, are read after it.
+pub fn read_items(feed_path: &path::Path) -> Result<Vec<NewsItem>> {
+    let mut items = Vec::new();
+    let mut visited = HashSet::new();
+    let mut next_path = Some(feed_path.to_path_buf());
+
+    while let Some(document_path) = next_path.take() {
+        if !visited.insert(document_path.clone()) {
+            break;
+        }
+        let xml = fs::read_to_string(&document_path)?;
+        let feed: Feed = xml.parse().with_context(|| format!("Unable to parse {}", document_path.display()))?;
+        items.extend(feed.entries.iter().map(|entry| entry.to_newsitem()));
+
+        next_path = feed.links.iter()
+            .find(|link| link.rel == "prev-archive" || link.rel == "next")
+            .map(|link| document_path.with_file_name(&link.href));
+    }
+
+    Ok(items)
+}
+
+// Put items that are not already in the existing ones on top and keep at most
+// `keep` items overall. Existing items are kept as they were so that readers
+// don't see them change between runs. Items are compared by the ids they are
+// written out with, which is all existing items read from feeds have.
+pub fn merge_items(items: Vec<NewsItem>, existing: Vec<NewsItem>, keep: usize) -> Vec<NewsItem> {
+    let existing_ids: HashSet<String> = existing.iter().map(|it| it.feed_id()).collect();
+
+    let mut output: Vec<NewsItem> = items.into_iter()
+        .filter(|it| !existing_ids.contains(&it.feed_id()))
+        .collect();
+    output.extend(existing);
+    output.truncate(keep);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paging::{paginate, PagingConfig, PagingStyle};
+    use crate::{NewsFeed, ToXmlString};
+
+    fn item(id: &str) -> NewsItem {
+        NewsItem {
+            // Items read back have the UUIDs they were written out with, so
+            // these compare equal to them
+            id: NewsItem::stable_id("test", id),
+            link: format!("https://example.com/{}", id),
+            title: format!("Item <{}>", id),
+            summary: Some("A & B".to_string()),
+            published: Utc::now(),
+            updated: Utc::now(),
+            categories: vec!["tag".to_string()],
+            enclosures: vec![Enclosure { url: format!("https://example.com/{}.pdf", id), mime_type: "application/pdf".to_string(), length: Some(1024) }],
+            related: vec![format!("https://example.com/{}/discussion", id)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn new_items_go_on_top() {
+        let existing = vec![item("b"), item("c")];
+        let merged = merge_items(vec![item("a"), item("b")], existing, 2);
+        assert_eq!(merged.iter().map(|it| it.id.clone()).collect::<Vec<_>>(), vec![item("a").id, item("b").id]);
+    }
+
+    #[test]
+    fn plain_ids_match_their_written_out_items() {
+        let plain = |id: &str| NewsItem { id: id.to_string(), ..item(id) };
+        let feed = NewsFeed {
+            id: "test".to_string(),
+            title: "Test".to_string(),
+            items: vec![plain("20240102T103000"), plain("abc#https://example.com")],
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let feed_path = dir.path().join("test.xml");
+        fs::write(&feed_path, feed.to_xml_string()).unwrap();
+
+        let existing = read_items(&feed_path).unwrap();
+        let merged = merge_items(vec![plain("new"), plain("20240102T103000"), plain("abc#https://example.com")], existing, 10);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].id, "new");
+    }
+
+    #[test]
+    fn written_items_are_read_back() {
+        let feed = NewsFeed {
+            id: "test".to_string(),
+            updated: Utc::now(),
+            link: "/test".to_string(),
+            title: "Test".to_string(),
+            subtitle: String::new(),
+            items: vec![item("a")],
+            authors: Vec::new(),
+            categories: Vec::new(),
+            generator: "journalist".to_string(),
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let feed_path = dir.path().join("feed.xml");
+        fs::write(&feed_path, feed.to_xml_string()).unwrap();
+
+        let items = read_items(&feed_path).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, item("a").id);
+        assert_eq!(items[0].title, "Item <a>");
+        assert_eq!(items[0].summary.as_deref(), Some("A & B"));
+        assert_eq!(items[0].categories, vec!["tag"]);
+        assert_eq!(items[0].link, "https://example.com/a");
+        assert_eq!(items[0].enclosures, item("a").enclosures);
+        assert_eq!(items[0].related, item("a").related);
+    }
+
+    #[test]
+    fn archives_are_read_back() {
+        let feed = NewsFeed {
+            id: "test".to_string(),
+            title: "Test".to_string(),
+            items: vec![item("c"), item("b"), item("a")],
+            ..Default::default()
+        };
+        let config = PagingConfig { page_size: 1, style: PagingStyle::Archive };
+        let dir = tempfile::tempdir().unwrap();
+        for (file_name, document) in paginate(feed, "test.xml", &config) {
+            fs::write(dir.path().join(file_name), document.to_xml_string()).unwrap();
+        }
+
+        let items = read_items(&dir.path().join("test.xml")).unwrap();
+        assert_eq!(items.iter().map(|it| it.id.clone()).collect::<Vec<_>>(), vec![item("c").id, item("b").id, item("a").id]);
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::{anyhow, Result};
+use log::error;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+// Push notification services used to report failed runs. Each one is enabled
+// by having its table under [notify] in the config.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct NotifyConfig {
+    pub ntfy: Option<NtfyConfig>,
+    pub pushover: Option<PushoverConfig>,
+    pub gotify: Option<GotifyConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NtfyConfig {
+    #[serde(default = "default_ntfy_server")]
+    pub server: String,
+    pub topic: String,
+    pub token: Option<String>,
+}
+
+fn default_ntfy_server() -> String {
+    "https://ntfy.sh".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PushoverConfig {
+    pub token: String,
+    pub user: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GotifyConfig {
+    pub url: String,
+    pub token: String,
+}
+
+fn check(response: reqwest::blocking::Response) -> Result<()> {
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!("{}: {}", response.status(), response.text().unwrap_or_default()))
+    }
+}
+
+// What to send. Failures stand out more than the nudges about new items.
+pub struct Notification<'a> {
+    pub title: &'a str,
+    pub message: &'a str,
+    // Opened when the notification is tapped
+    pub url: Option<&'a str>,
+    pub failure: bool,
+}
+
+// Published as JSON to the server root rather than with headers, which can't
+// carry titles that aren't ASCII
+fn ntfy_request(client: &Client, config: &NtfyConfig, notification: &Notification) -> reqwest::blocking::RequestBuilder {
+    let mut body = serde_json::json!({
+        "topic": config.topic,
+        "title": notification.title,
+        "message": notification.message,
+    });
+    if notification.failure {
+        body["tags"] = serde_json::json!(["warning"]);
+    }
+    if let Some(click) = notification.url {
+        body["click"] = serde_json::json!(click);
+    }
+    let mut request = client.post(config.server.trim_end_matches('/')).json(&body);
+    if let Some(token) = &config.token {
+        request = request.bearer_auth(token);
+    }
+    request
+}
+
+fn send_ntfy(client: &Client, config: &NtfyConfig, notification: &Notification) -> Result<()> {
+    check(ntfy_request(client, config, notification).send()?)
+}
+
+fn send_pushover(client: &Client, config: &PushoverConfig, notification: &Notification) -> Result<()> {
+    let mut params = vec![
+        ("token", config.token.as_str()),
+        ("user", config.user.as_str()),
+        ("title", notification.title),
+        ("message", notification.message),
+    ];
+    if let Some(url) = notification.url {
+        params.push(("url", url));
+    }
+    check(client.post("https://api.pushover.net/1/messages.json").form(&params).send()?)
+}
+
+fn send_gotify(client: &Client, config: &GotifyConfig, notification: &Notification) -> Result<()> {
+    let url = format!("{}/message", config.url.trim_end_matches('/'));
+    let mut body = serde_json::json!({
+        "title": notification.title,
+        "message": notification.message,
+        "priority": if notification.failure { 8 } else { 5 },
+    });
+    if let Some(click) = notification.url {
+        body["extras"] = serde_json::json!({ "client::notification": { "click": { "url": click } } });
+    }
+    check(client.post(url).query(&[("token", &config.token)]).json(&body).send()?)
+}
+
+impl NotifyConfig {
+    pub fn is_empty(&self) -> bool {
+        self.ntfy.is_none() && self.pushover.is_none() && self.gotify.is_none()
+    }
+}
+
+// Send the notification to every configured service, with how it went for
+// each of them
+pub fn send(config: &NotifyConfig, notification: &Notification) -> Vec<(&'static str, Result<()>)> {
+    let client = Client::new();
+    let mut results = Vec::new();
+
+    if let Some(ntfy) = &config.ntfy {
+        results.push(("ntfy", send_ntfy(&client, ntfy, notification)));
+    }
+    if let Some(pushover) = &config.pushover {
+        results.push(("Pushover", send_pushover(&client, pushover, notification)));
+    }
+    if let Some(gotify) = &config.gotify {
+        results.push(("Gotify", send_gotify(&client, gotify, notification)));
+    }
+    results
+}
+
+// Send the message to every configured service. Failures here are only
+// logged since this runs while we are already reporting another error.
+pub fn send_all(config: &NotifyConfig, title: &str, message: &str) {
+    let notification = Notification { title, message, url: None, failure: true };
+    for (service, result) in send(config, &notification) {
+        if let Err(err) = result {
+            error!("Failed to notify via {}: {}", service, err);
+        }
+    }
+}
+
+pub fn notify_failure(config: &NotifyConfig, err: &anyhow::Error) {
+    send_all(config, "journalist run failed", &format!("{:#}", err));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ntfy_titles_can_be_any_text() {
+        let config = NtfyConfig { server: "https://ntfy.sh/".to_string(), topic: "journalist".to_string(), token: None };
+        let notification = Notification { title: "Nouveautés ✨ 新しい", message: "3 new items", url: Some("https://example.com/feed.xml"), failure: false };
+        let request = ntfy_request(&Client::new(), &config, &notification).build().unwrap();
+        assert_eq!(request.url().as_str(), "https://ntfy.sh/");
+
+        let body: serde_json::Value = serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(body["topic"], "journalist");
+        assert_eq!(body["title"], "Nouveautés ✨ 新しい");
+        assert_eq!(body["click"], "https://example.com/feed.xml");
+        assert!(body.get("tags").is_none());
+    }
+}
@@ -0,0 +1,62 @@
+// OPML subscription lists of feeds, for importing them into readers at once
+
+use anyhow::Result;
+use chrono::Utc;
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+
+// A feed as listed in the OPML body
+pub struct Outline {
+    pub title: String,
+    pub description: String,
+    pub xml_url: String,
+}
+
+pub fn to_opml_string(title: &str, outlines: &[Outline]) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+
+    writer.create_element("opml")
+        .with_attribute(("version", "2.0"))
+        .write_inner_content(|writer| {
+            writer.create_element("head").write_inner_content(|writer| {
+                writer.create_element("title").write_text_content(BytesText::new(title))?;
+                writer.create_element("dateCreated").write_text_content(BytesText::new(&Utc::now().to_rfc2822()))?;
+                Ok(())
+            })?;
+            writer.create_element("body").write_inner_content(|writer| {
+                for outline in outlines {
+                    let mut element = writer.create_element("outline")
+                        .with_attribute(("type", "rss"))
+                        .with_attribute(("text", outline.title.as_str()))
+                        .with_attribute(("title", outline.title.as_str()));
+                    if !outline.description.is_empty() {
+                        element = element.with_attribute(("description", outline.description.as_str()));
+                    }
+                    element.with_attribute(("xmlUrl", outline.xml_url.as_str())).write_empty()?;
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outlines_are_escaped() {
+        let outlines = vec![Outline {
+            title: "Q&A".to_string(),
+            description: String::new(),
+            xml_url: "https://example.com/feeds/qa.xml?a=1&b=2".to_string(),
+        }];
+        let opml = to_opml_string("My feeds", &outlines).unwrap();
+
+        assert!(opml.contains("<title>My feeds</title>"));
+        assert!(opml.contains(r#"<outline type="rss" text="Q&amp;A" title="Q&amp;A" xmlUrl="https://example.com/feeds/qa.xml?a=1&amp;b=2"/>"#));
+    }
+}
@@ -0,0 +1,279 @@
+// A small org markup to HTML and plain text converter for bookmark notes. It
+// covers what shows up in summaries: paragraphs, headings, plain lists, quote
+// and source blocks, drawers, links, and emphasis. Anything else passes
+// through as text.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static LIST_ITEM_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*(?:([-+])|\d+[.)])\s+(.*)$").unwrap());
+static HEADING_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\*+)\s+(.*)$").unwrap());
+static BLOCK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^\s*#\+(begin|end)_(\w+)").unwrap());
+static DRAWER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*:([\w-]+):\s*$").unwrap());
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+enum Block {
+    Paragraph(Vec<String>),
+    List { ordered: bool, items: Vec<String> },
+}
+
+fn close(block: Option<Block>, html: &mut String) {
+    match block {
+        Some(Block::Paragraph(lines)) => html.push_str(&format!("<p>{}</p>\n", inline(&lines.join(" ")))),
+        Some(Block::List { ordered, items }) => {
+            let tag = if ordered { "ol" } else { "ul" };
+            html.push_str(&format!("<{}>\n", tag));
+            for item in items {
+                html.push_str(&format!("<li>{}</li>\n", inline(&item)));
+            }
+            html.push_str(&format!("</{}>\n", tag));
+        },
+        None => (),
+    }
+}
+
+pub fn to_html(org: &str) -> String {
+    let mut html = String::new();
+    let mut block: Option<Block> = None;
+    // Name of the #+begin_ block we are in, and its lines
+    let mut special: Option<(String, Vec<String>)> = None;
+    let mut in_drawer = false;
+
+    for line in org.lines() {
+        if let Some((name, lines)) = special.as_mut() {
+            match BLOCK_REGEX.captures(line) {
+                Some(captures) if captures[1].eq_ignore_ascii_case("end") && captures[2].eq_ignore_ascii_case(name) => {
+                    let (name, lines) = special.take().unwrap();
+                    if name == "quote" {
+                        html.push_str(&format!("<blockquote>{}</blockquote>\n", to_html(&lines.join("\n")).trim_end()));
+                    } else {
+                        html.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&lines.join("\n"))));
+                    }
+                },
+                _ => lines.push(line.to_string()),
+            }
+            continue;
+        }
+        if in_drawer {
+            in_drawer = !line.trim().eq_ignore_ascii_case(":END:");
+            continue;
+        }
+
+        if let Some(captures) = BLOCK_REGEX.captures(line).filter(|captures| captures[1].eq_ignore_ascii_case("begin")) {
+            close(block.take(), &mut html);
+            special = Some((captures[2].to_lowercase(), Vec::new()));
+        } else if DRAWER_REGEX.is_match(line) {
+            // Drawers, like properties of headings, are bookkeeping
+            in_drawer = true;
+        } else if line.trim().is_empty() {
+            close(block.take(), &mut html);
+        } else if line.trim_start().starts_with("#+") || line.trim_start() == "#" || line.trim_start().starts_with("# ") {
+            // Keywords and comments
+            continue;
+        } else if let Some(captures) = HEADING_REGEX.captures(line) {
+            close(block.take(), &mut html);
+            let level = (captures[1].len() + 2).min(6);
+            html.push_str(&format!("<h{}>{}</h{}>\n", level, inline(&captures[2]), level));
+        } else if let Some(captures) = LIST_ITEM_REGEX.captures(line) {
+            let ordered = captures.get(1).is_none();
+            match block.as_mut() {
+                Some(Block::List { ordered: list_ordered, items }) if *list_ordered == ordered => items.push(captures[2].to_string()),
+                _ => {
+                    close(block.take(), &mut html);
+                    block = Some(Block::List { ordered, items: vec![captures[2].to_string()] });
+                },
+            }
+        } else {
+            match block.as_mut() {
+                // Indented lines continue the last list item
+                Some(Block::List { items, .. }) if line.starts_with(char::is_whitespace) => {
+                    let item = items.last_mut().unwrap();
+                    item.push(' ');
+                    item.push_str(line.trim());
+                },
+                Some(Block::Paragraph(lines)) => lines.push(line.trim().to_string()),
+                _ => {
+                    close(block.take(), &mut html);
+                    block = Some(Block::Paragraph(vec![line.trim().to_string()]));
+                },
+            }
+        }
+    }
+
+    if let Some((_, lines)) = special {
+        // An unterminated block is kept as text
+        block = Some(Block::Paragraph(lines));
+    }
+    close(block, &mut html);
+    html
+}
+
+// Characters allowed around emphasis markers
+fn is_pre(c: Option<char>) -> bool {
+    c.is_none_or(|c| c.is_whitespace() || "-({'\"".contains(c))
+}
+
+fn is_post(c: Option<char>) -> bool {
+    c.is_none_or(|c| c.is_whitespace() || "-.,;:!?')}[\"".contains(c))
+}
+
+// Pieces of a line of text as far as inline markup goes
+enum Span {
+    Text(String),
+    Link { target: String, description: Option<String> },
+    // Text between a pair of markers like * or =
+    Emphasis { marker: char, inner: String },
+}
+
+fn spans(text: &str) -> Vec<Span> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        // [[target][description]] or [[target]]
+        if chars[i] == '[' && chars.get(i + 1) == Some(&'[') {
+            let rest: String = chars[i + 2..].iter().collect();
+            if let Some(end) = rest.find("]]") {
+                let (target, description) = match rest[..end].split_once("][") {
+                    Some((target, description)) => (target.to_string(), Some(description.to_string())),
+                    None => (rest[..end].to_string(), None),
+                };
+                spans.push(Span::Text(std::mem::take(&mut plain)));
+                spans.push(Span::Link { target, description });
+                i += 2 + rest[..end].chars().count() + 2;
+                continue;
+            }
+        }
+
+        let marker = chars[i];
+        if "*/_=~+".contains(marker) && is_pre(i.checked_sub(1).map(|j| chars[j])) && chars.get(i + 1).is_some_and(|c| !c.is_whitespace()) {
+            let close = (i + 2..chars.len()).find(|&j| chars[j] == marker && !chars[j - 1].is_whitespace() && is_post(chars.get(j + 1).copied()));
+            if let Some(close) = close {
+                spans.push(Span::Text(std::mem::take(&mut plain)));
+                spans.push(Span::Emphasis { marker, inner: chars[i + 1..close].iter().collect() });
+                i = close + 1;
+                continue;
+            }
+        }
+
+        plain.push(chars[i]);
+        i += 1;
+    }
+
+    spans.push(Span::Text(plain));
+    spans
+}
+
+fn inline(text: &str) -> String {
+    spans(text).into_iter().map(|span| match span {
+        Span::Text(text) => escape(&text),
+        Span::Link { target, description } => {
+            let description = description.map(|description| inline(&description)).unwrap_or_else(|| escape(&target));
+            format!("<a href=\"{}\">{}</a>", escape(&target), description)
+        },
+        Span::Emphasis { marker, inner } => {
+            let (tag, content) = match marker {
+                '*' => ("b", inline(&inner)),
+                '/' => ("i", inline(&inner)),
+                '_' => ("u", inline(&inner)),
+                '+' => ("del", inline(&inner)),
+                _ => ("code", escape(&inner)),
+            };
+            format!("<{}>{}</{}>", tag, content, tag)
+        },
+    }).collect()
+}
+
+// Text with the markup taken out: links become their descriptions and emphasis
+// markers are dropped
+fn inline_text(text: &str) -> String {
+    spans(text).into_iter().map(|span| match span {
+        Span::Text(text) => text,
+        Span::Link { target, description } => description.map(|description| inline_text(&description)).unwrap_or(target),
+        Span::Emphasis { marker: '=' | '~', inner } => inner,
+        Span::Emphasis { inner, .. } => inline_text(&inner),
+    }).collect()
+}
+
+// Org markup as readable plain text, for places that can't show HTML. Lines
+// are kept as they are, minus keywords, comments, drawers, and the lines
+// around blocks.
+pub fn to_text(org: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    // Name of the #+begin_ block we are in
+    let mut special: Option<String> = None;
+    let mut in_drawer = false;
+
+    for line in org.lines() {
+        if let Some(name) = &special {
+            match BLOCK_REGEX.captures(line) {
+                Some(captures) if captures[1].eq_ignore_ascii_case("end") && captures[2].eq_ignore_ascii_case(name) => special = None,
+                // Only quotes have markup in them, the rest is verbatim
+                _ if name == "quote" => lines.push(inline_text(line.trim())),
+                _ => lines.push(line.to_string()),
+            }
+            continue;
+        }
+        if in_drawer {
+            in_drawer = !line.trim().eq_ignore_ascii_case(":END:");
+            continue;
+        }
+
+        if let Some(captures) = BLOCK_REGEX.captures(line).filter(|captures| captures[1].eq_ignore_ascii_case("begin")) {
+            special = Some(captures[2].to_lowercase());
+        } else if DRAWER_REGEX.is_match(line) {
+            in_drawer = true;
+        } else if line.trim_start().starts_with("#+") || line.trim_start() == "#" || line.trim_start().starts_with("# ") {
+            continue;
+        } else if let Some(captures) = HEADING_REGEX.captures(line) {
+            lines.push(inline_text(&captures[2]));
+        } else if line.trim().is_empty() {
+            // Runs of blank lines, like the ones left by dropped lines, are
+            // cut down to one
+            if lines.last().is_some_and(|last| !last.is_empty()) {
+                lines.push(String::new());
+            }
+        } else {
+            lines.push(inline_text(line.trim_end()));
+        }
+    }
+
+    lines.join("\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn org_markup_is_converted() {
+        let org = "Some *notes* about /this/ with a [[https://example.com/?a=1&b=2][link]].\nSame paragraph, =a < b=.\n\n- first\n- second\n  continued\n\n#+begin_src python\nprint(\"hi\")\n#+end_src\n\n* Heading";
+
+        assert_eq!(to_html(org), "<p>Some <b>notes</b> about <i>this</i> with a <a href=\"https://example.com/?a=1&amp;b=2\">link</a>. Same paragraph, <code>a &lt; b</code>.</p>\n\
+<ul>\n<li>first</li>\n<li>second continued</li>\n</ul>\n\
+<pre><code>print(&quot;hi&quot;)</code></pre>\n\
+<h3>Heading</h3>\n");
+    }
+
+    #[test]
+    fn markers_need_word_boundaries() {
+        assert_eq!(inline("see https://example.com/a/b and 2*3*4"), "see https://example.com/a/b and 2*3*4");
+        assert_eq!(inline("*a* *b*"), "<b>a</b> <b>b</b>");
+        assert_eq!(inline("[[https://example.com]]"), "<a href=\"https://example.com\">https://example.com</a>");
+    }
+
+    #[test]
+    fn org_markup_is_taken_out_of_text() {
+        let org = "Some *notes* with a [[https://example.com][/nice/ link]] and [[https://example.com/b]].\n#+TITLE: Not here\n\n#+begin_quote\nA =quote=\n#+end_quote\n\n\n** Heading\n:PROPERTIES:\n:ID: a\n:END:\n- item with ~code~\n#+begin_src python\nprint(*args)\n#+end_src";
+
+        assert_eq!(to_text(org), "Some notes with a nice link and https://example.com/b.\n\nA quote\n\nHeading\n- item with code\nprint(*args)");
+    }
+}
@@ -0,0 +1,172 @@
+// Splitting large feeds into several linked documents (RFC 5005), so that
+// readers don't have to download every entry on each poll.
+
+use serde::Deserialize;
+
+use crate::{FeedLink, NewsFeed};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PagingConfig {
+    // Entries per document
+    pub page_size: usize,
+    #[serde(default)]
+    pub style: PagingStyle,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PagingStyle {
+    // The main document has the newest entries and links to archives of
+    // older ones via prev-archive. Archives are filled from the oldest entry
+    // so that they don't change as new entries come in.
+    #[default]
+    Archive,
+    // Pages of page_size entries in feed order, linked via next and previous
+    Pages,
+}
+
+fn link(rel: &str, href: &str) -> FeedLink {
+    FeedLink { rel: rel.to_string(), href: href.to_string() }
+}
+
+// Documents to write for a feed whose main document goes in file_name, as
+// (file name, feed) pairs with the main document first. Entries are taken to
+// be newest first. Links between documents are relative to their directory.
+pub fn paginate(mut feed: NewsFeed, file_name: &str, config: &PagingConfig) -> Vec<(String, NewsFeed)> {
+    let page_size = config.page_size.max(1);
+    if feed.items.len() <= page_size {
+        return vec![(file_name.to_string(), feed)];
+    }
+
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension)) => (stem, format!(".{}", extension)),
+        None => (file_name, String::new()),
+    };
+    let items = std::mem::take(&mut feed.items);
+    let page_feed = |items: Vec<_>, links: Vec<FeedLink>, archive: bool| NewsFeed {
+        id: feed.id.clone(),
+        updated: feed.updated,
+        link: feed.link.clone(),
+        title: feed.title.clone(),
+        subtitle: feed.subtitle.clone(),
+        items,
+        authors: feed.authors.clone(),
+        categories: feed.categories.clone(),
+        generator: feed.generator.clone(),
+        icon: feed.icon.clone(),
+        logo: feed.logo.clone(),
+        links: feed.links.iter().cloned().chain(links).collect(),
+        archive,
+        language: feed.language.clone(),
+    };
+
+    match config.style {
+        PagingStyle::Archive => {
+            let archive_name = |index: usize| format!("{}-archive-{}{}", stem, index, extension);
+            // The main document keeps between 1 and page_size entries
+            let archives = (items.len() - 1) / page_size;
+            let current = items.len() - archives * page_size;
+
+            let mut documents = vec![(
+                file_name.to_string(),
+                page_feed(items[..current].to_vec(), vec![link("prev-archive", &archive_name(archives))], false),
+            )];
+            // Archive 1 has the oldest entries
+            for index in (1..=archives).rev() {
+                let end = items.len() - (index - 1) * page_size;
+                let mut links = vec![link("current", file_name)];
+                if index > 1 {
+                    links.push(link("prev-archive", &archive_name(index - 1)));
+                }
+                if index < archives {
+                    links.push(link("next-archive", &archive_name(index + 1)));
+                }
+                documents.push((archive_name(index), page_feed(items[end - page_size..end].to_vec(), links, true)));
+            }
+            documents
+        },
+        PagingStyle::Pages => {
+            let pages: Vec<_> = items.chunks(page_size).collect();
+            let page_name = |index: usize| if index == 1 { file_name.to_string() } else { format!("{}-page-{}{}", stem, index, extension) };
+
+            pages.iter().enumerate().map(|(i, page)| {
+                let index = i + 1;
+                let mut links = vec![link("first", &page_name(1)), link("last", &page_name(pages.len()))];
+                if index > 1 {
+                    links.push(link("previous", &page_name(index - 1)));
+                }
+                if index < pages.len() {
+                    links.push(link("next", &page_name(index + 1)));
+                }
+                (page_name(index), page_feed(page.to_vec(), links, false))
+            }).collect()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::NewsItem;
+
+    fn feed(count: usize) -> NewsFeed {
+        NewsFeed {
+            id: "links".to_string(),
+            title: "Links".to_string(),
+            // Newest first
+            items: (0..count).rev().map(|n| NewsItem {
+                id: n.to_string(),
+                link: format!("https://example.com/{}", n),
+                title: n.to_string(),
+                published: Utc::now(),
+                updated: Utc::now(),
+                ..Default::default()
+            }).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn ids(feed: &NewsFeed) -> Vec<&str> {
+        feed.items.iter().map(|it| it.id.as_str()).collect()
+    }
+
+    fn href<'a>(feed: &'a NewsFeed, rel: &str) -> Option<&'a str> {
+        feed.links.iter().find(|link| link.rel == rel).map(|link| link.href.as_str())
+    }
+
+    #[test]
+    fn archives_are_filled_from_the_oldest() {
+        let config = PagingConfig { page_size: 2, style: PagingStyle::Archive };
+        let documents = paginate(feed(5), "links.xml", &config);
+
+        let names: Vec<&str> = documents.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["links.xml", "links-archive-2.xml", "links-archive-1.xml"]);
+
+        let (_, current) = &documents[0];
+        assert_eq!(ids(current), vec!["4"]);
+        assert_eq!(href(current, "prev-archive"), Some("links-archive-2.xml"));
+        assert!(!current.archive);
+
+        let (_, oldest) = &documents[2];
+        assert_eq!(ids(oldest), vec!["1", "0"]);
+        assert_eq!(href(oldest, "current"), Some("links.xml"));
+        assert_eq!(href(oldest, "next-archive"), Some("links-archive-2.xml"));
+        assert_eq!(href(oldest, "prev-archive"), None);
+        assert!(oldest.archive);
+
+        assert_eq!(paginate(feed(2), "links.xml", &config).len(), 1);
+    }
+
+    #[test]
+    fn pages_follow_feed_order() {
+        let config = PagingConfig { page_size: 2, style: PagingStyle::Pages };
+        let documents = paginate(feed(5), "links.xml", &config);
+
+        assert_eq!(documents.len(), 3);
+        assert_eq!(ids(&documents[0].1), vec!["4", "3"]);
+        assert_eq!(href(&documents[0].1, "next"), Some("links-page-2.xml"));
+        assert_eq!(href(&documents[2].1, "previous"), Some("links-page-2.xml"));
+        assert_eq!(href(&documents[2].1, "last"), Some("links-page-3.xml"));
+    }
+}
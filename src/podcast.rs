@@ -0,0 +1,236 @@
+// Podcasts of feeds for listening to them: each item is read out by a text to
+// speech backend into an MP3, and the MP3s are listed as enclosures of an RSS
+// feed that podcast apps subscribe to.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::{fs, path};
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{sinks, Enclosure, NewsFeed, NewsItem};
+
+const ITUNES_NS: &str = "http://www.itunes.com/dtds/podcast-1.0.dtd";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PodcastConfig {
+    // Directory for the episodes and the podcast feed, resolved against
+    // output_dir
+    pub path: path::PathBuf,
+    // Public URL the directory is served at
+    pub url: String,
+    pub tts: TtsConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "backend", rename_all = "kebab-case")]
+pub enum TtsConfig {
+    // A command reading text on stdin and writing MP3 audio to stdout, like
+    // piper piped into lame
+    Command { command: Vec<String> },
+    // OpenAI's speech API, or another one compatible with it
+    Openai {
+        api_key: String,
+        #[serde(default = "default_openai_url")]
+        url: String,
+        #[serde(default = "default_openai_model")]
+        model: String,
+        #[serde(default = "default_openai_voice")]
+        voice: String,
+    },
+}
+
+fn default_openai_url() -> String {
+    "https://api.openai.com/v1/audio/speech".to_string()
+}
+
+fn default_openai_model() -> String {
+    "tts-1".to_string()
+}
+
+fn default_openai_voice() -> String {
+    "alloy".to_string()
+}
+
+impl TtsConfig {
+    // MP3 audio of the text read out
+    pub fn synthesize(&self, text: &str) -> Result<Vec<u8>> {
+        match self {
+            TtsConfig::Command { command } => {
+                let program = command.first().ok_or_else(|| anyhow!("The TTS command is empty"))?;
+                let mut child = Command::new(program)
+                    .args(&command[1..])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .with_context(|| format!("Unable to run {}", program))?;
+                // Writing from another thread so that a command writing out
+                // audio as it reads doesn't block on a full pipe
+                let mut stdin = child.stdin.take().unwrap();
+                let text = text.to_string();
+                let writer = std::thread::spawn(move || stdin.write_all(text.as_bytes()));
+
+                let output = child.wait_with_output()?;
+                writer.join().map_err(|_| anyhow!("Unable to pass text to {}", program))??;
+                if !output.status.success() {
+                    return Err(anyhow!("{} failed with {}", program, output.status));
+                }
+                Ok(output.stdout)
+            },
+            TtsConfig::Openai { api_key, url, model, voice } => {
+                let response = Client::new()
+                    .post(url)
+                    .bearer_auth(api_key)
+                    .json(&json!({ "model": model, "voice": voice, "input": text, "response_format": "mp3" }))
+                    .send()?;
+                if !response.status().is_success() {
+                    return Err(anyhow!("{} failed with {}", url, response.status()));
+                }
+                Ok(response.bytes()?.to_vec())
+            },
+        }
+    }
+}
+
+// What gets read out for an item: its title and then its full text or summary
+fn script(item: &NewsItem) -> String {
+    let text = item.content.as_ref().filter(|_| !item.content_html).or(item.summary.as_ref());
+    match text {
+        Some(text) => format!("{}.\n\n{}", item.title, text),
+        None => format!("{}.", item.title),
+    }
+}
+
+// Write an episode for every item that doesn't have one yet and the podcast
+// feed listing them. Episodes are named after the UUIDs items are written out
+// with, which stay the same across runs and, unlike raw ids, are safe in file
+// names and URLs, so each is only synthesized once.
+pub fn write(config: &PodcastConfig, dir: &path::Path, feed: &NewsFeed) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+    let base_url = config.url.trim_end_matches('/');
+
+    let mut episodes = feed.clone();
+    for item in episodes.items.iter_mut() {
+        let file_name = format!("{}.mp3", item.feed_id());
+        let episode_path = dir.join(&file_name);
+        if !episode_path.exists() {
+            info!("Reading out {}", item.title);
+            let audio = config.tts.synthesize(&script(item))
+                .with_context(|| format!("Unable to read out {}", item.title))?;
+            sinks::write_atomic(&episode_path, &audio)?;
+        }
+
+        item.enclosures = vec![Enclosure {
+            url: format!("{}/{}", base_url, file_name),
+            mime_type: "audio/mpeg".to_string(),
+            length: Some(fs::metadata(&episode_path)?.len()),
+        }];
+    }
+
+    sinks::write_atomic(&dir.join("feed.xml"), to_rss_string(&episodes, base_url)?.as_bytes())
+}
+
+// An RSS 2.0 feed with the iTunes elements podcast apps look for
+pub fn to_rss_string(feed: &NewsFeed, link: &str) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    writer.create_element("rss")
+        .with_attribute(("version", "2.0"))
+        .with_attribute(("xmlns:itunes", ITUNES_NS))
+        .write_inner_content(|writer| {
+            writer.create_element("channel").write_inner_content(|writer| {
+                writer.create_element("title").write_text_content(BytesText::new(&feed.title))?;
+                writer.create_element("link").write_text_content(BytesText::new(link))?;
+                writer.create_element("description").write_text_content(BytesText::new(&feed.subtitle))?;
+                writer.create_element("generator").write_text_content(BytesText::new(&feed.generator))?;
+                writer.create_element("lastBuildDate").write_text_content(BytesText::new(&feed.updated.to_rfc2822()))?;
+                if let Some(language) = &feed.language {
+                    writer.create_element("language").write_text_content(BytesText::new(language))?;
+                }
+                if let Some(author) = feed.authors.first() {
+                    writer.create_element("itunes:author").write_text_content(BytesText::new(&author.name))?;
+                }
+                if let Some(image) = feed.logo.as_ref().or(feed.icon.as_ref()) {
+                    writer.create_element("itunes:image").with_attribute(("href", image.as_str())).write_empty()?;
+                }
+                writer.create_element("itunes:explicit").write_text_content(BytesText::new("false"))?;
+
+                for item in &feed.items {
+                    writer.create_element("item").write_inner_content(|writer| {
+                        writer.create_element("title").write_text_content(BytesText::new(&item.title))?;
+                        writer.create_element("link").write_text_content(BytesText::new(&item.link))?;
+                        writer.create_element("guid")
+                            .with_attribute(("isPermaLink", "false"))
+                            .write_text_content(BytesText::new(&item.id))?;
+                        writer.create_element("pubDate").write_text_content(BytesText::new(&item.published.to_rfc2822()))?;
+                        if let Some(summary) = &item.summary {
+                            writer.create_element("description").write_text_content(BytesText::new(summary))?;
+                        }
+                        for enclosure in &item.enclosures {
+                            writer.create_element("enclosure")
+                                .with_attribute(("url", enclosure.url.as_str()))
+                                .with_attribute(("length", enclosure.length.unwrap_or_default().to_string().as_str()))
+                                .with_attribute(("type", enclosure.mime_type.as_str()))
+                                .write_empty()?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn episodes_are_synthesized_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        // Audio that is just the text
+        let config = PodcastConfig {
+            path: dir.to_path_buf(),
+            url: "https://example.com/podcast/".to_string(),
+            tts: TtsConfig::Command { command: vec!["cat".to_string()] },
+        };
+        let feed = NewsFeed {
+            id: "unread".to_string(),
+            title: "Unread & read out".to_string(),
+            items: vec![NewsItem {
+                id: "abc#https://example.com/a".to_string(),
+                link: "https://example.com/a".to_string(),
+                title: "A".to_string(),
+                summary: Some("Worth a listen".to_string()),
+                published: Utc::now(),
+                updated: Utc::now(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let file_name = format!("{}.mp3", feed.items[0].feed_id());
+        write(&config, dir, &feed).unwrap();
+        assert_eq!(fs::read_to_string(dir.join(&file_name)).unwrap(), "A.\n\nWorth a listen");
+
+        let rss = fs::read_to_string(dir.join("feed.xml")).unwrap();
+        assert!(rss.contains("<title>Unread &amp; read out</title>"));
+        assert!(rss.contains(&format!(r#"<enclosure url="https://example.com/podcast/{}" length="18" type="audio/mpeg"/>"#, file_name)));
+
+        // Existing episodes are kept as they are
+        fs::write(dir.join(&file_name), "kept").unwrap();
+        write(&config, dir, &feed).unwrap();
+        assert_eq!(fs::read_to_string(dir.join(&file_name)).unwrap(), "kept");
+    }
+}
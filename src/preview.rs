@@ -0,0 +1,83 @@
+use std::io::IsTerminal;
+use anyhow::Result;
+use atom_syndication::{Feed, Text};
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const CYAN: &str = "\x1b[36m";
+const BLUE_UNDERLINE: &str = "\x1b[4;34m";
+const RESET: &str = "\x1b[0m";
+
+// Wraps text in ANSI escape codes only when colors are enabled
+struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.enabled {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+// Colors are used when writing to a terminal, unless NO_COLOR is set
+fn use_colors() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+fn text_value(text: &Text) -> &str {
+    text.value.trim()
+}
+
+// Render an Atom feed as human readable text for the terminal
+pub fn render(feed: &Feed) -> String {
+    let painter = Painter { enabled: use_colors() };
+    let width = textwrap::termwidth().min(100);
+    let indent = "    ";
+    let mut output = String::new();
+
+    output.push_str(&painter.paint(BOLD, text_value(&feed.title)));
+    output.push('\n');
+    if let Some(subtitle) = &feed.subtitle {
+        output.push_str(&painter.paint(DIM, text_value(subtitle)));
+        output.push('\n');
+    }
+    output.push_str(&painter.paint(DIM, &format!("{} entries, updated {}", feed.entries.len(), feed.updated.format("%Y-%m-%d %H:%M"))));
+    output.push('\n');
+
+    for (i, entry) in feed.entries.iter().enumerate() {
+        output.push('\n');
+        output.push_str(&format!("{:>2}. {}\n", i + 1, painter.paint(&format!("{}{}", BOLD, CYAN), text_value(&entry.title))));
+
+        for link in &entry.links {
+            output.push_str(&format!("{}{}\n", indent, painter.paint(BLUE_UNDERLINE, &link.href)));
+        }
+
+        let mut meta = vec![entry.published.unwrap_or(entry.updated).format("%Y-%m-%d").to_string()];
+        if !entry.categories.is_empty() {
+            meta.push(entry.categories.iter().map(|c| c.term.as_str()).collect::<Vec<_>>().join(", "));
+        }
+        output.push_str(&format!("{}{}\n", indent, painter.paint(DIM, &meta.join(" · "))));
+
+        if let Some(summary) = &entry.summary {
+            let options = textwrap::Options::new(width)
+                .initial_indent(indent)
+                .subsequent_indent(indent);
+            for paragraph in text_value(summary).split("\n\n") {
+                output.push_str(&textwrap::fill(paragraph, &options));
+                output.push('\n');
+            }
+        }
+    }
+
+    output
+}
+
+// Parse an Atom document and render it
+pub fn render_xml(xml: &str) -> Result<String> {
+    let feed: Feed = xml.parse()?;
+    Ok(render(&feed))
+}
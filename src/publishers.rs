@@ -0,0 +1,60 @@
+use std::path;
+use anyhow::Result;
+use serde::Deserialize;
+
+pub mod ftp;
+pub mod git;
+pub mod ipfs;
+pub mod neocities;
+pub mod s3;
+pub mod ssh;
+pub mod webdav;
+
+// Destinations where written feed files get published. Each publisher is
+// enabled by having its table in the config, like [publish.ipfs].
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct PublishConfig {
+    pub ftp: Option<ftp::FtpConfig>,
+    pub git: Option<git::GitConfig>,
+    pub ipfs: Option<ipfs::IpfsConfig>,
+    pub neocities: Option<neocities::NeocitiesConfig>,
+    pub s3: Option<s3::S3Config>,
+    pub ssh: Option<ssh::SshConfig>,
+    pub webdav: Option<webdav::WebDavConfig>,
+}
+
+// Publish a written feed file to every configured destination
+pub fn publish_all(config: &PublishConfig, file_path: &path::Path) -> Result<()> {
+    if let Some(ftp_config) = &config.ftp {
+        ftp::publish(ftp_config, file_path)?;
+    }
+    if let Some(git_config) = &config.git {
+        git::publish(git_config, file_path)?;
+    }
+    if let Some(ipfs_config) = &config.ipfs {
+        ipfs::publish(ipfs_config, file_path)?;
+    }
+    if let Some(neocities_config) = &config.neocities {
+        neocities::publish(neocities_config, file_path)?;
+    }
+    if let Some(s3_config) = &config.s3 {
+        s3::publish(s3_config, file_path)?;
+    }
+    if let Some(ssh_config) = &config.ssh {
+        ssh::publish(ssh_config, file_path)?;
+    }
+    if let Some(webdav_config) = &config.webdav {
+        webdav::publish(webdav_config, file_path)?;
+    }
+
+    Ok(())
+}
+
+// Wrap up publishing after all the feeds of a run are written
+pub fn finish_all(config: &PublishConfig) -> Result<()> {
+    if let Some(git_config) = &config.git {
+        git::commit(git_config)?;
+    }
+
+    Ok(())
+}
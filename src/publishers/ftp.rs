@@ -0,0 +1,108 @@
+use std::io::Write;
+use std::path;
+use std::process::{Command, Stdio};
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use serde::Deserialize;
+
+// Uploading to a directory on an FTP server with curl, which gets the
+// protocol's corners, like TLS session reuse on data connections, right
+#[derive(Debug, Clone, Deserialize)]
+pub struct FtpConfig {
+    pub host: String,
+    // Defaults to 21, or 990 for implicit TLS
+    pub port: Option<u16>,
+    pub username: String,
+    pub password: String,
+    // Remote directory to upload into, created if missing
+    #[serde(default)]
+    pub directory: String,
+    #[serde(default)]
+    pub security: FtpSecurity,
+    // Active mode is only needed for servers that can't do passive
+    #[serde(default = "default_passive")]
+    pub passive: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FtpSecurity {
+    // Plain FTP upgraded with AUTH TLS
+    #[default]
+    Explicit,
+    // TLS from the start
+    Implicit,
+    // Plain FTP, credentials included
+    None,
+}
+
+fn default_passive() -> bool {
+    true
+}
+
+fn curl(config: &FtpConfig, file_path: &path::Path) -> Command {
+    let scheme = if config.security == FtpSecurity::Implicit { "ftps" } else { "ftp" };
+    let port = config.port.unwrap_or(if config.security == FtpSecurity::Implicit { 990 } else { 21 });
+    let directory = config.directory.trim_matches('/');
+    let url = if directory.is_empty() {
+        format!("{}://{}:{}/", scheme, config.host, port)
+    } else {
+        format!("{}://{}:{}/{}/", scheme, config.host, port, directory)
+    };
+
+    let mut command = Command::new("curl");
+    command.args(["--silent", "--show-error", "--ftp-create-dirs"]);
+    if config.security == FtpSecurity::Explicit {
+        command.arg("--ssl-reqd");
+    }
+    if !config.passive {
+        command.args(["--ftp-port", "-"]);
+    }
+    // Credentials go through stdin so they don't show up in ps
+    command.args(["--config", "-", "--upload-file"]).arg(file_path).arg(url);
+    command
+}
+
+pub fn publish(config: &FtpConfig, file_path: &path::Path) -> Result<()> {
+    let mut child = curl(config, file_path)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Unable to run curl")?;
+    let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "user = \"{}:{}\"", escape(&config.username), escape(&config.password))?;
+    drop(stdin);
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!("Uploading {} to {} failed: {}", file_path.display(), config.host, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    info!("Uploaded {} to {}", file_path.display(), config.host);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curl_uploads_into_the_directory() {
+        let config = FtpConfig {
+            host: "ftp.example.com".to_string(),
+            port: None,
+            username: "me".to_string(),
+            password: "secret".to_string(),
+            directory: "/public_html/feeds/".to_string(),
+            security: FtpSecurity::Implicit,
+            passive: false,
+        };
+        let command = curl(&config, path::Path::new("/tmp/out/feed.xml"));
+        let args: Vec<_> = command.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+        assert_eq!(args, vec![
+            "--silent", "--show-error", "--ftp-create-dirs", "--ftp-port", "-", "--config", "-",
+            "--upload-file", "/tmp/out/feed.xml", "ftps://ftp.example.com:990/public_html/feeds/",
+        ]);
+        assert!(!args.iter().any(|arg| arg.contains("secret")));
+    }
+}
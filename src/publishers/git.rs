@@ -0,0 +1,100 @@
+use std::path;
+use std::process::Command;
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use log::info;
+use serde::Deserialize;
+
+// Committing written feeds into a git repository and pushing them, for
+// hosting on GitHub/Codeberg Pages with the history of every run. Files are
+// staged as they get written and committed together once the run is over.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitConfig {
+    // Work tree the feed files are written into
+    pub repo: path::PathBuf,
+    // {date} is replaced by the time of the run and {files} by the changed
+    // files
+    #[serde(default = "default_message")]
+    pub message: String,
+    #[serde(default = "default_push")]
+    pub push: bool,
+    // Pushed to the upstream of the current branch if not set
+    pub remote: Option<String>,
+    pub branch: Option<String>,
+}
+
+fn default_message() -> String {
+    "Update feeds on {date}".to_string()
+}
+
+fn default_push() -> bool {
+    true
+}
+
+fn git(repo: &path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").arg("-C").arg(repo).args(args).output()
+        .context("Unable to run git")?;
+    if !output.status.success() {
+        return Err(anyhow!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub fn publish(config: &GitConfig, file_path: &path::Path) -> Result<()> {
+    let file_path = file_path.to_str().context("Not able to get file path")?;
+    git(&config.repo, &["add", "--", file_path])?;
+    Ok(())
+}
+
+// Commit whatever was staged during the run and push it
+pub fn commit(config: &GitConfig) -> Result<()> {
+    let staged = git(&config.repo, &["diff", "--cached", "--name-only"])?;
+    if staged.trim().is_empty() {
+        return Ok(());
+    }
+
+    let message = config.message
+        .replace("{date}", &Local::now().format("%Y-%m-%d %H:%M").to_string())
+        .replace("{files}", &staged.lines().collect::<Vec<_>>().join(", "));
+    git(&config.repo, &["commit", "--quiet", "-m", &message])?;
+    info!("Committed feeds to {}", config.repo.display());
+
+    if config.push {
+        let mut args = vec!["push", "--quiet"];
+        args.extend(config.remote.as_deref());
+        args.extend(config.branch.as_deref());
+        git(&config.repo, &args)?;
+        info!("Pushed feeds from {}", config.repo.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn written_files_are_committed() {
+        let repo = tempfile::tempdir().unwrap();
+        git(repo.path(), &["init", "--quiet"]).unwrap();
+        git(repo.path(), &["config", "user.name", "journalist"]).unwrap();
+        git(repo.path(), &["config", "user.email", "journalist@example.com"]).unwrap();
+
+        let config = GitConfig {
+            repo: repo.path().to_path_buf(),
+            message: "Update {files}".to_string(),
+            push: false,
+            remote: None,
+            branch: None,
+        };
+        fs::write(repo.path().join("feed.xml"), "<feed/>").unwrap();
+        publish(&config, &repo.path().join("feed.xml")).unwrap();
+        commit(&config).unwrap();
+        assert_eq!(git(repo.path(), &["log", "--format=%s"]).unwrap(), "Update feed.xml\n");
+
+        // Nothing staged, nothing committed
+        commit(&config).unwrap();
+        assert_eq!(git(repo.path(), &["rev-list", "--count", "HEAD"]).unwrap(), "1\n");
+    }
+}
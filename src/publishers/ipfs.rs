@@ -0,0 +1,103 @@
+use std::path;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use reqwest::blocking::{multipart, Client, RequestBuilder};
+use serde::Deserialize;
+
+// Publishing via the RPC API of a Kubo node (or a pinning service exposing the
+// same API). Files are added and pinned, copied into an MFS directory, and the
+// directory is published under an IPNS key so all feeds share one name.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IpfsConfig {
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    // Sent as a bearer token, needed by most hosted services
+    pub auth_token: Option<String>,
+    #[serde(default = "default_mfs_dir")]
+    pub mfs_dir: String,
+    // Name of the key to publish under. IPNS is skipped if not set.
+    pub ipns_key: Option<String>,
+}
+
+fn default_api_url() -> String {
+    "http://127.0.0.1:5001".to_string()
+}
+
+fn default_mfs_dir() -> String {
+    "/journalist".to_string()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AddResponse {
+    hash: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StatResponse {
+    hash: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NamePublishResponse {
+    name: String,
+    value: String,
+}
+
+struct Api<'a> {
+    client: Client,
+    config: &'a IpfsConfig,
+}
+
+impl Api<'_> {
+    fn post(&self, endpoint: &str, args: &[(&str, &str)]) -> RequestBuilder {
+        let url = format!("{}/api/v0/{}", self.config.api_url.trim_end_matches('/'), endpoint);
+        let request = self.client.post(url).query(args);
+        match &self.config.auth_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    fn call(&self, request: RequestBuilder) -> Result<reqwest::blocking::Response> {
+        let response = request.send()?;
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(anyhow!("IPFS API call failed with {}: {}", response.status(), response.text().unwrap_or_default()))
+        }
+    }
+}
+
+pub fn publish(config: &IpfsConfig, file_path: &path::Path) -> Result<()> {
+    let api = Api { client: Client::new(), config };
+    let file_name = file_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("Not able to get file name")?;
+
+    let form = multipart::Form::new().file("file", file_path)?;
+    let added: AddResponse = api
+        .call(api.post("add", &[("pin", "true"), ("cid-version", "1")]).multipart(form))?
+        .json()?;
+    info!("Added {} to IPFS as {}", file_path.display(), added.hash);
+
+    let mfs_path = format!("{}/{}", config.mfs_dir.trim_end_matches('/'), file_name);
+    api.call(api.post("files/mkdir", &[("arg", &config.mfs_dir), ("parents", "true")]))?;
+    api.call(api.post("files/rm", &[("arg", &mfs_path), ("force", "true")]))?;
+    api.call(api.post("files/cp", &[("arg", &format!("/ipfs/{}", added.hash)), ("arg", &mfs_path)]))?;
+
+    if let Some(key) = &config.ipns_key {
+        let dir: StatResponse = api
+            .call(api.post("files/stat", &[("arg", &config.mfs_dir)]))?
+            .json()?;
+        let published: NamePublishResponse = api
+            .call(api.post("name/publish", &[("arg", &format!("/ipfs/{}", dir.hash)), ("key", key)]))?
+            .json()?;
+        info!("Published {} under IPNS name {}", published.value, published.name);
+    }
+
+    Ok(())
+}
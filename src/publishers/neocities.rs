@@ -0,0 +1,54 @@
+use std::path;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use reqwest::blocking::{multipart, Client};
+use serde::Deserialize;
+
+const UPLOAD_URL: &str = "https://neocities.org/api/upload";
+
+// Uploading to a Neocities site through its API
+// (https://neocities.org/api). Free sites only take some file types, which
+// include HTML, XML, and JSON.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NeocitiesConfig {
+    // From the site's settings page
+    pub api_key: String,
+    // Directory on the site to put files in, the root if not set
+    #[serde(default)]
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+struct UploadResponse {
+    result: String,
+    message: Option<String>,
+}
+
+fn remote_path(config: &NeocitiesConfig, file_name: &str) -> String {
+    match config.path.trim_matches('/') {
+        "" => file_name.to_string(),
+        dir => format!("{}/{}", dir, file_name),
+    }
+}
+
+pub fn publish(config: &NeocitiesConfig, file_path: &path::Path) -> Result<()> {
+    let file_name = file_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("Not able to get file name")?;
+    let remote_path = remote_path(config, file_name);
+
+    // The field name is where the file goes on the site
+    let form = multipart::Form::new().file(remote_path.clone(), file_path)?;
+    let response: UploadResponse = Client::new().post(UPLOAD_URL)
+        .bearer_auth(&config.api_key)
+        .multipart(form)
+        .send()?
+        .json()
+        .context("Unexpected response from Neocities")?;
+    if response.result != "success" {
+        return Err(anyhow!("Uploading {} to Neocities failed: {}", file_path.display(), response.message.unwrap_or_default()));
+    }
+    info!("Uploaded {} to Neocities as {}", file_path.display(), remote_path);
+    Ok(())
+}
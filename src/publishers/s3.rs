@@ -0,0 +1,147 @@
+use std::{fs, path};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::info;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+// Uploading to an S3 compatible bucket (AWS, R2, MinIO, etc.) with requests
+// signed by AWS Signature Version 4. Objects are addressed path style, as
+// all the compatible services support that.
+#[derive(Debug, Clone, Deserialize)]
+pub struct S3Config {
+    // Like https://<account>.r2.cloudflarestorage.com. Defaults to AWS in
+    // the region.
+    pub endpoint: Option<String>,
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    // Taken from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY if not set
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    // Object key, with {file} replaced by the name of the written file
+    #[serde(default = "default_key")]
+    pub key: String,
+    // Guessed from the file extension if not set
+    pub content_type: Option<String>,
+    pub cache_control: Option<String>,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_key() -> String {
+    "{file}".to_string()
+}
+
+fn content_type(file_path: &path::Path) -> &'static str {
+    match file_path.extension().and_then(|ext| ext.to_str()) {
+        Some("xml") | Some("atom") => "application/atom+xml",
+        Some("json") => "application/feed+json",
+        Some("html") => "text/html; charset=utf-8",
+        Some("gmi") => "text/gemini",
+        Some("ics") => "text/calendar",
+        Some("opml") => "text/x-opml",
+        _ => "application/octet-stream",
+    }
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret).as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    hmac(&key, "aws4_request")
+}
+
+// Percent encode everything but unreserved characters and the separators
+fn encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn credential(value: &Option<String>, var: &str) -> Result<String> {
+    value.clone()
+        .or_else(|| std::env::var(var).ok())
+        .ok_or_else(|| anyhow!("No S3 credentials, set them in [publish.s3] or {}", var))
+}
+
+pub fn publish(config: &S3Config, file_path: &path::Path) -> Result<()> {
+    let file_name = file_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("Not able to get file name")?;
+    let body = fs::read(file_path)?;
+    let access_key_id = credential(&config.access_key_id, "AWS_ACCESS_KEY_ID")?;
+    let secret_access_key = credential(&config.secret_access_key, "AWS_SECRET_ACCESS_KEY")?;
+
+    let endpoint = config.endpoint.clone()
+        .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", config.region));
+    let endpoint = endpoint.trim_end_matches('/');
+    let host = endpoint.split("://").nth(1).unwrap_or(endpoint);
+    let key = config.key.replace("{file}", file_name);
+    let uri = encode_path(&format!("/{}/{}", config.bucket, key.trim_start_matches('/')));
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hex::encode(Sha256::digest(&body));
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        uri, host, payload_hash, amz_date, signed_headers, payload_hash
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date, scope, hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let signature = hex::encode(hmac(&signing_key(&secret_access_key, &date, &config.region, "s3"), &string_to_sign));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key_id, scope, signed_headers, signature
+    );
+
+    let mut request = Client::new().put(format!("{}{}", endpoint, uri))
+        .header("Authorization", authorization)
+        .header("x-amz-content-sha256", payload_hash)
+        .header("x-amz-date", amz_date)
+        .header("Content-Type", config.content_type.as_deref().unwrap_or_else(|| content_type(file_path)));
+    if let Some(cache_control) = &config.cache_control {
+        request = request.header("Cache-Control", cache_control);
+    }
+
+    let response = request.body(body).send()?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Uploading {} to S3 failed with {}: {}", file_path.display(), response.status(), response.text().unwrap_or_default()));
+    }
+    info!("Uploaded {} to s3://{}/{}", file_path.display(), config.bucket, key);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signing_key_matches_aws_example() {
+        // From the Signature Version 4 documentation
+        let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+        assert_eq!(hex::encode(key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+
+        assert_eq!(encode_path("/feeds/my feed+1.xml"), "/feeds/my%20feed%2B1.xml");
+    }
+}
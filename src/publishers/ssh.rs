@@ -0,0 +1,69 @@
+use std::path;
+use std::process::Command;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use serde::Deserialize;
+
+// Copying files to a remote directory with rsync over SSH, so feeds built on
+// one machine end up on the server hosting them
+#[derive(Debug, Clone, Deserialize)]
+pub struct SshConfig {
+    pub host: String,
+    // Defaults to the user in ~/.ssh/config, or the local one
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    // Private key to log in with
+    pub key: Option<path::PathBuf>,
+    // Remote directory to copy the files into
+    pub path: String,
+}
+
+fn rsync(config: &SshConfig, file_path: &path::Path) -> Command {
+    let mut ssh = vec!["ssh".to_string(), "-o".to_string(), "BatchMode=yes".to_string()];
+    if let Some(port) = config.port {
+        ssh.extend(["-p".to_string(), port.to_string()]);
+    }
+    if let Some(key) = &config.key {
+        ssh.extend(["-i".to_string(), key.display().to_string()]);
+    }
+
+    let destination = match &config.user {
+        Some(user) => format!("{}@{}:{}/", user, config.host, config.path.trim_end_matches('/')),
+        None => format!("{}:{}/", config.host, config.path.trim_end_matches('/')),
+    };
+
+    let mut command = Command::new("rsync");
+    command.arg("--times").arg("--chmod=F644").arg("-e").arg(ssh.join(" ")).arg(file_path).arg(destination);
+    command
+}
+
+pub fn publish(config: &SshConfig, file_path: &path::Path) -> Result<()> {
+    let output = rsync(config, file_path).output().context("Unable to run rsync")?;
+    if !output.status.success() {
+        return Err(anyhow!("Copying {} to {} failed: {}", file_path.display(), config.host, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    info!("Copied {} to {}:{}", file_path.display(), config.host, config.path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rsync_goes_through_ssh() {
+        let config = SshConfig {
+            host: "example.com".to_string(),
+            user: Some("me".to_string()),
+            port: Some(2222),
+            key: Some("/home/me/.ssh/feeds".into()),
+            path: "/srv/feeds/".to_string(),
+        };
+        let command = rsync(&config, path::Path::new("/tmp/out/feed.xml"));
+        let args: Vec<_> = command.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+        assert_eq!(args, vec![
+            "--times", "--chmod=F644", "-e", "ssh -o BatchMode=yes -p 2222 -i /home/me/.ssh/feeds",
+            "/tmp/out/feed.xml", "me@example.com:/srv/feeds/",
+        ]);
+    }
+}
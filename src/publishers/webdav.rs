@@ -0,0 +1,66 @@
+use std::process::Command;
+use std::{fs, path};
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use reqwest::blocking::Client;
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+
+// Uploading to a folder on a WebDAV server, like Nextcloud's at
+// https://cloud.example.com/remote.php/dav/files/<user>/<folder>
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebDavConfig {
+    pub url: String,
+    pub username: String,
+    pub password: Option<String>,
+    // Command printing the password, to keep it in a keyring or password
+    // manager, like ["secret-tool", "lookup", "service", "nextcloud"]
+    #[serde(default)]
+    pub password_command: Vec<String>,
+}
+
+impl WebDavConfig {
+    fn password(&self) -> Result<String> {
+        if let Some(password) = &self.password {
+            return Ok(password.clone());
+        }
+        let Some((program, args)) = self.password_command.split_first() else {
+            return Err(anyhow!("WebDAV needs password or password_command"));
+        };
+
+        let output = Command::new(program).args(args).output()
+            .with_context(|| format!("Unable to run {}", program))?;
+        if !output.status.success() {
+            return Err(anyhow!("{} failed with {}", program, output.status));
+        }
+        Ok(String::from_utf8(output.stdout)?.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+pub fn publish(config: &WebDavConfig, file_path: &path::Path) -> Result<()> {
+    let file_name = file_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("Not able to get file name")?;
+    let folder = config.url.trim_end_matches('/');
+    let url = format!("{}/{}", folder, file_name);
+    let password = config.password()?;
+    let body = fs::read(file_path)?;
+
+    let client = Client::new();
+    let put = || client.put(&url).basic_auth(&config.username, Some(&password)).body(body.clone()).send();
+    let mut response = put()?;
+    // Servers answer with a conflict when the folder doesn't exist yet
+    if response.status() == StatusCode::CONFLICT {
+        client.request(Method::from_bytes(b"MKCOL")?, folder)
+            .basic_auth(&config.username, Some(&password))
+            .send()?;
+        response = put()?;
+    }
+
+    if !response.status().is_success() {
+        return Err(anyhow!("Uploading {} to {} failed with {}", file_path.display(), url, response.status()));
+    }
+    info!("Uploaded {} to {}", file_path.display(), url);
+    Ok(())
+}
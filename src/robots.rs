@@ -0,0 +1,121 @@
+use regex::Regex;
+
+// Product token we identify ourselves with when matching robots.txt groups
+pub const AGENT: &str = "journalist";
+
+// An Allow (true) or Disallow (false) rule with its path pattern
+type Rule = (bool, String);
+
+// Rules from a robots.txt file that apply to us
+#[derive(Debug, Clone, Default)]
+pub struct Robots {
+    rules: Vec<Rule>,
+}
+
+impl Robots {
+    // Parse robots.txt keeping only the rules from groups naming our agent,
+    // or from `*` groups if there is no such group.
+    pub fn parse(body: &str, agent: &str) -> Self {
+        let agent = agent.to_lowercase();
+        let mut groups: Vec<(Vec<String>, Vec<Rule>)> = Vec::new();
+        let mut in_agents = false;
+
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim().to_string();
+
+            match key.as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        groups.push((Vec::new(), Vec::new()));
+                        in_agents = true;
+                    }
+                    groups.last_mut().unwrap().0.push(value.to_lowercase());
+                },
+                "allow" | "disallow" => {
+                    in_agents = false;
+                    // An empty Disallow means everything is allowed
+                    if let Some(group) = groups.last_mut().filter(|_| !value.is_empty()) {
+                        group.1.push((key == "allow", value));
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        let names_us = |a: &str| a != "*" && agent.contains(a);
+        let has_own_group = groups.iter().any(|(agents, _)| agents.iter().any(|a| names_us(a)));
+
+        let rules = groups.into_iter()
+            .filter(|(agents, _)| agents.iter().any(|a| if has_own_group { names_us(a) } else { a == "*" }))
+            .flat_map(|(_, rules)| rules)
+            .collect();
+
+        Robots { rules }
+    }
+
+    // The longest matching rule decides, with Allow winning ties
+    pub fn is_allowed(&self, path: &str) -> bool {
+        let mut best: Option<(usize, bool)> = None;
+
+        for (allow, pattern) in &self.rules {
+            if !pattern_matches(pattern, path) {
+                continue;
+            }
+            let length = pattern.len();
+            best = match best {
+                Some((best_length, best_allow)) if best_length > length || (best_length == length && best_allow) => best,
+                _ => Some((length, *allow)),
+            };
+        }
+
+        best.is_none_or(|(_, allow)| allow)
+    }
+}
+
+// Patterns are path prefixes where `*` matches any sequence and a trailing `$`
+// anchors the end.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+
+    let mut regex_str = String::from("^");
+    regex_str.push_str(&pattern.split('*').map(regex::escape).collect::<Vec<_>>().join(".*"));
+    if anchored {
+        regex_str.push('$');
+    }
+
+    Regex::new(&regex_str).is_ok_and(|re| re.is_match(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn robots_rules_work() {
+        let body = r#"
+User-agent: *
+Disallow: /private
+Allow: /private/public
+Disallow: /*.pdf$
+
+User-agent: badbot
+Disallow: /
+"#;
+        let robots = Robots::parse(body, AGENT);
+        assert!(robots.is_allowed("/papers"));
+        assert!(!robots.is_allowed("/private/notes"));
+        assert!(robots.is_allowed("/private/public/notes"));
+        assert!(!robots.is_allowed("/papers/2406.pdf"));
+        assert!(robots.is_allowed("/papers/2406.pdf?download"));
+
+        assert!(!Robots::parse(body, "badbot").is_allowed("/papers"));
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, TimeZone};
+use croner::Cron;
+
+// Parse a cron expression like `0 */6 * * *` or `@weekly`
+pub fn parse(expression: &str) -> Result<Cron> {
+    Cron::new(expression)
+        .parse()
+        .map_err(|err| anyhow!("Invalid schedule {}: {}", expression, err))
+}
+
+// First time strictly after `after` that the schedule fires
+pub fn next_run<Tz: TimeZone>(cron: &Cron, after: &DateTime<Tz>) -> Result<DateTime<Tz>> {
+    cron.find_next_occurrence(after, false)
+        .map_err(|err| anyhow!("Unable to find next run for {}: {}", cron.pattern, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn next_runs_follow_the_schedule() {
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 7, 30, 0).unwrap();
+
+        let every_six_hours = parse("0 */6 * * *").unwrap();
+        assert_eq!(next_run(&every_six_hours, &after).unwrap(), Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap());
+
+        // 2024-01-01 is a Monday
+        let weekly = parse("@weekly").unwrap();
+        assert_eq!(next_run(&weekly, &after).unwrap(), Utc.with_ymd_and_hms(2024, 1, 7, 0, 0, 0).unwrap());
+
+        assert!(parse("every day").is_err());
+    }
+}
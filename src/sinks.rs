@@ -0,0 +1,340 @@
+use std::io::Write;
+use std::str::FromStr;
+use std::{fs, path};
+use anyhow::{anyhow, Context, Result};
+use atom_syndication as atom;
+use chrono::{DateTime, TimeZone, Utc};
+use flate2::write::GzEncoder;
+use serde::Deserialize;
+
+use crate::template::Template;
+use crate::{gemtext, html, ics, jsonfeed, sitemap, NewsFeed, ToXmlString};
+
+pub mod activitypub;
+pub mod discord;
+pub mod email;
+pub mod linkding;
+pub mod micropub;
+pub mod nostr;
+pub mod push;
+pub mod slack;
+pub mod telegram;
+pub mod wallabag;
+
+// A destination generated feeds are emitted to
+pub trait Sink {
+    fn emit(&self, feed: &NewsFeed) -> Result<()>;
+}
+
+// Serialization formats for feeds
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    #[default]
+    Atom,
+    #[serde(rename = "jsonfeed")]
+    JsonFeed,
+    Html,
+    Gemtext,
+    Sitemap,
+    Ics,
+}
+
+impl Format {
+    // Atom and JSON Feed are indented unless compact is set. Pages are
+    // always written as they are.
+    pub fn render(&self, feed: &NewsFeed, compact: bool) -> String {
+        match self {
+            Format::Atom if compact => feed.to_compact_xml_string(),
+            Format::Atom => feed.to_xml_string(),
+            Format::JsonFeed if compact => jsonfeed::to_compact_json_string(feed),
+            Format::JsonFeed => jsonfeed::to_json_string(feed),
+            Format::Html => html::to_html_string(feed),
+            Format::Gemtext => gemtext::to_gemtext_string(feed),
+            Format::Sitemap => sitemap::to_sitemap_string(feed),
+            Format::Ics => ics::to_ics_string(feed),
+        }
+    }
+
+    // Whether feed readers can subscribe to outputs in this format
+    pub fn is_subscribable(&self) -> bool {
+        matches!(self, Format::Atom | Format::JsonFeed)
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "atom" => Ok(Format::Atom),
+            "jsonfeed" => Ok(Format::JsonFeed),
+            "html" => Ok(Format::Html),
+            "gemtext" => Ok(Format::Gemtext),
+            "sitemap" => Ok(Format::Sitemap),
+            "ics" => Ok(Format::Ics),
+            _ => Err(anyhow!("Unknown format {}", s)),
+        }
+    }
+}
+
+// Precompressed copies written next to file outputs, for web servers that
+// serve them directly (like nginx's gzip_static)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compression {
+    Gzip,
+    Br,
+}
+
+impl Compression {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Br => "br",
+        }
+    }
+
+    pub fn compress(&self, content: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(content)?;
+                Ok(encoder.finish()?)
+            },
+            Compression::Br => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+                encoder.write_all(content)?;
+                Ok(encoder.into_inner())
+            },
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "gzip" => Ok(Compression::Gzip),
+            "br" => Ok(Compression::Br),
+            _ => Err(anyhow!("Unknown compression {}", s)),
+        }
+    }
+}
+
+// One output of a feed. A path of `-` means stdout.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutputConfig {
+    pub path: path::PathBuf,
+    #[serde(default)]
+    pub format: Format,
+    // Tera templates to render with instead of the format, see `template`
+    pub template: Option<path::PathBuf>,
+    pub item_template: Option<path::PathBuf>,
+    // Leave out indentation to save space
+    #[serde(default)]
+    pub compact: bool,
+    // Also write compressed copies of file outputs
+    #[serde(default)]
+    pub compress: Vec<Compression>,
+}
+
+impl OutputConfig {
+    pub fn new(path: path::PathBuf, format: Format) -> Self {
+        OutputConfig { path, format, template: None, item_template: None, compact: false, compress: Vec::new() }
+    }
+
+    pub fn is_stdout(&self) -> bool {
+        self.path.as_os_str() == "-"
+    }
+
+    pub fn renderer(&self) -> Result<Renderer> {
+        Ok(match &self.template {
+            Some(template) => Renderer::Template(Box::new(Template::load(template, self.item_template.as_deref())?)),
+            None => Renderer::Format { format: self.format, compact: self.compact },
+        })
+    }
+}
+
+// How a feed is turned into the content of an output
+pub enum Renderer {
+    Format { format: Format, compact: bool },
+    Template(Box<Template>),
+}
+
+impl Renderer {
+    pub fn render(&self, feed: &NewsFeed) -> Result<String> {
+        match self {
+            Renderer::Format { format, compact } => Ok(format.render(feed, *compact)),
+            Renderer::Template(template) => template.render(feed),
+        }
+    }
+}
+
+pub struct FileSink {
+    pub path: path::PathBuf,
+    pub renderer: Renderer,
+    pub compress: Vec<Compression>,
+}
+
+impl FileSink {
+    // Write to a temporary file next to the target and rename it in place, so
+    // readers never see a half written feed. Compressed copies go next to it
+    // with their extension added, like feed.xml.gz.
+    //
+    // A file that would only change in when it was generated is left alone,
+    // keeping its old updated time so that readers don't refetch it. Returns
+    // whether the file was written.
+    pub fn write(&self, feed: &NewsFeed) -> Result<bool> {
+        let content = self.renderer.render(feed)?;
+        let changed = match fs::read_to_string(&self.path) {
+            Ok(existing) => existing != content && !self.renders_to(feed, &existing) && !self.differs_in_updated_only(feed, &content, &existing),
+            Err(_) => true,
+        };
+        if changed {
+            write_atomic(&self.path, content.as_bytes())?;
+        }
+
+        for compression in &self.compress {
+            let mut compressed_path = self.path.clone().into_os_string();
+            compressed_path.push(format!(".{}", compression.extension()));
+            let compressed_path = path::PathBuf::from(compressed_path);
+            if changed || !compressed_path.exists() {
+                write_atomic(&compressed_path, &compression.compress(content.as_bytes())?)?;
+            }
+        }
+        Ok(changed)
+    }
+
+    // Whether the feed renders to the existing Atom document when given its
+    // updated time
+    fn renders_to(&self, feed: &NewsFeed, existing: &str) -> bool {
+        let Ok(previous) = existing.parse::<atom::Feed>() else {
+            return false;
+        };
+        let feed = NewsFeed { updated: previous.updated.with_timezone(&Utc), ..feed.clone() };
+        self.renderer.render(&feed).is_ok_and(|content| content == existing)
+    }
+
+    // Whether the content differs from the existing output only where the
+    // feed's updated time goes, for outputs like HTML pages where it can't be
+    // read back. Lines that render differently for two far apart times are
+    // compared by what comes before and after the time on them.
+    fn differs_in_updated_only(&self, feed: &NewsFeed, content: &str, existing: &str) -> bool {
+        let render_at = |updated: DateTime<Utc>| self.renderer.render(&NewsFeed { updated, ..feed.clone() });
+        let (Ok(early), Ok(late)) = (render_at(DateTime::UNIX_EPOCH), render_at(Utc.with_ymd_and_hms(2011, 11, 11, 11, 11, 11).unwrap())) else {
+            return false;
+        };
+        let lines = [early.lines().collect::<Vec<_>>(), late.lines().collect(), content.lines().collect(), existing.lines().collect()];
+        if lines.iter().any(|document| document.len() != lines[0].len()) {
+            return false;
+        }
+
+        (0..lines[0].len()).all(|i| {
+            let (early, late, new, old) = (lines[0][i], lines[1][i], lines[2][i], lines[3][i]);
+            if early == late {
+                return new == old;
+            }
+            let prefix = common_prefix(early, late);
+            let suffix = common_prefix(&early[prefix..].chars().rev().collect::<String>(), &late[prefix..].chars().rev().collect::<String>());
+            old.len() >= prefix + suffix && old.starts_with(&early[..prefix]) && old.ends_with(&early[early.len() - suffix..])
+        })
+    }
+}
+
+impl Sink for FileSink {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        self.write(feed)?;
+        Ok(())
+    }
+}
+
+pub struct StdoutSink {
+    pub renderer: Renderer,
+}
+
+impl Sink for StdoutSink {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(self.renderer.render(feed)?.as_bytes())?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+// Length in bytes of the common start of two strings
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, a), b)| a != b)
+        .map(|((index, _), _)| index)
+        .unwrap_or_else(|| a.len().min(b.len()))
+}
+
+pub fn write_atomic(file_path: &path::Path, content: &[u8]) -> Result<()> {
+    let file_name = file_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("Not able to get file name")?;
+    let tmp_path = file_path.with_file_name(format!(".{}.tmp", file_name));
+
+    fs::write(&tmp_path, content)
+        .with_context(|| format!("Unable to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, file_path)
+        .with_context(|| format!("Unable to move {} to {}", tmp_path.display(), file_path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use flate2::read::GzDecoder;
+
+    #[test]
+    fn compressed_copies_decompress_to_the_content() {
+        let content = "<feed>".repeat(100);
+
+        let mut gzipped = String::new();
+        GzDecoder::new(&Compression::Gzip.compress(content.as_bytes()).unwrap()[..]).read_to_string(&mut gzipped).unwrap();
+        assert_eq!(gzipped, content);
+
+        let mut brotli = String::new();
+        brotli::Decompressor::new(&Compression::Br.compress(content.as_bytes()).unwrap()[..], 4096).read_to_string(&mut brotli).unwrap();
+        assert_eq!(brotli, content);
+    }
+
+    #[test]
+    fn unchanged_files_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let feed_path = dir.path().join("feed.xml");
+        let sink = FileSink { path: feed_path.clone(), renderer: Renderer::Format { format: Format::Atom, compact: false }, compress: Vec::new() };
+        let feed = NewsFeed { id: "test".to_string(), title: "Test".to_string(), updated: Utc::now(), ..Default::default() };
+
+        assert!(sink.write(&feed).unwrap());
+        let written = fs::read_to_string(&feed_path).unwrap();
+
+        let later = NewsFeed { updated: Utc::now() + chrono::TimeDelta::hours(1), ..feed.clone() };
+        assert!(!sink.write(&later).unwrap());
+        assert_eq!(fs::read_to_string(&feed_path).unwrap(), written);
+
+        let retitled = NewsFeed { title: "Other".to_string(), ..later };
+        assert!(sink.write(&retitled).unwrap());
+    }
+
+    #[test]
+    fn pages_changing_only_in_updated_time_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = FileSink { path: dir.path().join("feed.html"), renderer: Renderer::Format { format: Format::Html, compact: false }, compress: Vec::new() };
+        let feed = NewsFeed { id: "test".to_string(), title: "Test".to_string(), updated: Utc::now(), ..Default::default() };
+
+        assert!(sink.write(&feed).unwrap());
+        let later = NewsFeed { updated: Utc::now() + chrono::TimeDelta::days(40), ..feed.clone() };
+        assert!(!sink.write(&later).unwrap());
+
+        let retitled = NewsFeed { title: "Other".to_string(), ..later };
+        assert!(sink.write(&retitled).unwrap());
+    }
+}
@@ -0,0 +1,191 @@
+// A feed as static ActivityStreams documents: an actor, its outbox with a
+// Create activity per item, and the notes themselves. Served from any static
+// host, the actor can be looked up and read from fediverse servers. Following
+// needs an inbox that accepts requests, which static hosting can't provide.
+
+use std::{fs, path};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::sinks::{write_atomic, Sink};
+use crate::{org, NewsFeed, NewsItem};
+
+const CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActivityPubConfig {
+    // Directory to write the documents to, resolved against output_dir
+    pub path: path::PathBuf,
+    // Public URL the directory is served at
+    pub url: String,
+    // Defaults to the feed id
+    pub username: Option<String>,
+}
+
+pub struct ActivityPubSink<'a> {
+    pub config: &'a ActivityPubConfig,
+    // Resolved path of the directory
+    pub dir: path::PathBuf,
+}
+
+impl ActivityPubSink<'_> {
+    fn url(&self, document: &str) -> String {
+        format!("{}/{}", self.config.url.trim_end_matches('/'), document)
+    }
+
+    fn username<'a>(&'a self, feed: &'a NewsFeed) -> &'a str {
+        self.config.username.as_deref().unwrap_or(&feed.id)
+    }
+
+    fn actor(&self, feed: &NewsFeed) -> Value {
+        let mut actor = json!({
+            "@context": CONTEXT,
+            "id": self.url("actor.json"),
+            "type": "Service",
+            "preferredUsername": self.username(feed),
+            "name": feed.title,
+            "summary": org::escape(&feed.subtitle),
+            "inbox": self.url("inbox.json"),
+            "outbox": self.url("outbox.json"),
+        });
+        if let Some(icon) = feed.logo.as_ref().or(feed.icon.as_ref()) {
+            actor["icon"] = json!({ "type": "Image", "url": icon });
+        }
+        actor
+    }
+
+    // Ids can have characters that don't belong in URLs or file names, like
+    // # or /, so notes are named by the UUID items are written out with
+    fn note_name(item: &NewsItem) -> String {
+        format!("notes/{}.json", item.feed_id())
+    }
+
+    fn note(&self, item: &NewsItem) -> Value {
+        let mut content = format!("<p><a href=\"{}\">{}</a></p>", org::escape(&item.link), org::escape(&item.title));
+        match (&item.content, &item.summary) {
+            (Some(html), _) if item.content_html => content.push_str(html),
+            (_, Some(summary)) => content.push_str(&format!("<p>{}</p>", org::escape(summary))),
+            _ => (),
+        }
+
+        json!({
+            "id": self.url(&Self::note_name(item)),
+            "type": "Note",
+            "attributedTo": self.url("actor.json"),
+            "to": [PUBLIC],
+            "url": item.link,
+            "published": item.published.to_rfc3339(),
+            "updated": item.updated.to_rfc3339(),
+            "content": content,
+            "tag": item.categories.iter()
+                .map(|tag| json!({ "type": "Hashtag", "name": format!("#{}", tag) }))
+                .collect::<Vec<_>>(),
+            "attachment": item.enclosures.iter()
+                .map(|enclosure| json!({ "type": "Document", "url": enclosure.url, "mediaType": enclosure.mime_type }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn create(&self, item: &NewsItem) -> Value {
+        let note = self.note(item);
+        json!({
+            "id": format!("{}#create", note["id"].as_str().unwrap_or_default()),
+            "type": "Create",
+            "actor": self.url("actor.json"),
+            "to": [PUBLIC],
+            "published": item.published.to_rfc3339(),
+            "object": note,
+        })
+    }
+
+    fn outbox(&self, feed: &NewsFeed) -> Value {
+        json!({
+            "@context": CONTEXT,
+            "id": self.url("outbox.json"),
+            "type": "OrderedCollection",
+            "totalItems": feed.items.len(),
+            "orderedItems": feed.items.iter().map(|item| self.create(item)).collect::<Vec<_>>(),
+        })
+    }
+
+    // To be served at /.well-known/webfinger so that @username@host resolves
+    fn webfinger(&self, feed: &NewsFeed) -> Result<Value> {
+        let host = self.config.url.split("://").nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| anyhow!("ActivityPub url {} needs to be a full URL", self.config.url))?;
+
+        Ok(json!({
+            "subject": format!("acct:{}@{}", self.username(feed), host),
+            "links": [{ "rel": "self", "type": "application/activity+json", "href": self.url("actor.json") }],
+        }))
+    }
+}
+
+fn write_json(file_path: &path::Path, value: &Value) -> Result<()> {
+    write_atomic(file_path, serde_json::to_string_pretty(value)?.as_bytes())
+}
+
+impl Sink for ActivityPubSink<'_> {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        fs::create_dir_all(self.dir.join("notes"))?;
+
+        write_json(&self.dir.join("actor.json"), &self.actor(feed))?;
+        write_json(&self.dir.join("webfinger.json"), &self.webfinger(feed)?)?;
+        // Nothing is accepted, but actors need an inbox
+        write_json(&self.dir.join("inbox.json"), &json!({
+            "@context": CONTEXT,
+            "id": self.url("inbox.json"),
+            "type": "OrderedCollection",
+            "totalItems": 0,
+            "orderedItems": [],
+        }))?;
+        for item in &feed.items {
+            let mut note = self.note(item);
+            note["@context"] = json!(CONTEXT);
+            write_json(&self.dir.join(Self::note_name(item)), &note)?;
+        }
+        write_json(&self.dir.join("outbox.json"), &self.outbox(feed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn outbox_has_a_create_per_item() {
+        let config = ActivityPubConfig { path: "ap".into(), url: "https://example.com/ap/".to_string(), username: None };
+        let sink = ActivityPubSink { config: &config, dir: "ap".into() };
+        let feed = NewsFeed {
+            id: "links".to_string(),
+            title: "Links".to_string(),
+            items: vec![NewsItem {
+                id: "abc#https://example.com/a".to_string(),
+                link: "https://example.com/a".to_string(),
+                title: "A & B".to_string(),
+                summary: Some("Worth <reading>".to_string()),
+                published: Utc::now(),
+                updated: Utc::now(),
+                categories: vec!["ml".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let outbox = sink.outbox(&feed);
+        let create = &outbox["orderedItems"][0];
+        assert_eq!(create["type"], "Create");
+        assert_eq!(create["actor"], "https://example.com/ap/actor.json");
+        let note_id = format!("https://example.com/ap/notes/{}.json", feed.items[0].feed_id());
+        assert_eq!(create["object"]["id"], note_id.as_str());
+        assert_eq!(create["id"], format!("{}#create", note_id).as_str());
+        assert_eq!(create["object"]["content"], "<p><a href=\"https://example.com/a\">A &amp; B</a></p><p>Worth &lt;reading&gt;</p>");
+        assert_eq!(create["object"]["tag"][0]["name"], "#ml");
+
+        assert_eq!(sink.webfinger(&feed).unwrap()["subject"], "acct:links@example.com");
+    }
+}
@@ -0,0 +1,93 @@
+// Posting items as embeds through a Discord webhook
+// (https://discord.com/developers/docs/resources/webhook#execute-webhook)
+
+use std::thread;
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::sinks::Sink;
+use crate::{NewsFeed, NewsItem};
+
+// Discord rejects embeds with longer titles and descriptions
+const MAX_TITLE: usize = 256;
+const MAX_DESCRIPTION: usize = 4096;
+
+pub struct DiscordSink<'a> {
+    pub webhook_url: &'a str,
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max - 1).collect::<String>())
+    }
+}
+
+fn embed(item: &NewsItem) -> Value {
+    let mut embed = json!({
+        "title": truncate(&item.title, MAX_TITLE),
+        "url": item.link,
+        "timestamp": item.published.to_rfc3339(),
+    });
+    if let Some(summary) = &item.summary {
+        embed["description"] = json!(truncate(summary, MAX_DESCRIPTION));
+    }
+    if !item.categories.is_empty() {
+        embed["footer"] = json!({ "text": item.categories.join(", ") });
+    }
+    embed
+}
+
+impl Sink for DiscordSink<'_> {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        let client = Client::new();
+        for item in &feed.items {
+            let body = json!({ "embeds": [embed(item)] });
+            let mut response = client.post(self.webhook_url).json(&body).send()
+                .with_context(|| format!("Unable to post {} to Discord", item.link))?;
+
+            // Webhooks are rate limited to a few posts a second, and say how
+            // long to wait when posting faster
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                let wait: Value = response.json().unwrap_or_default();
+                thread::sleep(Duration::from_secs_f64(wait["retry_after"].as_f64().unwrap_or(1.0)));
+                response = client.post(self.webhook_url).json(&body).send()
+                    .with_context(|| format!("Unable to post {} to Discord", item.link))?;
+            }
+
+            if !response.status().is_success() {
+                return Err(anyhow!("Discord rejected {}: {}", item.link, response.status()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn items_become_embeds() {
+        let item = NewsItem {
+            id: "a".to_string(),
+            link: "https://example.com/a".to_string(),
+            title: "A".repeat(300),
+            published: Utc::now(),
+            updated: Utc::now(),
+            categories: vec!["ml".to_string(), "papers".to_string()],
+            ..Default::default()
+        };
+
+        let embed = embed(&item);
+        assert_eq!(embed["title"].as_str().unwrap().chars().count(), MAX_TITLE);
+        assert_eq!(embed["url"], "https://example.com/a");
+        assert_eq!(embed["footer"]["text"], "ml, papers");
+        assert!(embed.get("description").is_none());
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::Deserialize;
+
+use crate::sinks::Sink;
+use crate::{html, NewsFeed};
+
+// SMTP server digests are sent through, from the [smtp] table of the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    // Defaults to the usual port for the security setting
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // Sender address, like `Journalist <journalist@example.com>`
+    pub from: String,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmtpSecurity {
+    // Upgrade a plain connection, usually on port 587
+    #[default]
+    Starttls,
+    // TLS from the start, usually on port 465
+    Tls,
+    // Plain text, only for local relays
+    None,
+}
+
+// Sends the feed as an email with plain text and HTML versions
+pub struct EmailSink<'a> {
+    pub smtp: &'a SmtpConfig,
+    pub to: &'a [String],
+}
+
+impl EmailSink<'_> {
+    fn message(&self, feed: &NewsFeed) -> Result<Message> {
+        let mut builder = Message::builder()
+            .from(self.smtp.from.parse().with_context(|| format!("Invalid sender address {}", self.smtp.from))?)
+            .subject(format!("{} for {}", feed.title, feed.updated.format("%B %-d, %Y")));
+        for to in self.to {
+            let mailbox: Mailbox = to.parse().with_context(|| format!("Invalid recipient address {}", to))?;
+            builder = builder.to(mailbox);
+        }
+
+        Ok(builder.multipart(MultiPart::alternative_plain_html(plain_text(feed), html::to_html_string(feed)))?)
+    }
+
+    fn transport(&self) -> Result<SmtpTransport> {
+        let mut builder = match self.smtp.security {
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&self.smtp.host)?,
+            SmtpSecurity::Tls => SmtpTransport::relay(&self.smtp.host)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(&self.smtp.host),
+        };
+        if let Some(port) = self.smtp.port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &self.smtp.username {
+            builder = builder.credentials(Credentials::new(username.clone(), self.smtp.password.clone().unwrap_or_default()));
+        }
+        Ok(builder.build())
+    }
+}
+
+impl Sink for EmailSink<'_> {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        let message = self.message(feed)?;
+        self.transport()?.send(&message)
+            .with_context(|| format!("Unable to send {} via {}", feed.id, self.smtp.host))?;
+        Ok(())
+    }
+}
+
+// Items as title, link, and summary blocks
+fn plain_text(feed: &NewsFeed) -> String {
+    let mut text = format!("{}\n\n", feed.title);
+    for item in &feed.items {
+        text.push_str(&format!("{}\n{}\n", item.title, item.link));
+        if let Some(summary) = &item.summary {
+            text.push_str(&format!("\n{}\n", summary));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::NewsItem;
+
+    #[test]
+    fn message_has_both_versions() {
+        let feed = NewsFeed {
+            id: "picks".to_string(),
+            updated: Utc::now(),
+            link: "/picks".to_string(),
+            title: "Unread picks".to_string(),
+            subtitle: String::new(),
+            items: vec![NewsItem {
+                id: "a".to_string(),
+                link: "https://example.com/a".to_string(),
+                title: "An article".to_string(),
+                summary: Some("Worth reading".to_string()),
+                published: Utc::now(),
+                updated: Utc::now(),
+                ..Default::default()
+            }],
+            authors: Vec::new(),
+            categories: Vec::new(),
+            generator: "journalist".to_string(),
+            ..Default::default()
+        };
+        let smtp = SmtpConfig {
+            host: "localhost".to_string(),
+            port: None,
+            username: None,
+            password: None,
+            from: "Journalist <journalist@example.com>".to_string(),
+            security: SmtpSecurity::None,
+        };
+        let to = vec!["me@example.com".to_string(), "you@example.com".to_string()];
+
+        let message = String::from_utf8(EmailSink { smtp: &smtp, to: &to }.message(&feed).unwrap().formatted()).unwrap();
+        assert!(message.contains("To: me@example.com, you@example.com"));
+        assert!(message.contains("multipart/alternative"));
+        assert!(message.contains("An article\r\nhttps://example.com/a"));
+        assert!(message.contains("Content-Type: text/html"));
+
+        let bad = vec!["not an address".to_string()];
+        assert!(EmailSink { smtp: &smtp, to: &bad }.message(&feed).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+// Mirroring items into a linkding instance through its REST API
+// (https://linkding.link/api/)
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::sinks::Sink;
+use crate::{NewsFeed, NewsItem};
+
+// Instance and token to save with, from the [linkding] table of the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinkdingConfig {
+    pub url: String,
+    // From the integrations page of the settings
+    pub token: String,
+    // Mark the bookmarks as unread
+    #[serde(default)]
+    pub unread: bool,
+}
+
+pub struct LinkdingSink<'a> {
+    pub config: &'a LinkdingConfig,
+}
+
+impl LinkdingSink<'_> {
+    fn bookmark(&self, item: &NewsItem) -> Value {
+        // linkding splits tags on whitespace
+        let tags: Vec<String> = item.categories.iter()
+            .map(|category| category.split_whitespace().collect::<Vec<_>>().join("-"))
+            .collect();
+        json!({
+            "url": item.link,
+            "title": item.title,
+            "description": item.summary.as_deref().unwrap_or_default(),
+            "tag_names": tags,
+            "unread": self.config.unread,
+        })
+    }
+}
+
+impl Sink for LinkdingSink<'_> {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        let client = Client::new();
+        let url = format!("{}/api/bookmarks/", self.config.url.trim_end_matches('/'));
+        for item in &feed.items {
+            let response = client.post(&url)
+                .header("Authorization", format!("Token {}", self.config.token))
+                .json(&self.bookmark(item))
+                .send()
+                .with_context(|| format!("Unable to save {} to linkding", item.link))?;
+
+            if !response.status().is_success() {
+                return Err(anyhow!("linkding rejected {}: {}", item.link, response.status()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn tags_carry_over() {
+        let config = LinkdingConfig { url: "https://links.example.com".to_string(), token: "token".to_string(), unread: true };
+        let item = NewsItem {
+            id: "a".to_string(),
+            link: "https://example.com/a".to_string(),
+            title: "A".to_string(),
+            summary: Some("Worth reading".to_string()),
+            published: Utc::now(),
+            updated: Utc::now(),
+            categories: vec!["machine learning".to_string(), "ml".to_string()],
+            ..Default::default()
+        };
+
+        let bookmark = LinkdingSink { config: &config }.bookmark(&item);
+        assert_eq!(bookmark["tag_names"], json!(["machine-learning", "ml"]));
+        assert_eq!(bookmark["description"], "Worth reading");
+        assert_eq!(bookmark["unread"], true);
+    }
+}
@@ -0,0 +1,91 @@
+// Posting items to a website through its Micropub endpoint
+// (https://www.w3.org/TR/micropub/) as bookmarks
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::sinks::Sink;
+use crate::{NewsFeed, NewsItem};
+
+// Endpoint and token to post with, from the [micropub] table of the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct MicropubConfig {
+    pub endpoint: String,
+    pub token: String,
+    // Create posts as drafts to look over before they go out
+    #[serde(default)]
+    pub draft: bool,
+}
+
+pub struct MicropubSink<'a> {
+    pub config: &'a MicropubConfig,
+}
+
+impl MicropubSink<'_> {
+    // An h-entry bookmarking the item's link
+    fn entry(&self, item: &NewsItem) -> Value {
+        let mut properties = json!({
+            "bookmark-of": [item.link],
+            "name": [item.title],
+            "published": [item.published.to_rfc3339()],
+        });
+        if let Some(summary) = &item.summary {
+            properties["content"] = json!([summary]);
+        }
+        if !item.categories.is_empty() {
+            properties["category"] = json!(item.categories);
+        }
+        if self.config.draft {
+            properties["post-status"] = json!(["draft"]);
+        }
+
+        json!({ "type": ["h-entry"], "properties": properties })
+    }
+}
+
+impl Sink for MicropubSink<'_> {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        let client = Client::new();
+        for item in &feed.items {
+            let response = client.post(&self.config.endpoint)
+                .bearer_auth(&self.config.token)
+                .json(&self.entry(item))
+                .send()
+                .with_context(|| format!("Unable to post {} to {}", item.link, self.config.endpoint))?;
+
+            if !response.status().is_success() {
+                return Err(anyhow!("{} rejected {}: {}", self.config.endpoint, item.link, response.status()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn items_become_bookmarks() {
+        let config = MicropubConfig { endpoint: "https://example.com/micropub".to_string(), token: "token".to_string(), draft: true };
+        let item = NewsItem {
+            id: "a".to_string(),
+            link: "https://example.com/a".to_string(),
+            title: "A".to_string(),
+            published: Utc::now(),
+            updated: Utc::now(),
+            categories: vec!["ml".to_string()],
+            ..Default::default()
+        };
+
+        let entry = MicropubSink { config: &config }.entry(&item);
+        assert_eq!(entry["type"][0], "h-entry");
+        assert_eq!(entry["properties"]["bookmark-of"][0], "https://example.com/a");
+        assert_eq!(entry["properties"]["category"][0], "ml");
+        assert_eq!(entry["properties"]["post-status"][0], "draft");
+        assert!(entry["properties"].get("content").is_none());
+    }
+}
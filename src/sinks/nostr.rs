@@ -0,0 +1,214 @@
+// Posting items as Nostr events (NIP-01) to relays, either as short text notes
+// or as long-form articles (NIP-23)
+
+use std::collections::HashSet;
+use std::net::TcpStream;
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use k256::schnorr::SigningKey;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+use crate::sinks::Sink;
+use crate::{NewsFeed, NewsItem};
+
+const RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Key and relays to post with, from the [nostr] table of the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct NostrConfig {
+    // Hex or nsec encoded
+    pub secret_key: String,
+    pub relays: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NostrKind {
+    // Kind 1 text notes with the title, summary, and link
+    #[default]
+    Note,
+    // Kind 30023 articles
+    LongForm,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: i64,
+    pub kind: u16,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String,
+}
+
+fn signing_key(secret_key: &str) -> Result<SigningKey> {
+    let bytes = if secret_key.starts_with("nsec") {
+        let (hrp, bytes) = bech32::decode(secret_key).context("Invalid nsec key")?;
+        if hrp.as_str() != "nsec" {
+            return Err(anyhow!("Invalid nsec key"));
+        }
+        bytes
+    } else {
+        hex::decode(secret_key).context("Invalid hex key")?
+    };
+    SigningKey::from_bytes(&bytes).map_err(|_| anyhow!("Invalid Nostr secret key"))
+}
+
+fn sign(key: &SigningKey, created_at: i64, kind: u16, tags: Vec<Vec<String>>, content: String) -> Result<Event> {
+    let pubkey = hex::encode(key.verifying_key().to_bytes());
+    // The id is the hash of this exact serialization
+    let serialized = serde_json::to_string(&json!([0, pubkey, created_at, kind, tags, content]))?;
+    let id = Sha256::digest(serialized.as_bytes());
+    let sig = key.sign_raw(&id, &rand::random())
+        .map_err(|err| anyhow!("Unable to sign event: {}", err))?;
+
+    Ok(Event { id: hex::encode(id), pubkey, created_at, kind, tags, content, sig: hex::encode(sig.to_bytes()) })
+}
+
+fn tag(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+fn event(key: &SigningKey, kind: NostrKind, item: &NewsItem) -> Result<Event> {
+    let mut tags = vec![tag(&["r", &item.link])];
+    tags.extend(item.categories.iter().map(|category| tag(&["t", &category.to_lowercase()])));
+    let summary = item.summary.as_deref().unwrap_or_default();
+
+    match kind {
+        NostrKind::Note => {
+            let content = [item.title.as_str(), summary, item.link.as_str()].iter()
+                .filter(|part| !part.is_empty())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            sign(key, chrono::Utc::now().timestamp(), 1, tags, content)
+        },
+        NostrKind::LongForm => {
+            tags.push(tag(&["d", &item.id]));
+            tags.push(tag(&["title", &item.title]));
+            tags.push(tag(&["published_at", &item.published.timestamp().to_string()]));
+            let text = item.content.as_deref().filter(|_| !item.content_html).unwrap_or(summary);
+            sign(key, item.updated.timestamp(), 30023, tags, format!("{}\n\n[{}]({})", text, item.title, item.link))
+        },
+    }
+}
+
+// Send the events and wait for the relay to acknowledge them. Returns how many
+// were accepted.
+fn publish(relay: &str, events: &[Event]) -> Result<usize> {
+    let (mut socket, _) = tungstenite::connect(relay).with_context(|| format!("Unable to connect to {}", relay))?;
+    set_timeout(&mut socket)?;
+
+    for event in events {
+        socket.send(Message::Text(json!(["EVENT", event]).to_string()))?;
+    }
+
+    let mut pending: HashSet<&str> = events.iter().map(|event| event.id.as_str()).collect();
+    let mut accepted = 0;
+    while !pending.is_empty() {
+        let text = match socket.read().with_context(|| format!("No answer from {}", relay))? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        // ["OK", <event id>, <accepted>, <message>]
+        let Ok(Value::Array(message)) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+        if message.first().and_then(Value::as_str) != Some("OK") {
+            continue;
+        }
+        if let Some(id) = message.get(1).and_then(Value::as_str).filter(|id| pending.remove::<str>(id)) {
+            if message.get(2).and_then(Value::as_bool) == Some(true) {
+                accepted += 1;
+            } else {
+                warn!("{} rejected {}: {}", relay, id, message.get(3).and_then(Value::as_str).unwrap_or_default());
+            }
+        }
+    }
+
+    let _ = socket.close(None);
+    Ok(accepted)
+}
+
+fn set_timeout(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Result<()> {
+    match socket.get_mut() {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(RELAY_TIMEOUT))?,
+        MaybeTlsStream::NativeTls(stream) => stream.get_mut().set_read_timeout(Some(RELAY_TIMEOUT))?,
+        _ => (),
+    }
+    Ok(())
+}
+
+pub struct NostrSink<'a> {
+    pub config: &'a NostrConfig,
+    pub kind: NostrKind,
+}
+
+impl Sink for NostrSink<'_> {
+    // Posts every item of the feed. Relays that fail are skipped as long as
+    // one of them takes the events.
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        let key = signing_key(&self.config.secret_key)?;
+        let events = feed.items.iter().map(|item| event(&key, self.kind, item)).collect::<Result<Vec<_>>>()?;
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut posted = false;
+        for relay in &self.config.relays {
+            match publish(relay, &events) {
+                Ok(accepted) => posted |= accepted > 0,
+                Err(err) => warn!("Unable to post {} to {}: {:#}", feed.id, relay, err),
+            }
+        }
+
+        if posted {
+            Ok(())
+        } else {
+            Err(anyhow!("No relay took the items of {}", feed.id))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn events_are_signed() {
+        let key = signing_key("0000000000000000000000000000000000000000000000000000000000000003").unwrap();
+        let item = NewsItem {
+            id: "a".to_string(),
+            link: "https://example.com/a".to_string(),
+            title: "A \"quoted\" title".to_string(),
+            summary: Some("Worth reading".to_string()),
+            published: Utc::now(),
+            updated: Utc::now(),
+            categories: vec!["ML".to_string()],
+            ..Default::default()
+        };
+
+        let note = event(&key, NostrKind::Note, &item).unwrap();
+        assert_eq!(note.kind, 1);
+        assert_eq!(note.content, "A \"quoted\" title\n\nWorth reading\n\nhttps://example.com/a");
+        assert_eq!(note.tags, vec![tag(&["r", "https://example.com/a"]), tag(&["t", "ml"])]);
+
+        let id = hex::decode(&note.id).unwrap();
+        let sig = k256::schnorr::Signature::try_from(hex::decode(&note.sig).unwrap().as_slice()).unwrap();
+        assert!(key.verifying_key().verify_raw(&id, &sig).is_ok());
+
+        let article = event(&key, NostrKind::LongForm, &item).unwrap();
+        assert_eq!(article.kind, 30023);
+        assert!(article.tags.contains(&tag(&["d", "a"])));
+
+        assert!(signing_key("nope").is_err());
+    }
+}
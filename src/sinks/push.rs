@@ -0,0 +1,90 @@
+// Push notifications about new items through the services under [notify], as
+// a nudge per item or one digest for all the new items of a run
+
+use anyhow::{anyhow, Result};
+use log::warn;
+use serde::Deserialize;
+
+use crate::notifications::{self, Notification, NotifyConfig};
+use crate::sinks::Sink;
+use crate::NewsFeed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PushMode {
+    Item,
+    Digest,
+}
+
+pub struct PushSink<'a> {
+    pub config: &'a NotifyConfig,
+    pub mode: PushMode,
+}
+
+// Title, message, and link of each notification to send for the items
+fn messages(feed: &NewsFeed, mode: PushMode) -> Vec<(String, String, Option<String>)> {
+    match mode {
+        PushMode::Item => feed.items.iter()
+            .map(|item| (item.title.clone(), item.summary.clone().unwrap_or_else(|| item.link.clone()), Some(item.link.clone())))
+            .collect(),
+        PushMode::Digest if feed.items.is_empty() => Vec::new(),
+        PushMode::Digest => {
+            let title = match feed.items.len() {
+                1 => format!("1 new item in {}", feed.title),
+                count => format!("{} new items in {}", count, feed.title),
+            };
+            let titles = feed.items.iter().map(|item| format!("• {}", item.title)).collect::<Vec<_>>().join("\n");
+            vec![(title, titles, Some(feed.link.clone()).filter(|link| !link.is_empty()))]
+        },
+    }
+}
+
+impl Sink for PushSink<'_> {
+    // Services that fail are skipped as long as one of them gets the
+    // notification
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        for (title, message, url) in messages(feed, self.mode) {
+            let notification = Notification { title: &title, message: &message, url: url.as_deref(), failure: false };
+            let mut sent = false;
+            for (service, result) in notifications::send(self.config, &notification) {
+                match result {
+                    Ok(()) => sent = true,
+                    Err(err) => warn!("Unable to notify about {} via {}: {}", feed.id, service, err),
+                }
+            }
+            if !sent {
+                return Err(anyhow!("No service took the notification about {}", title));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::NewsItem;
+
+    #[test]
+    fn digests_list_titles() {
+        let item = |id: &str| NewsItem {
+            id: id.to_string(),
+            link: format!("https://example.com/{}", id),
+            title: id.to_uppercase(),
+            published: Utc::now(),
+            updated: Utc::now(),
+            ..Default::default()
+        };
+        let feed = NewsFeed {
+            id: "unread".to_string(),
+            title: "Unread".to_string(),
+            items: vec![item("a"), item("b")],
+            ..Default::default()
+        };
+
+        assert_eq!(messages(&feed, PushMode::Digest), vec![("2 new items in Unread".to_string(), "• A\n• B".to_string(), None)]);
+        assert_eq!(messages(&feed, PushMode::Item)[1], ("B".to_string(), "https://example.com/b".to_string(), Some("https://example.com/b".to_string())));
+        assert!(messages(&NewsFeed::default(), PushMode::Digest).is_empty());
+    }
+}
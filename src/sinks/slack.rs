@@ -0,0 +1,85 @@
+// Posting items through a Slack incoming webhook, laid out with Block Kit
+// (https://api.slack.com/block-kit)
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+
+use crate::sinks::Sink;
+use crate::{NewsFeed, NewsItem};
+
+// Slack rejects section texts longer than this
+const MAX_SECTION: usize = 3000;
+
+pub struct SlackSink<'a> {
+    pub webhook_url: &'a str,
+}
+
+// The only characters mrkdwn needs escaped
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn message(item: &NewsItem) -> Value {
+    let mut text = format!("*<{}|{}>*", item.link, escape(&item.title));
+    if let Some(summary) = &item.summary {
+        text.push('\n');
+        text.push_str(&escape(summary));
+    }
+    if text.chars().count() > MAX_SECTION {
+        text = format!("{}…", text.chars().take(MAX_SECTION - 1).collect::<String>());
+    }
+
+    let mut blocks = vec![json!({ "type": "section", "text": { "type": "mrkdwn", "text": text } })];
+    if !item.categories.is_empty() {
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": escape(&item.categories.join(", ")) }],
+        }));
+    }
+
+    // The plain text is what notifications show
+    json!({ "text": format!("{} {}", item.title, item.link), "blocks": blocks, "unfurl_links": false })
+}
+
+impl Sink for SlackSink<'_> {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        let client = Client::new();
+        for item in &feed.items {
+            let response = client.post(self.webhook_url)
+                .json(&message(item))
+                .send()
+                .with_context(|| format!("Unable to post {} to Slack", item.link))?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                return Err(anyhow!("Slack rejected {}: {} {}", item.link, status, response.text().unwrap_or_default()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn items_become_blocks() {
+        let item = NewsItem {
+            id: "a".to_string(),
+            link: "https://example.com/a".to_string(),
+            title: "A <b> & c".to_string(),
+            summary: Some("Worth reading".to_string()),
+            published: Utc::now(),
+            updated: Utc::now(),
+            categories: vec!["ml".to_string()],
+            ..Default::default()
+        };
+
+        let message = message(&item);
+        assert_eq!(message["blocks"][0]["text"]["text"], "*<https://example.com/a|A &lt;b&gt; &amp; c>*\nWorth reading");
+        assert_eq!(message["blocks"][1]["elements"][0]["text"], "ml");
+    }
+}
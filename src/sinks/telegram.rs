@@ -0,0 +1,97 @@
+// Sending items to a Telegram chat through a bot
+// (https://core.telegram.org/bots/api#sendmessage)
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::sinks::Sink;
+use crate::{org, NewsFeed, NewsItem};
+
+// Bot and chat to send with, from the [telegram] table of the config
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramConfig {
+    pub bot_token: String,
+    // Numeric id, or @username for public channels
+    pub chat_id: String,
+}
+
+pub struct TelegramSink<'a> {
+    pub config: &'a TelegramConfig,
+}
+
+// Telegram only links hashtags made of letters, digits, and underscores
+fn hashtag(category: &str) -> String {
+    let tag: String = category.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("#{}", tag)
+}
+
+// The message in Telegram's HTML subset: linked title, summary, and tags
+fn message(item: &NewsItem) -> String {
+    let mut text = format!("<b><a href=\"{}\">{}</a></b>", org::escape(&item.link), org::escape(&item.title));
+    if let Some(summary) = &item.summary {
+        text.push_str(&format!("\n\n{}", org::escape(summary)));
+    }
+    if !item.categories.is_empty() {
+        text.push_str(&format!("\n\n{}", item.categories.iter().map(|category| hashtag(category)).collect::<Vec<_>>().join(" ")));
+    }
+    text
+}
+
+impl TelegramSink<'_> {
+    fn request(&self, item: &NewsItem) -> Value {
+        json!({
+            "chat_id": self.config.chat_id,
+            "text": message(item),
+            "parse_mode": "HTML",
+        })
+    }
+}
+
+impl Sink for TelegramSink<'_> {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        let client = Client::new();
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.config.bot_token);
+        for item in &feed.items {
+            let response = client.post(&url)
+                .json(&self.request(item))
+                .send()
+                .with_context(|| format!("Unable to send {} to Telegram", item.link))?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let body: Value = response.json().unwrap_or_default();
+                return Err(anyhow!("Telegram rejected {}: {} {}", item.link, status, body["description"].as_str().unwrap_or_default()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn messages_are_escaped() {
+        let item = NewsItem {
+            id: "a".to_string(),
+            link: "https://example.com/a?x=1&y=2".to_string(),
+            title: "<A> & B".to_string(),
+            summary: Some("Worth reading".to_string()),
+            published: Utc::now(),
+            updated: Utc::now(),
+            categories: vec!["machine-learning".to_string(), "ml".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            message(&item),
+            "<b><a href=\"https://example.com/a?x=1&amp;y=2\">&lt;A&gt; &amp; B</a></b>\n\nWorth reading\n\n#machine_learning #ml"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+// Saving items to a Wallabag instance (https://doc.wallabag.org/developer/api/)
+// to read them later with the full text it extracts
+
+use std::cell::OnceCell;
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::sinks::Sink;
+use crate::{NewsFeed, NewsItem};
+
+// API client and account to save with, from the [wallabag] table of the
+// config. Clients are created under "API clients management" in Wallabag.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WallabagConfig {
+    pub url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+pub struct WallabagSink<'a> {
+    pub config: &'a WallabagConfig,
+    client: Client,
+    // Fetched on first use and kept for the run
+    token: OnceCell<String>,
+}
+
+impl<'a> WallabagSink<'a> {
+    pub fn new(config: &'a WallabagConfig) -> Self {
+        WallabagSink { config, client: Client::new(), token: OnceCell::new() }
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}/{}", self.config.url.trim_end_matches('/'), endpoint)
+    }
+
+    fn token(&self) -> Result<&str> {
+        if let Some(token) = self.token.get() {
+            return Ok(token);
+        }
+
+        let params = [
+            ("grant_type", "password"),
+            ("client_id", self.config.client_id.as_str()),
+            ("client_secret", self.config.client_secret.as_str()),
+            ("username", self.config.username.as_str()),
+            ("password", self.config.password.as_str()),
+        ];
+        let response = self.client.post(self.url("oauth/v2/token")).form(&params).send()
+            .with_context(|| format!("Unable to log in to {}", self.config.url))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Logging in to {} failed with {}", self.config.url, response.status()));
+        }
+        let token: TokenResponse = response.json()?;
+        Ok(self.token.get_or_init(|| token.access_token))
+    }
+
+    fn save(&self, item: &NewsItem) -> Result<()> {
+        let response = self.client.post(self.url("api/entries.json"))
+            .bearer_auth(self.token()?)
+            .json(&json!({ "url": item.link, "title": item.title, "tags": item.categories.join(",") }))
+            .send()
+            .with_context(|| format!("Unable to save {} to Wallabag", item.link))?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Wallabag rejected {}: {}", item.link, response.status()));
+        }
+        Ok(())
+    }
+}
+
+impl Sink for WallabagSink<'_> {
+    fn emit(&self, feed: &NewsFeed) -> Result<()> {
+        feed.items.iter().try_for_each(|item| self.save(item))
+    }
+}
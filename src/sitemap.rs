@@ -0,0 +1,64 @@
+// Sitemaps (https://www.sitemaps.org/protocol.html) listing the links of a
+// feed's items, so that search engines pick up a published page of them
+
+use chrono::SecondsFormat;
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+
+use crate::NewsFeed;
+
+const NAMESPACE: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+// Items are listed with when they were last updated. Sitemaps only take full
+// URLs, so other links are left out.
+pub fn to_sitemap_string(feed: &NewsFeed) -> String {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None))).unwrap();
+
+    writer.create_element("urlset")
+        .with_attribute(("xmlns", NAMESPACE))
+        .write_inner_content(|writer| {
+            let items = feed.items.iter().filter(|item| item.link.starts_with("http://") || item.link.starts_with("https://"));
+            for item in items {
+                writer.create_element("url").write_inner_content(|writer| {
+                    writer.create_element("loc").write_text_content(BytesText::new(&item.link))?;
+                    writer.create_element("lastmod").write_text_content(BytesText::new(&item.updated.to_rfc3339_opts(SecondsFormat::Secs, true)))?;
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    String::from_utf8(writer.into_inner()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use crate::NewsItem;
+
+    #[test]
+    fn full_links_are_listed() {
+        let updated = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let item = |link: &str| NewsItem {
+            id: link.to_string(),
+            link: link.to_string(),
+            title: link.to_string(),
+            published: updated,
+            updated,
+            ..Default::default()
+        };
+        let feed = NewsFeed {
+            id: "links".to_string(),
+            items: vec![item("https://example.com/?a=1&b=2"), item("/relative")],
+            ..Default::default()
+        };
+
+        let sitemap = to_sitemap_string(&feed);
+        assert!(sitemap.contains("<loc>https://example.com/?a=1&amp;b=2</loc>"));
+        assert!(sitemap.contains("<lastmod>2024-06-01T12:00:00Z</lastmod>"));
+        assert!(!sitemap.contains("/relative"));
+    }
+}
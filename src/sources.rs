@@ -1,2 +1,83 @@
+use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::config::{Config, FeedConfig, Profile};
+use crate::NewsItem;
+
 pub mod pile;
 pub mod hf;
+pub mod exec;
+pub mod markdown;
+pub mod obsidian;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+
+// Anything that can produce news items for a feed. Fetching is async so that
+// feeds reading from different sources can be fetched concurrently.
+#[async_trait]
+pub trait Source: Send + Sync {
+    async fn fetch(&self) -> Result<Vec<NewsItem>>;
+
+    // Sources with the same key return the same items, so feeds sharing one
+    // are fetched only once per run. None if the source can't be shared.
+    fn shared_key(&self) -> Option<String> {
+        None
+    }
+
+    // Problems the last fetch ran into without failing, like files that
+    // could not be read
+    fn issues(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// What sources get to look at while constructing themselves
+pub struct SourceContext<'a> {
+    pub config: &'a Config,
+    pub profile: &'a Profile,
+    pub offline: bool,
+}
+
+type Constructor = Box<dyn Fn(&SourceContext, &FeedConfig) -> Result<Box<dyn Source>>>;
+
+// Sources keyed by the name used in the `source` field of feed definitions
+pub struct Registry {
+    constructors: HashMap<String, Constructor>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry { constructors: HashMap::new() };
+        registry.register("pile", |ctx, feed| Ok(Box::new(pile::PileSource::new(ctx, feed)?)));
+        registry.register("pile-daily", |ctx, feed| Ok(Box::new(pile::daily::PileDailySource::new(ctx, feed)?)));
+        registry.register("hf", |ctx, feed| Ok(Box::new(hf::HfSource::new(ctx, feed)?)));
+        registry.register("exec", |ctx, feed| Ok(Box::new(exec::ExecSource::new(ctx, feed)?)));
+        registry.register("markdown", |ctx, feed| Ok(Box::new(markdown::MarkdownSource::new(ctx, feed)?)));
+        registry.register("obsidian", |ctx, feed| Ok(Box::new(obsidian::ObsidianSource::new(ctx, feed)?)));
+        registry
+    }
+}
+
+impl Registry {
+    pub fn register<F>(&mut self, name: &str, constructor: F)
+    where
+        F: Fn(&SourceContext, &FeedConfig) -> Result<Box<dyn Source>> + 'static,
+    {
+        self.constructors.insert(name.to_string(), Box::new(constructor));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.constructors.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    // Construct the source the feed reads from
+    pub fn build(&self, ctx: &SourceContext, feed: &FeedConfig) -> Result<Box<dyn Source>> {
+        let constructor = self.constructors
+            .get(&feed.source)
+            .ok_or_else(|| anyhow!("Unknown source {} for feed {}, available: {}", feed.source, feed.id, self.names().join(", ")))?;
+        constructor(ctx, feed)
+    }
+}
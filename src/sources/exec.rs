@@ -0,0 +1,121 @@
+use std::process::Command;
+use chrono::{DateTime, Utc};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::config::FeedConfig;
+use crate::sources::{Source, SourceContext};
+use crate::{Enclosure, NewsItem, ToNewsItem};
+
+// An item as printed by the command, one JSON object per line
+#[derive(Debug, Deserialize)]
+pub struct ExecItem {
+    id: String,
+    link: String,
+    title: String,
+    summary: Option<String>,
+    // Defaults to the time of the run
+    published: Option<DateTime<Utc>>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    enclosures: Vec<Enclosure>,
+    // Language tag like en or de
+    language: Option<String>,
+}
+
+impl ToNewsItem for ExecItem {
+    fn to_newsitem(&self) -> NewsItem {
+        let published = self.published.unwrap_or_else(Utc::now);
+        NewsItem {
+            id: NewsItem::stable_id("exec", &self.id),
+            link: self.link.clone(),
+            title: self.title.clone(),
+            summary: self.summary.clone(),
+            content: None,
+            content_html: false,
+            published,
+            updated: published,
+            authors: Vec::new(),
+            categories: self.tags.clone(),
+            enclosures: self.enclosures.clone(),
+            related: Vec::new(),
+            language: self.language.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+// Items from an arbitrary user command that prints newline delimited JSON on
+// stdout. Useful for one off personal sources that don't belong here.
+pub struct ExecSource {
+    command: Vec<String>,
+}
+
+impl ExecSource {
+    pub fn new(_ctx: &SourceContext, feed: &FeedConfig) -> Result<Self> {
+        if feed.command.is_empty() {
+            return Err(anyhow!("Feed {} needs a command for the exec source", feed.id));
+        }
+
+        Ok(ExecSource { command: feed.command.clone() })
+    }
+}
+
+#[async_trait]
+impl Source for ExecSource {
+    async fn fetch(&self) -> Result<Vec<NewsItem>> {
+        let command = self.command.clone();
+        let stdout = tokio::task::spawn_blocking(move || run_command(&command)).await??;
+        Ok(parse_items(&stdout)?.iter().map(|it| it.to_newsitem()).collect())
+    }
+
+    fn shared_key(&self) -> Option<String> {
+        Some(format!("exec:{:?}", self.command))
+    }
+}
+
+fn run_command(command: &[String]) -> Result<String> {
+    let output = Command::new(&command[0])
+        .args(&command[1..])
+        .output()
+        .with_context(|| format!("Unable to run {}", command[0]))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim() {
+            "" => anyhow!("{} failed with {}", command[0], output.status),
+            stderr => anyhow!("{} failed with {}: {}", command[0], output.status, stderr),
+        });
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+pub fn parse_items(stdout: &str) -> Result<Vec<ExecItem>> {
+    stdout.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).with_context(|| format!("Invalid item on line {}", i + 1)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_are_parsed() {
+        let stdout = r#"{"id": "a", "link": "https://example.com/a", "title": "A", "tags": ["bank"]}
+
+{"id": "b", "link": "https://example.com/b", "title": "B", "summary": "Bee", "published": "2024-01-02T10:00:00Z"}
+"#;
+        let items = parse_items(stdout).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].tags, vec!["bank"]);
+        assert_eq!(items[1].to_newsitem().published.to_rfc3339(), "2024-01-02T10:00:00+00:00");
+
+        assert!(parse_items("{\"id\": \"a\"}").is_err());
+    }
+}
@@ -1,12 +1,19 @@
-use chrono::{DateTime, Datelike, Utc};
-use anyhow::Result;
-use reqwest::blocking::Client;
-use reqwest::header;
-use scraper::{Html, Selector};
+use std::collections::HashMap;
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use atom_syndication as atom;
+use futures::future::try_join_all;
+use log::warn;
+use serde::Deserialize;
 
-use crate::{NewsItem, ToNewsItem};
+use crate::config::FeedConfig;
+use crate::http::Fetcher;
+use crate::sources::{Source, SourceContext};
+use crate::{Enclosure, NewsItem, ToNewsItem};
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct Paper {
     id: String,
     title: String,
@@ -19,7 +26,7 @@ pub struct Paper {
     n_comments: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Week {
     year: usize,
     week: usize,
@@ -32,68 +39,257 @@ impl ToNewsItem for Paper {
             link: self.link.clone(),
             title: self.title.clone(),
             summary: if self.description.is_empty() { None } else { Some(self.description.clone()) },
+            content: None,
+            content_html: false,
             published: self.added,
             updated: self.added,
             authors: Vec::new(),
             categories: self.tags.clone(),
+            enclosures: self.arxiv.iter()
+                .map(|arxiv_id| Enclosure { url: format!("https://arxiv.org/pdf/{}", arxiv_id), mime_type: "application/pdf".to_string(), length: None })
+                .collect(),
+            related: Vec::new(),
+            language: None,
+            ..Default::default()
         }
     }
 }
 
+// Papers of the current week, or of the weeks asked for, from Huggingface
+// Daily Papers
+pub struct HfSource {
+    fetcher: Fetcher,
+    weeks: Vec<Week>,
+    skip_abstracts: bool,
+    min_votes: usize,
+}
+
+impl HfSource {
+    pub fn new(ctx: &SourceContext, feed: &FeedConfig) -> Result<Self> {
+        Ok(HfSource {
+            fetcher: Fetcher::new(ctx.offline, &ctx.config.http_options("hf"))?,
+            weeks: parse_weeks(&feed.weeks)?,
+            skip_abstracts: feed.skip_abstracts,
+            min_votes: feed.min_votes.unwrap_or(0),
+        })
+    }
+}
+
+#[async_trait]
+impl Source for HfSource {
+    async fn fetch(&self) -> Result<Vec<NewsItem>> {
+        let weeks = if self.weeks.is_empty() { vec![get_current_week()] } else { self.weeks.clone() };
+        // Weeks are fetched all at once, as a backfill can be many of them
+        let mut papers: Vec<Paper> = try_join_all(weeks.into_iter().map(|week| read_weekly_papers(&self.fetcher, week))).await?
+            .into_iter()
+            .flatten()
+            .collect();
+        papers.retain(|paper| paper.votes >= self.min_votes);
+        if !self.skip_abstracts {
+            // Papers are still worth listing without their abstracts
+            if let Err(err) = add_abstracts(&self.fetcher, &mut papers).await {
+                warn!("Unable to read abstracts of papers: {}", err);
+            }
+        }
+        Ok(papers.iter().map(|p| p.to_newsitem()).collect())
+    }
+
+    fn shared_key(&self) -> Option<String> {
+        Some(format!("hf:{:?}:{}:{}", self.weeks, self.skip_abstracts, self.min_votes))
+    }
+}
+
 pub fn get_current_week() -> Week {
     let now = chrono::Local::now();
-    let year = now.year() as usize;
+    // The ISO year, which differs from the calendar one around new year
+    let year = now.iso_week().year() as usize;
     let week = now.iso_week().week() as usize;
 
     Week { year, week }
 }
 
-pub fn read_weekly_papers(week: Week) -> Result<Vec<Paper>> {
-    let url = format!("https://huggingface.co/papers/week/{}-W{}", week.year, week.week);
-
-    let mut headers = header::HeaderMap::new();
-    headers.insert("Accept", header::HeaderValue::from_static("text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7"));
-    headers.insert("Accept-Language", header::HeaderValue::from_static("en-US,en;q=0.9"));
-    headers.insert("Cache-Control", header::HeaderValue::from_static("no-cache"));
-    headers.insert("Pragma", header::HeaderValue::from_static("no-cache"));
-
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36")
-        .default_headers(headers)
-        .build()?;
-
-    let response = client.get(&url).send()?;
-    let body = response.text()?;
-    let document = Html::parse_document(&body);
-
-    let selector = Selector::parse("div.\\[content-visibility\\:auto\\] > article:nth-child(1) > div:nth-child(3) > div:nth-child(1)").unwrap();
-    let vote_selector = &Selector::parse("div:nth-child(1)").unwrap();
-    let title_selector = &Selector::parse("div:nth-child(2) > h3:nth-child(1) > a:nth-child(1)").unwrap();
-
-    let mut papers = Vec::new();
-
-    for element in document.select(&selector) {
-        let vote_element = element.select(&vote_selector).next().unwrap();
-        let votes: usize = vote_element.text().collect::<String>().trim().parse().unwrap();
-
-        let title_element = element.select(&title_selector).next().unwrap();
-        let title = title_element.text().collect::<String>().trim().to_string();
-        let rel_link = title_element.attr("href").unwrap().to_string();
-
-        let paper = Paper {
-            id: rel_link.clone(),
-            title,
-            link: format!("https://huggingface.co{}", rel_link),
-            description: "".to_string(),
-            tags: vec![],
-            arxiv: None,
-            added: Utc::now(),
-            votes,
-            n_comments: 0,
+// An ISO week like 2024-W37
+fn parse_week(text: &str) -> Result<Week> {
+    let (year, week) = text.trim().split_once("-W")
+        .and_then(|(year, week)| Some((year.parse::<i32>().ok()?, week.parse::<u32>().ok()?)))
+        .ok_or_else(|| anyhow!("Bad week {}, expected one like 2024-W37", text))?;
+    // Not every year has a 53rd week
+    NaiveDate::from_isoywd_opt(year, week, Weekday::Mon)
+        .ok_or_else(|| anyhow!("No week {} in {}", week, year))?;
+    Ok(Week { year: year as usize, week: week as usize })
+}
+
+// Weeks like 2024-W37, or ranges of them like 2024-W30..2024-W37
+pub fn parse_weeks(specs: &[String]) -> Result<Vec<Week>> {
+    let mut weeks = Vec::new();
+    for spec in specs {
+        let Some((first, last)) = spec.split_once("..") else {
+            weeks.push(parse_week(spec)?);
+            continue;
         };
+        let (first, last) = (parse_week(first)?, parse_week(last)?);
+        let mut monday = NaiveDate::from_isoywd_opt(first.year as i32, first.week as u32, Weekday::Mon).unwrap();
+        let mut week = first;
+        if (last.year, last.week) < (first.year, first.week) {
+            return Err(anyhow!("Week range {} ends before it starts", spec));
+        }
+        while week != last {
+            weeks.push(week);
+            monday += chrono::Duration::weeks(1);
+            week = Week { year: monday.iso_week().year() as usize, week: monday.iso_week().week() as usize };
+        }
+        weeks.push(last);
+    }
+    Ok(weeks)
+}
+
+// Entries of the daily papers API, of which only the paper is needed
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DailyPaper {
+    paper: ApiPaper,
+    #[serde(default)]
+    num_comments: usize,
+}
 
-        papers.push(paper);
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiPaper {
+    // arXiv id, which paper pages are at
+    id: String,
+    title: String,
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    upvotes: usize,
+    published_at: Option<DateTime<Utc>>,
+}
+
+pub async fn read_weekly_papers(fetcher: &Fetcher, week: Week) -> Result<Vec<Paper>> {
+    let url = format!("https://huggingface.co/api/daily_papers?week={}-W{:02}&limit=100", week.year, week.week);
+    let body = fetcher.get_text(&url).await?;
+    read_papers(&body)
+}
+
+fn read_papers(body: &str) -> Result<Vec<Paper>> {
+    let daily_papers: Vec<DailyPaper> = serde_json::from_str(body).context("Unexpected response from the papers API")?;
+    Ok(daily_papers.into_iter()
+        .map(|daily_paper| {
+            let paper = daily_paper.paper;
+            let rel_link = format!("/papers/{}", paper.id);
+            Paper {
+                id: NewsItem::stable_id("hf", &rel_link),
+                title: paper.title.split_whitespace().collect::<Vec<_>>().join(" "),
+                link: format!("https://huggingface.co{}", rel_link),
+                description: paper.summary.split_whitespace().collect::<Vec<_>>().join(" "),
+                tags: vec![],
+                arxiv: Some(paper.id),
+                added: paper.published_at.unwrap_or_else(Utc::now),
+                votes: paper.upvotes,
+                n_comments: daily_paper.num_comments,
+            }
+        })
+        .collect())
+}
+
+// Fill in missing descriptions with the abstracts of papers on arXiv, all in
+// one request to its API
+pub async fn add_abstracts(fetcher: &Fetcher, papers: &mut [Paper]) -> Result<()> {
+    let arxiv_ids: Vec<&str> = papers.iter()
+        .filter(|paper| paper.description.is_empty())
+        .filter_map(|paper| paper.arxiv.as_deref())
+        .collect();
+    if arxiv_ids.is_empty() {
+        return Ok(());
+    }
+
+    let url = format!("https://export.arxiv.org/api/query?id_list={}&max_results={}", arxiv_ids.join(","), arxiv_ids.len());
+    let abstracts = read_abstracts(&fetcher.get_text(&url).await?)?;
+    for paper in papers.iter_mut() {
+        if !paper.description.is_empty() {
+            continue;
+        }
+        if let Some(summary) = paper.arxiv.as_ref().and_then(|arxiv_id| abstracts.get(arxiv_id)) {
+            paper.description = summary.clone();
+        }
     }
+    Ok(())
+}
+
+// Abstracts by arXiv id, without the version, from an API response
+fn read_abstracts(body: &str) -> Result<HashMap<String, String>> {
+    let feed = atom::Feed::read_from(body.as_bytes())?;
+    Ok(feed.entries().iter()
+        .filter_map(|entry| {
+            // Entry ids are like http://arxiv.org/abs/2401.12345v2
+            let versioned = entry.id().rsplit_once("/abs/")?.1;
+            let arxiv_id = match versioned.rsplit_once('v') {
+                Some((arxiv_id, version)) if version.chars().all(|c| c.is_ascii_digit()) => arxiv_id,
+                _ => versioned,
+            };
+            let summary = entry.summary()?.value.split_whitespace().collect::<Vec<_>>().join(" ");
+            Some((arxiv_id.to_string(), summary))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn papers_are_read() {
+        let body = r#"[
+  {"paper": {"id": "2409.01234", "title": "A Paper\n  on Things", "summary": "We show\nthings.", "upvotes": 42, "publishedAt": "2024-09-09T10:00:00.000Z", "authors": []}, "numComments": 3},
+  {"paper": {"id": "2409.05678", "title": "Another"}}
+]"#;
+        let papers = read_papers(body).unwrap();
+        assert_eq!(papers.len(), 2);
+        assert_eq!(papers[0].title, "A Paper on Things");
+        assert_eq!(papers[0].link, "https://huggingface.co/papers/2409.01234");
+        assert_eq!(papers[0].id, NewsItem::stable_id("hf", "/papers/2409.01234"));
+        assert_eq!(papers[0].description, "We show things.");
+        assert_eq!(papers[0].votes, 42);
+        assert_eq!(papers[0].n_comments, 3);
+        assert_eq!(papers[0].added.to_rfc3339(), "2024-09-09T10:00:00+00:00");
+        assert!(papers[1].description.is_empty());
 
-    Ok(papers)
+        assert!(read_papers("<html></html>").is_err());
+    }
+
+    #[test]
+    fn weeks_are_parsed() {
+        let weeks = parse_weeks(&["2024-W37".to_string(), "2020-W52..2021-W02".to_string()]).unwrap();
+        assert_eq!(weeks, vec![
+            Week { year: 2024, week: 37 },
+            Week { year: 2020, week: 52 },
+            Week { year: 2020, week: 53 },
+            Week { year: 2021, week: 1 },
+            Week { year: 2021, week: 2 },
+        ]);
+        assert!(parse_weeks(&["2021-W53".to_string()]).is_err());
+        assert!(parse_weeks(&["2024-37".to_string()]).is_err());
+        assert!(parse_weeks(&["2024-W37..2024-W30".to_string()]).is_err());
+    }
+
+    #[test]
+    fn abstracts_are_read() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>arXiv Query</title>
+  <id>http://arxiv.org/api/query</id>
+  <updated>2024-09-10T00:00:00-04:00</updated>
+  <entry>
+    <id>http://arxiv.org/abs/1706.03762v7</id>
+    <updated>2023-08-02T00:41:18Z</updated>
+    <title>Attention Is All You Need</title>
+    <summary>  The dominant sequence transduction models
+are based on complex recurrent networks.
+</summary>
+  </entry>
+</feed>"#;
+        let abstracts = read_abstracts(body).unwrap();
+        assert_eq!(abstracts.len(), 1);
+        assert_eq!(abstracts["1706.03762"], "The dominant sequence transduction models are based on complex recurrent networks.");
+    }
 }
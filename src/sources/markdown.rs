@@ -0,0 +1,245 @@
+// Bookmarks as markdown files with YAML frontmatter, like the posts in the
+// links/ folder of a static site, independent of any note taking tool:
+//
+//   ---
+//   title: A post
+//   url: https://example.com/post
+//   tags: [unsorted, ml]
+//   date: 2024-01-02
+//   ---
+//
+// The text after the frontmatter is the summary. Files without a url, and
+// drafts, are not bookmarks.
+
+use std::sync::{Arc, Mutex};
+use std::{fs, path};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use crate::config::FeedConfig;
+use crate::sources::pile::NotesTimezone;
+use crate::sources::{Source, SourceContext};
+use crate::{NewsItem, ToNewsItem};
+
+#[derive(Debug, Default, Deserialize)]
+struct Frontmatter {
+    title: Option<String>,
+    url: Option<String>,
+    // Where Obsidian's Web Clipper puts the link
+    source: Option<String>,
+    #[serde(default)]
+    tags: Tags,
+    created: Option<String>,
+    date: Option<String>,
+    // Posts of static sites that aren't out yet
+    #[serde(default)]
+    draft: bool,
+}
+
+// A list of tags, or a line of them separated by commas or spaces
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Tags {
+    List(Vec<String>),
+    Line(String),
+}
+
+impl Default for Tags {
+    fn default() -> Self {
+        Tags::List(Vec::new())
+    }
+}
+
+impl Tags {
+    fn to_vec(&self) -> Vec<String> {
+        let tags: Vec<&str> = match self {
+            Tags::List(tags) => tags.iter().map(|tag| tag.as_str()).collect(),
+            Tags::Line(line) => line.split(|c: char| c == ',' || c.is_whitespace()).collect(),
+        };
+        tags.into_iter()
+            .map(|tag| tag.trim().trim_start_matches('#'))
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| tag.to_string())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MarkdownBookmark {
+    // Name of the source and the path of the file in the directory
+    source: &'static str,
+    id: String,
+    link: String,
+    title: String,
+    tags: Vec<String>,
+    created: DateTime<Utc>,
+    content: Option<String>,
+}
+
+impl ToNewsItem for MarkdownBookmark {
+    fn to_newsitem(&self) -> NewsItem {
+        NewsItem {
+            id: NewsItem::stable_id(self.source, &self.id),
+            link: self.link.clone(),
+            title: self.title.clone(),
+            summary: self.content.clone(),
+            content: None,
+            content_html: false,
+            published: self.created,
+            updated: self.created,
+            authors: Vec::new(),
+            categories: self.tags.clone(),
+            enclosures: Vec::new(),
+            related: Vec::new(),
+            language: None,
+            ..Default::default()
+        }
+    }
+}
+
+// The YAML between the --- lines at the top of a note, and the rest
+pub fn split_frontmatter(markdown: &str) -> Option<(&str, &str)> {
+    let rest = markdown.strip_prefix("---")?.trim_start_matches([' ', '\t']);
+    let rest = rest.strip_prefix("\r\n").or(rest.strip_prefix('\n'))?;
+    if let Some(body) = rest.strip_prefix("---") {
+        return Some(("", body));
+    }
+    let end = rest.find("\n---")?;
+    let body = rest[end + 4..].trim_start_matches('-');
+    Some((&rest[..end], body))
+}
+
+// Dates as written by hand or by Obsidian, with or without a time and an
+// offset. Times without an offset are in the timezone of the notes.
+fn parse_date(date: &str, tz: NotesTimezone) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(date) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"].iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok().map(|day| day.and_time(chrono::NaiveTime::MIN)))
+        .and_then(|dt| tz.to_utc(dt))
+}
+
+// A bookmark out of a file, None if the file isn't one
+fn read_bookmark(source: &'static str, dir: &path::Path, file_path: &path::Path, tz: NotesTimezone) -> Result<Option<MarkdownBookmark>> {
+    let markdown = fs::read_to_string(file_path)?;
+    let Some((yaml, body)) = split_frontmatter(&markdown) else {
+        return Ok(None);
+    };
+    let frontmatter: Frontmatter = if yaml.trim().is_empty() {
+        Frontmatter::default()
+    } else {
+        serde_yaml::from_str(yaml).map_err(|err| anyhow!("Bad frontmatter: {}", err))?
+    };
+    let Some(link) = frontmatter.url.or(frontmatter.source).filter(|_| !frontmatter.draft) else {
+        return Ok(None);
+    };
+
+    let body = body.trim();
+    Ok(Some(MarkdownBookmark {
+        source,
+        id: file_path.strip_prefix(dir).unwrap_or(file_path).to_string_lossy().to_string(),
+        link,
+        title: frontmatter.title
+            .or_else(|| file_path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+            .unwrap_or_default(),
+        tags: frontmatter.tags.to_vec(),
+        created: frontmatter.created.or(frontmatter.date).as_deref().and_then(|created| parse_date(created, tz))
+            .or_else(|| fs::metadata(file_path).and_then(|metadata| metadata.modified()).ok().map(DateTime::<Utc>::from))
+            .unwrap_or_else(Utc::now),
+        content: if body.is_empty() { None } else { Some(body.to_string()) },
+    }))
+}
+
+// Bookmarks of the directory and its subdirectories, and the files that could
+// not be read. Hidden directories, like Obsidian's .obsidian and .trash, are
+// skipped. Ids of the items are made with the source name.
+pub fn read_dir(source: &'static str, dir: &path::Path, tz: NotesTimezone) -> (Vec<MarkdownBookmark>, Vec<String>) {
+    let mut file_paths: Vec<path::PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|file_path| file_path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    file_paths.sort();
+
+    let mut bookmarks = Vec::new();
+    let mut issues = Vec::new();
+    for file_path in file_paths {
+        match read_bookmark(source, dir, &file_path, tz) {
+            Ok(Some(bookmark)) => bookmarks.push(bookmark),
+            Ok(None) => (),
+            Err(err) => issues.push(format!("{}: {}", file_path.display(), err)),
+        }
+    }
+    (bookmarks, issues)
+}
+
+#[derive(Clone)]
+pub struct MarkdownSource {
+    dir: path::PathBuf,
+    tz: NotesTimezone,
+    // Files the last fetch could not read
+    issues: Arc<Mutex<Vec<String>>>,
+}
+
+impl MarkdownSource {
+    pub fn new(ctx: &SourceContext, feed: &FeedConfig) -> Result<Self> {
+        Ok(MarkdownSource {
+            dir: feed.markdown_dir.clone()
+                .ok_or_else(|| anyhow!("Feed {} needs a markdown_dir for the markdown source", feed.id))?,
+            tz: NotesTimezone::new(ctx.profile.timezone.as_deref())?,
+            issues: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+}
+
+#[async_trait]
+impl Source for MarkdownSource {
+    async fn fetch(&self) -> Result<Vec<NewsItem>> {
+        let source = self.clone();
+        let bookmarks = tokio::task::spawn_blocking(move || {
+            let (bookmarks, issues) = read_dir("markdown", &source.dir, source.tz);
+            *source.issues.lock().unwrap() = issues;
+            bookmarks
+        }).await?;
+        Ok(bookmarks.iter().map(|bookmark| bookmark.to_newsitem()).collect())
+    }
+
+    fn issues(&self) -> Vec<String> {
+        self.issues.lock().unwrap().clone()
+    }
+
+    fn shared_key(&self) -> Option<String> {
+        Some(format!("markdown:{:?}", self.dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn posts_with_links_are_bookmarks() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("2024")).unwrap();
+        fs::write(dir.path().join("2024/a.md"), "---\ntitle: A post\nurl: https://example.com/a\ntags: [ml]\ndate: 2024-01-02 10:30\n---\n\nWorth a read.\n").unwrap();
+        fs::write(dir.path().join("draft.md"), "---\nurl: https://example.com/draft\ndraft: true\n---\n").unwrap();
+        fs::write(dir.path().join("index.md"), "# Links\n").unwrap();
+
+        let (bookmarks, issues) = read_dir("markdown", dir.path(), NotesTimezone::Named(chrono_tz::UTC));
+        assert!(issues.is_empty());
+        assert_eq!(bookmarks.len(), 1);
+        let item = bookmarks[0].to_newsitem();
+        assert_eq!((item.title.as_str(), item.link.as_str()), ("A post", "https://example.com/a"));
+        assert_eq!(item.categories, vec!["ml"]);
+        assert_eq!(item.published.to_rfc3339(), "2024-01-02T10:30:00+00:00");
+        assert_eq!(item.summary.as_deref(), Some("Worth a read."));
+    }
+}
@@ -0,0 +1,97 @@
+// Web clippings kept in an Obsidian vault, read as markdown bookmarks. Notes
+// with a link in their YAML frontmatter are bookmarks, like
+//
+//   ---
+//   url: https://example.com/post
+//   tags: [unsorted, ml]
+//   created: 2024-01-02T10:30
+//   ---
+//
+// Obsidian's Web Clipper puts the link in `source` instead, which works too.
+// Tags are the same as in notes, so the unread, project, and recommended
+// filters pick clippings the way they pick notes.
+
+use std::sync::{Arc, Mutex};
+use std::path;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::config::FeedConfig;
+use crate::sources::markdown;
+use crate::sources::pile::NotesTimezone;
+use crate::sources::{Source, SourceContext};
+use crate::{NewsItem, ToNewsItem};
+
+#[derive(Clone)]
+pub struct ObsidianSource {
+    vault: path::PathBuf,
+    tz: NotesTimezone,
+    // Notes the last fetch could not read
+    issues: Arc<Mutex<Vec<String>>>,
+}
+
+impl ObsidianSource {
+    pub fn new(ctx: &SourceContext, _feed: &FeedConfig) -> Result<Self> {
+        Ok(ObsidianSource {
+            vault: ctx.profile.obsidian_vault_path.clone()
+                .ok_or_else(|| anyhow!("Need obsidian_vault_path to be set for the obsidian source!"))?,
+            tz: NotesTimezone::new(ctx.profile.timezone.as_deref())?,
+            issues: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+}
+
+#[async_trait]
+impl Source for ObsidianSource {
+    async fn fetch(&self) -> Result<Vec<NewsItem>> {
+        let source = self.clone();
+        let clippings = tokio::task::spawn_blocking(move || {
+            let (clippings, issues) = markdown::read_dir("obsidian", &source.vault, source.tz);
+            *source.issues.lock().unwrap() = issues;
+            clippings
+        }).await?;
+        Ok(clippings.iter().map(|clipping| clipping.to_newsitem()).collect())
+    }
+
+    fn issues(&self) -> Vec<String> {
+        self.issues.lock().unwrap().clone()
+    }
+
+    fn shared_key(&self) -> Option<String> {
+        Some(format!("obsidian:{:?}", self.vault))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn clippings_are_read_from_the_vault() {
+        let vault = tempfile::tempdir().unwrap();
+        fs::create_dir_all(vault.path().join("Clippings")).unwrap();
+        fs::create_dir_all(vault.path().join(".obsidian")).unwrap();
+        fs::write(vault.path().join("Clippings/A post.md"), "---\nsource: https://example.com/a\ntags:\n  - clippings\n  - \"#unsorted\"\ncreated: 2024-01-02\n---\nWorth a read.\n").unwrap();
+        fs::write(vault.path().join("Clippings/B.md"), "---\ntitle: B\nurl: https://example.com/b\ntags: ml, project\ncreated: 2024-01-03T10:30:00+05:30\n---\n").unwrap();
+        fs::write(vault.path().join("Thoughts.md"), "---\ntags: [journal]\n---\nNot a clipping.\n").unwrap();
+        fs::write(vault.path().join("Broken.md"), "---\nurl: [unclosed\n---\n").unwrap();
+        fs::write(vault.path().join(".obsidian/Hidden.md"), "---\nurl: https://example.com/hidden\n---\n").unwrap();
+
+        let (clippings, issues) = markdown::read_dir("obsidian", vault.path(), NotesTimezone::Named(chrono_tz::UTC));
+        assert_eq!(clippings.len(), 2);
+        assert_eq!(issues.len(), 1);
+
+        let items: Vec<NewsItem> = clippings.iter().map(|clipping| clipping.to_newsitem()).collect();
+        assert_eq!(items[0].title, "A post");
+        assert_eq!(items[0].link, "https://example.com/a");
+        assert_eq!(items[0].categories, vec!["clippings", "unsorted"]);
+        assert_eq!(items[0].published.to_rfc3339(), "2024-01-02T00:00:00+00:00");
+        assert_eq!(items[0].summary.as_deref(), Some("Worth a read."));
+
+        assert_eq!(items[1].title, "B");
+        assert_eq!(items[1].categories, vec!["ml", "project"]);
+        assert_eq!(items[1].published.to_rfc3339(), "2024-01-03T05:00:00+00:00");
+        assert!(items[1].summary.is_none());
+    }
+}
@@ -1,140 +1,421 @@
+use std::collections::HashMap;
 use std::fs;
-use std::{path, fs::File};
-use std::io::{self, BufRead};
+use std::path;
+use std::sync::{Arc, Mutex};
 use regex::Regex;
 use anyhow::{Result, anyhow, Context};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use chrono_tz::Tz;
 use once_cell::sync::Lazy;
-use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use async_trait::async_trait;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
 
-use crate::{ToNewsItem, NewsItem};
+use crate::attachments;
+use crate::config::{FeedConfig, Profile};
+use crate::filters::{BookmarkConfig, Filter};
+use crate::sources::{Source, SourceContext};
+use crate::{utils, ToNewsItem, NewsAuthor, NewsItem};
+use cache::{FileStamp, NotesCache};
+use document::{split_colon_tags, Document, Section};
 
-static ID_REGEX: Lazy<Regex> = Lazy::new(|| { Regex::new(r"(?i)^:id:\s*(.*)").unwrap() });
-static REF_REGEX: Lazy<Regex> = Lazy::new(|| { Regex::new(r"(?i)^:ROAM_REFS:\s*(.*)").unwrap() });
-static TAGS_REGEX: Lazy<Regex> = Lazy::new(|| { Regex::new(r"(?i)^\#\+TAGS:\s*(.*)").unwrap() });
-static TITLE_REGEX: Lazy<Regex> = Lazy::new(|| { Regex::new(r"(?i)^\#\+TITLE:\s*(.*)").unwrap() });
+mod cache;
+pub mod daily;
+mod document;
+
+// Timezone the times in notes are in, since org timestamps and file names
+// don't say which one they are in
+#[derive(Debug, Clone, Copy)]
+pub enum NotesTimezone {
+    Named(Tz),
+    Local,
+}
+
+impl NotesTimezone {
+    // The configured timezone, else the one in $TZ, else the system's
+    pub fn new(timezone: Option<&str>) -> Result<Self> {
+        if let Some(timezone) = timezone {
+            let tz = timezone.parse::<Tz>().map_err(|_| anyhow!("Unknown timezone {}", timezone))?;
+            return Ok(NotesTimezone::Named(tz));
+        }
+
+        Ok(std::env::var("TZ").ok()
+            .and_then(|tz| tz.trim_start_matches(':').parse::<Tz>().ok())
+            .map(NotesTimezone::Named)
+            .unwrap_or(NotesTimezone::Local))
+    }
+
+    pub fn to_utc(self, dt: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            NotesTimezone::Named(tz) => dt.and_local_timezone(tz).earliest().map(|dt| dt.to_utc()),
+            NotesTimezone::Local => dt.and_local_timezone(Local).earliest().map(|dt| dt.to_utc()),
+        }
+    }
+}
+
+// Entries of the alist of properties org-roam keeps for nodes, like
+// (("ID" . "a") ("CREATED" . "[2023-05-10 Wed 10:10]"))
+static DB_PROPERTY_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\("([^"]+)" \. "([^"]*)"\)"#).unwrap());
+// The modification time in the plist of file metadata of org-roam v1
+static V1_MTIME_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r":mtime \(([^)]*)\)").unwrap());
+static QUOTED_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#""([^"]*)""#).unwrap());
+
+// Links to other notes, like [[id:...][description]] or [[id:...]]
+static ID_LINK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[\[id:([^\]]+)\](?:\[([^\]]*)\])?\]").unwrap());
+
+// How to read the notes directory
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    // Levels of directories to read, 1 being only the directory itself
+    pub max_depth: Option<usize>,
+    // Files and directories to skip, relative to the notes directory
+    pub exclude: Vec<glob::Pattern>,
+    pub timezone: NotesTimezone,
+    // Where parsed files are cached, not cached if None
+    pub cache_path: Option<path::PathBuf>,
+}
+
+impl ScanOptions {
+    pub fn from_profile(profile: &Profile) -> Result<Self> {
+        let exclude = profile.notes_exclude.iter().flatten()
+            .map(|pattern| glob::Pattern::new(pattern).with_context(|| format!("Invalid exclude pattern {}", pattern)))
+            .collect::<Result<_>>()?;
+        Ok(ScanOptions {
+            max_depth: profile.notes_max_depth,
+            exclude,
+            timezone: NotesTimezone::new(profile.timezone.as_deref())?,
+            cache_path: cache::default_path(),
+        })
+    }
+
+    fn is_excluded(&self, relative_path: &path::Path) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches_path(relative_path))
+    }
+}
+
+// Identifier, then optionally a signature, a title, and keywords
+static DENOTE_NAME_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\d{8}T\d{6})(?:==[^-_.]+)?(?:--([^_.]+))?(?:__([^.]+))?\.").unwrap()
+});
+
+static TIMESTAMP_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"[\[<](\d{4}-\d{2}-\d{2})(?:\s+[^\s\]>\d]+)?(?:\s+(\d{1,2}:\d{2}))?[^\]>]*[\]>]").unwrap()
+});
 
 // An org node from my notes directory. This could be a bookmark (a literature
 // note) or a general note.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrgNode {
     id: String,
     ref_: Option<String>,
     title: String,
     tags: Vec<String>,
     created: DateTime<Utc>,
+    // When what it is about was published, if noted
+    published: Option<DateTime<Utc>>,
     content: Option<String>,
+    // Its org-attach directory, listed when bookmarks are made since files
+    // are attached without the note changing
+    attach_dir: Option<path::PathBuf>,
+    // Who wrote what it is about
+    authors: Vec<String>,
 }
 
 impl OrgNode {
-    fn from_file(file_path: &path::Path) -> Result<Self> {
-        let mut id: Option<String> = None;
-        let mut ref_: Option<String> = None;
-        let mut tags: Vec<String> = Vec::new();
-        let mut title: Option<String> = None;
-
-        let body = fs::read_to_string(file_path)?;
-        let mut header_done = false;
-        let mut content = String::new();
-
-        for line in body.lines() {
-            if let Some(captures) = ID_REGEX.captures(&line) {
-                if let Some(id_str) = captures.get(1) {
-                    id = Some(id_str.as_str().to_string());
-                } else {
-                    return Err(anyhow!("Pattern for id matched but not able to parse value"));
-                }
-            } else if let Some(captures) = REF_REGEX.captures(&line) {
-                if let Some(ref_str) = captures.get(1) {
-                    ref_ = Some(ref_str.as_str().to_string());
-                } else {
-                    return Err(anyhow!("Pattern for ref matched but not able to parse value"));
-                }
-            } else if let Some(captures) = TAGS_REGEX.captures(&line) {
-                if let Some(tags_str) = captures.get(1) {
-                    tags = tags_str.as_str()
-                        .split(",")
-                        .map(|tag| tag.trim().to_string())
-                        .collect();
-                } else {
-                    return Err(anyhow!("Pattern for tags matched but not able to parse value"));
-                }
-            } else if let Some(captures) = TITLE_REGEX.captures(&line) {
-                if let Some(title_str) = captures.get(1) {
-                    title = Some(title_str.as_str().to_string());
-                    // In the way I have been keeping my notes, title is the
-                    // last line of the metadata block.
-                    header_done = true;
-                    continue;
-                } else {
-                    return Err(anyhow!("Pattern for title matched but not able to parse value"));
-                }
+    // The nodes of a file: the file itself if it has an id and title, and
+    // headings with an id in their property drawer. Headings take their text
+    // as title and inherit the tags of the file and the headings above them.
+    // Denote files go by the identifier, title, and keywords in their names
+    // when they don't say otherwise.
+    fn from_file(file_path: &path::Path, tz: NotesTimezone) -> Result<Vec<Self>> {
+        let document = Document::parse(&fs::read_to_string(file_path)?);
+        let denote = file_path.file_name().and_then(|name| name.to_str()).and_then(DenoteName::parse);
+        let mut file_tags = section_tags(&document.preamble);
+        if let Some(denote) = &denote {
+            merge_tags(&mut file_tags, denote.keywords.clone());
+        }
+
+        let mut nodes = Vec::new();
+        // Tags of the headings above the current one
+        let mut ancestors: Vec<(usize, Vec<String>)> = Vec::new();
+        for heading in &document.headings {
+            ancestors.retain(|(level, _)| *level < heading.level);
+            let mut tags = file_tags.clone();
+            for (_, ancestor_tags) in &ancestors {
+                merge_tags(&mut tags, ancestor_tags.clone());
             }
+            merge_tags(&mut tags, heading.tags.clone());
+            ancestors.push((heading.level, heading.tags.clone()));
 
-            if header_done {
-                content.push_str(line);
-                content.push_str("\n");
+            if let Some(id) = heading.section.property("ID") {
+                nodes.push(OrgNode {
+                    id: id.to_string(),
+                    ref_: heading.section.property("ROAM_REFS").map(|ref_| ref_.to_string()),
+                    title: heading.title.clone(),
+                    tags,
+                    created: node_datetime(file_path, &heading.section, tz),
+                    published: node_published(&heading.section, tz),
+                    content: heading.section.content(),
+                    attach_dir: attach_dir(file_path, id, heading.section.property("DIR")),
+                    authors: heading.section.property("BY").or(heading.section.property("AUTHOR")).map(split_authors).unwrap_or_default(),
+                });
             }
         }
 
-        let trimmed_content = content.trim();
+        // Title and id are mandatory for the file to be a node, but files that
+        // are only containers of heading nodes are fine
+        let id = document.preamble.property("ID")
+            .or(document.preamble.keyword("IDENTIFIER"))
+            .or(denote.as_ref().map(|denote| denote.identifier.as_str()));
+        let title = document.preamble.keyword("TITLE").map(|title| title.to_string())
+            .or(denote.as_ref().and_then(|denote| denote.title.clone()));
+        match (id, title) {
+            (Some(id), Some(title)) => nodes.insert(0, OrgNode {
+                id: id.to_string(),
+                ref_: document.preamble.property("ROAM_REFS")
+                    .or(document.preamble.keyword("REFERENCE"))
+                    .map(|ref_| ref_.to_string()),
+                title,
+                tags: file_tags,
+                created: node_datetime(file_path, &document.preamble, tz),
+                published: node_published(&document.preamble, tz),
+                content: file_content(&document),
+                attach_dir: attach_dir(file_path, id, document.preamble.property("DIR")),
+                authors: document.preamble.property("BY")
+                    .or(document.preamble.keyword("AUTHOR"))
+                    .map(split_authors)
+                    .unwrap_or_default(),
+            }),
+            _ if nodes.is_empty() => return Err(anyhow!("Parsing error")),
+            _ => (),
+        }
+        Ok(nodes)
+    }
+}
 
-        // Title and id are mandatory, if they are not present, return an
-        // Err. Else return whatever is parsed.
-        if title.is_some() && id.is_some() {
-            return Ok(OrgNode {
-                id: id.context("Unable to parse ID")?,
-                ref_,
-                title: title.context("Unable to parse title")?,
-                tags,
-                created: read_datetime(file_path)?,
-                content: if trimmed_content.is_empty() { None } else { Some(trimmed_content.to_string()) }
-            });
-        } else {
-            return Err(anyhow!("Parsing error"));
+// Where org-attach keeps files of a node: the directory in its DIR property or
+// else data/<first two characters of the id>/<the rest> next to the file
+fn attach_dir(file_path: &path::Path, id: &str, dir: Option<&str>) -> Option<path::PathBuf> {
+    let base = file_path.parent().unwrap_or(path::Path::new("."));
+    match dir {
+        Some(dir) => Some(base.join(dir)),
+        None if id.len() > 2 && id.is_char_boundary(2) => Some(base.join("data").join(&id[..2]).join(&id[2..])),
+        None => None,
+    }
+}
+
+fn list_attachments(attach_dir: Option<&path::Path>) -> Vec<path::PathBuf> {
+    let Some(Ok(entries)) = attach_dir.map(fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<path::PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|entry_path| entry_path.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+// Names in an author line, separated by commas or "and" like in BibTeX
+fn split_authors(authors: &str) -> Vec<String> {
+    authors
+        .split(',')
+        .flat_map(|part| part.split(" and "))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+// What Denote puts in file names, as in
+// 20240101T120000==signature--the-title__keyword1_keyword2.org
+struct DenoteName {
+    identifier: String,
+    title: Option<String>,
+    keywords: Vec<String>,
+}
+
+impl DenoteName {
+    fn parse(file_name: &str) -> Option<Self> {
+        let captures = DENOTE_NAME_REGEX.captures(file_name)?;
+        Some(DenoteName {
+            identifier: captures[1].to_string(),
+            title: captures.get(2).map(|title| title.as_str().replace('-', " ")),
+            keywords: captures.get(3).map(|keywords| split_denote_keywords(keywords.as_str())).unwrap_or_default(),
+        })
+    }
+
+    fn datetime(&self) -> Result<NaiveDateTime> {
+        Ok(NaiveDateTime::parse_from_str(&self.identifier, "%Y%m%dT%H%M%S")?)
+    }
+}
+
+fn split_denote_keywords(keywords: &str) -> Vec<String> {
+    keywords.split('_').filter(|keyword| !keyword.is_empty()).map(|keyword| keyword.to_string()).collect()
+}
+
+// Tags of a file from both #+TAGS: and #+filetags:
+fn section_tags(section: &Section) -> Vec<String> {
+    let mut tags = Vec::new();
+    for (key, value) in &section.keywords {
+        match key.as_str() {
+            "TAGS" => merge_tags(&mut tags, split_tags(value)),
+            "FILETAGS" => merge_tags(&mut tags, split_colon_tags(value)),
+            _ => (),
+        }
+    }
+    tags
+}
+
+// Links in a ROAM_REFS value, separated by whitespace, with the ones that have
+// spaces in them quoted
+fn split_refs(refs: &str) -> Vec<String> {
+    let mut output = Vec::new();
+    let mut rest = refs.trim();
+    while !rest.is_empty() {
+        let (ref_, remaining) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => rest.split_once(char::is_whitespace).unwrap_or((rest, "")),
+        };
+        if !ref_.is_empty() {
+            output.push(ref_.to_string());
+        }
+        rest = remaining.trim_start();
+    }
+    output
+}
+
+// The link of a bookmark out of its refs, preferring web pages over things like
+// citation keys, and the rest of the refs
+fn pick_link(mut refs: Vec<String>) -> Option<(String, Vec<String>)> {
+    let index = refs.iter().position(|ref_| ref_.starts_with("http://") || ref_.starts_with("https://")).unwrap_or(0);
+    if refs.is_empty() {
+        None
+    } else {
+        let link = refs.remove(index);
+        Some((link, refs))
+    }
+}
+
+// Title and link of each node by id, for resolving links between notes
+type NodeIndex = HashMap<String, (String, Option<String>)>;
+
+// Point links to other notes at what those notes are about, or leave just the
+// text for notes that aren't bookmarks, since ids mean nothing outside notes
+fn resolve_id_links(content: &str, index: &NodeIndex) -> String {
+    ID_LINK_REGEX.replace_all(content, |captures: &regex::Captures| {
+        let node = index.get(&captures[1]);
+        let text = captures.get(2).map(|description| description.as_str())
+            .or(node.map(|(title, _)| title.as_str()))
+            .unwrap_or(&captures[1]);
+        match node.and_then(|(_, link)| link.as_ref()) {
+            Some(link) => format!("[[{}][{}]]", link, text),
+            None => text.to_string(),
         }
+    }).to_string()
+}
+
+// How many nodes link to each node, counting a node once however often it
+// links
+fn count_backlinks(nodes: &[OrgNode]) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for node in nodes {
+        let Some(content) = &node.content else {
+            continue;
+        };
+        let targets: std::collections::HashSet<&str> = ID_LINK_REGEX.captures_iter(content)
+            .map(|captures| captures.get(1).unwrap().as_str())
+            .filter(|target| *target != node.id)
+            .collect();
+        for target in targets {
+            *counts.entry(target.to_string()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+fn resolve_bookmark_links(bookmarks: &mut [Bookmark], index: &NodeIndex) {
+    for bookmark in bookmarks.iter_mut() {
+        bookmark.content = bookmark.content.as_deref().map(|content| resolve_id_links(content, index));
+    }
+}
+
+// Text of the file up to the first heading that is a node of its own
+fn file_content(document: &Document) -> Option<String> {
+    let mut section = Section { text: document.preamble.text.clone(), ..Default::default() };
+    for heading in document.headings.iter().take_while(|heading| heading.section.property("ID").is_none()) {
+        section.text.push(format!("{} {}", "*".repeat(heading.level), heading.title));
+        section.text.extend(heading.section.text.iter().cloned());
     }
+    section.content()
 }
 
 #[derive(Debug, Clone)]
 pub struct Bookmark {
     id: String,
     ref_: String,
+    // Other links in ROAM_REFS
+    related: Vec<String>,
     title: String,
     tags: Vec<String>,
     created: DateTime<Utc>,
+    published: Option<DateTime<Utc>>,
     content: Option<String>,
+    attachments: Vec<path::PathBuf>,
+    authors: Vec<String>,
+    // How many other notes link to it
+    backlinks: usize,
 }
 
 impl Bookmark {
     fn from_org_node(node: &OrgNode) -> Result<Self> {
-        if node.ref_.is_some() {
-            Ok(Bookmark {
+        match node.ref_.as_deref().map(split_refs).and_then(pick_link) {
+            Some((ref_, related)) => Ok(Bookmark {
                 id: node.id.clone(),
-                ref_: node.ref_.clone().unwrap(),
+                ref_,
+                related,
                 title: node.title.clone(),
                 tags: node.tags.clone(),
                 created: node.created,
+                published: node.published,
                 content: node.content.clone(),
-            })
-        } else {
-            Err(anyhow!("Reference not found in node."))
+                attachments: list_attachments(node.attach_dir.as_deref()),
+                authors: node.authors.clone(),
+                backlinks: 0,
+            }),
+            None => Err(anyhow!("Reference not found in node.")),
         }
     }
 
-    pub fn is_unread(&self) -> bool {
-        self.tags.contains(&"unsorted".to_string())
+    // A bookmark for each link, the first one keeping the id of the node
+    pub fn split(&self) -> Vec<Bookmark> {
+        let first = Bookmark { related: Vec::new(), ..self.clone() };
+        let rest = self.related.iter().map(|ref_| Bookmark {
+            id: format!("{}#{}", self.id, ref_),
+            ref_: ref_.clone(),
+            ..first.clone()
+        });
+        std::iter::once(first.clone()).chain(rest).collect()
     }
 
-    pub fn is_project(&self) -> bool {
-        if self.tags.contains(&"project".to_string()) {
-            true
-        } else {
-            self.ref_.starts_with("https://github.com")
-        }
+    // Archived with org's archive tag or kept out of exports, either on the
+    // node or above it, since tags are inherited
+    pub fn is_archived(&self) -> bool {
+        self.tags.iter().any(|tag| tag == "ARCHIVE" || tag == "noexport")
+    }
+
+    pub fn is_unread(&self, bookmarks: &BookmarkConfig) -> bool {
+        Filter::unread(bookmarks).matches(&self.to_newsitem())
     }
 
-    pub fn is_recommended(&self) -> bool {
-        self.tags.contains(&"recommend".to_string()) & !self.is_unread()
+    pub fn is_project(&self, bookmarks: &BookmarkConfig) -> bool {
+        Filter::project(bookmarks).matches(&self.to_newsitem())
+    }
+
+    pub fn is_recommended(&self, bookmarks: &BookmarkConfig) -> bool {
+        Filter::recommended(bookmarks).matches(&self.to_newsitem())
     }
 }
 
@@ -145,68 +426,267 @@ impl ToNewsItem for Bookmark {
             link: self.ref_.clone(),
             title: self.title.clone(),
             summary: self.content.clone(),
-            // NOTE: This is semantically wrong since created (when bookmark was
-            //       saved) != published (when content was actually published).
-            published: self.created,
+            content: None,
+            content_html: false,
+            // Saving the bookmark is the closest to publishing there is
+            // when the real date isn't noted
+            published: self.published.unwrap_or(self.created),
             updated: self.created,
-            authors: Vec::new(),
+            authors: self.authors.iter()
+                .map(|name| NewsAuthor { name: name.clone(), email: String::new(), uri: String::new() })
+                .collect(),
             categories: self.tags.clone(),
+            enclosures: self.attachments.iter().filter_map(|file_path| attachments::file_enclosure(file_path)).collect(),
+            related: self.related.clone(),
+            backlinks: self.backlinks,
+            language: None,
         }
     }
 }
 
-fn read_bookmark_from_file(file_path: &path::Path) -> Result<Bookmark> {
-    let org_node = OrgNode::from_file(file_path)?;
-    Bookmark::from_org_node(&org_node)
+// Bookmarks from my org-roam notes, read from the db if its path is set, else
+// from the notes directory. With both, the db is checked against the files.
+#[derive(Clone)]
+pub struct PileSource {
+    roam_db_path: Option<path::PathBuf>,
+    notes_dir_path: Option<path::PathBuf>,
+    scan: ScanOptions,
+    // Make a bookmark of each link of nodes with many refs
+    split_refs: bool,
+    // Keep archived and noexport nodes
+    include_archived: bool,
+    // Files the last fetch could not read
+    issues: Arc<Mutex<Vec<String>>>,
 }
 
-// Read #+TAGS: from the file and return a list
-// This doesn't read filetags like it should
-fn read_tags(file_path: &path::Path) -> Vec<String> {
-    if let Ok(file) = File::open(file_path) {
-        for line in io::BufReader::new(file).lines() {
-            if let Ok(line_content) = line {
-                if let Some(captures) = TAGS_REGEX.captures(&line_content) {
-                    if let Some(tags) = captures.get(1) {
-                        return tags.as_str()
-                            .split(",")
-                            .map(|tag| tag.trim().to_string())
-                            .collect();
-                    }
+impl PileSource {
+    pub fn new(ctx: &SourceContext, feed: &FeedConfig) -> Result<Self> {
+        Ok(PileSource {
+            split_refs: feed.split_refs,
+            include_archived: feed.include_archived,
+            ..PileSource::from_profile(ctx.profile)?
+        })
+    }
+
+    pub fn from_profile(profile: &Profile) -> Result<Self> {
+        if profile.roam_db_path.is_none() && profile.notes_dir_path.is_none() {
+            return Err(anyhow!("Need either --notes-dir-path or --roam-db-path to be set!"));
+        }
+
+        Ok(PileSource {
+            roam_db_path: profile.roam_db_path.clone(),
+            notes_dir_path: profile.notes_dir_path.clone(),
+            scan: ScanOptions::from_profile(profile)?,
+            split_refs: false,
+            include_archived: false,
+            issues: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    pub fn read_bookmarks(&self) -> Vec<Bookmark> {
+        self.read_bookmarks_with_issues().0
+    }
+
+    // Bookmarks, with the ones saved more than once merged, and the files
+    // that could not be read
+    fn read_bookmarks_with_issues(&self) -> (Vec<Bookmark>, Vec<String>) {
+        let (mut bookmarks, issues) = match (&self.roam_db_path, &self.notes_dir_path) {
+            (Some(db_path), Some(dir_path)) => reconcile(read_bookmarks(db_path, self.scan.timezone), scan_notes_dir(dir_path, &self.scan)),
+            (Some(db_path), None) => (read_bookmarks(db_path, self.scan.timezone), Vec::new()),
+            (None, Some(dir_path)) => scan_notes_dir(dir_path, &self.scan),
+            (None, None) => (Vec::new(), Vec::new()),
+        };
+        // Before merging, so that a page is dropped if any of its bookmarks
+        // was archived
+        if !self.include_archived {
+            bookmarks.retain(|bookmark| !bookmark.is_archived());
+        }
+        (dedup_bookmarks(bookmarks), issues)
+    }
+}
+
+// Bookmarks of the db, with the text of nodes parsed out of the files since
+// the db only knows which file a node is in, and the nodes the db is missing
+// as it hasn't caught up with the files. Differences between the two are
+// reported along with the files that could not be read.
+fn reconcile(db_bookmarks: Vec<Bookmark>, (dir_bookmarks, mut issues): (Vec<Bookmark>, Vec<String>)) -> (Vec<Bookmark>, Vec<String>) {
+    let mut from_files: HashMap<String, Bookmark> = dir_bookmarks.iter()
+        .map(|bookmark| (bookmark.id.clone(), bookmark.clone()))
+        .collect();
+
+    let mut output = Vec::new();
+    for mut bookmark in db_bookmarks {
+        match from_files.remove(&bookmark.id) {
+            Some(parsed) => {
+                if parsed.ref_ != bookmark.ref_ {
+                    issues.push(format!("Node {} links to {} in the db but {} in the notes", bookmark.id, bookmark.ref_, parsed.ref_));
                 }
-            }
+                bookmark.content = parsed.content;
+            },
+            None => issues.push(format!("Node {} ({}) is in the db but not in the notes", bookmark.id, bookmark.title)),
         }
+        output.push(bookmark);
     }
-    Vec::new()
+
+    // In the order the files were read
+    for bookmark in dir_bookmarks {
+        if let Some(missing) = from_files.remove(&bookmark.id) {
+            issues.push(format!("Node {} ({}) is in the notes but not in the db", missing.id, missing.title));
+            output.push(missing);
+        }
+    }
+    (output, issues)
 }
 
-fn read_content(file_path: &path::Path) -> Result<String> {
-    let file = File::open(file_path)?;
-    let reader = io::BufReader::new(file);
-    let mut content = String::new();
+// Merge bookmarks of the same page, as told by their normalized links, into
+// the one saved first, with the tags and links of all
+fn dedup_bookmarks(bookmarks: Vec<Bookmark>) -> Vec<Bookmark> {
+    let mut output: Vec<Bookmark> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
 
-    let mut in_content = false;
-    for line in reader.lines() {
-        let line = line?;
-        let trimmed_line = line.trim();
+    for bookmark in bookmarks {
+        match positions.get(&utils::normalize_url(&bookmark.ref_)) {
+            Some(&position) => {
+                let existing = &mut output[position];
+                let (mut first, second) = if bookmark.created < existing.created {
+                    (bookmark, existing.clone())
+                } else {
+                    (existing.clone(), bookmark)
+                };
+                merge_tags(&mut first.tags, second.tags);
+                merge_tags(&mut first.related, second.related);
+                merge_tags(&mut first.attachments, second.attachments);
+                merge_tags(&mut first.authors, second.authors);
+                first.published = first.published.or(second.published);
+                first.backlinks += second.backlinks;
+                *existing = first;
+            },
+            None => {
+                positions.insert(utils::normalize_url(&bookmark.ref_), output.len());
+                output.push(bookmark);
+            },
+        }
+    }
+    output
+}
 
-        if !in_content {
-            if trimmed_line.starts_with("#") || trimmed_line.starts_with(":") || trimmed_line.is_empty() {
-                continue;
-            } else {
-                in_content = true;
-            }
+#[async_trait]
+impl Source for PileSource {
+    // Reading notes is blocking file and db IO, so this runs off the async
+    // workers.
+    async fn fetch(&self) -> Result<Vec<NewsItem>> {
+        let source = self.clone();
+        let bookmarks = tokio::task::spawn_blocking(move || {
+            let (bookmarks, issues) = source.read_bookmarks_with_issues();
+            *source.issues.lock().unwrap() = issues;
+            bookmarks
+        }).await?;
+        let bookmarks = if self.split_refs {
+            bookmarks.iter().flat_map(|bm| bm.split()).collect()
+        } else {
+            bookmarks
+        };
+        Ok(bookmarks.iter().map(|bm| bm.to_newsitem()).collect())
+    }
+
+    fn issues(&self) -> Vec<String> {
+        self.issues.lock().unwrap().clone()
+    }
+
+    fn shared_key(&self) -> Option<String> {
+        Some(format!("pile:{:?}:{:?}:{}:{}", self.roam_db_path, self.notes_dir_path, self.split_refs, self.include_archived))
+    }
+}
+
+// Split the value of a #+TAGS: line into individual tags
+fn split_tags(tags_str: &str) -> Vec<String> {
+    tags_str
+        .split(",")
+        .map(|tag| tag.trim().to_string())
+        .collect()
+}
+
+// Add the tags not already there, keeping the order they were found in
+fn merge_tags<T: PartialEq>(tags: &mut Vec<T>, new_tags: Vec<T>) {
+    for tag in new_tags {
+        if !tags.contains(&tag) {
+            tags.push(tag);
         }
+    }
+}
+
+// A note file the db points to, if it is there to read
+fn read_document(file_path: &path::Path) -> Option<Document> {
+    fs::read_to_string(file_path).ok().map(|org| Document::parse(&org))
+}
 
-        content.push_str(&line);
-        content.push_str("\n");
+// Text of a node from the db in its file, where nodes at level 0 are the file
+// itself and others are headings of that level with the node's id
+fn db_node_content(document: &Document, id: &str, level: usize) -> Option<String> {
+    if level == 0 {
+        return file_content(document);
     }
+    document.headings.iter()
+        .find(|heading| heading.level == level && heading.section.property("ID") == Some(id))
+        .and_then(|heading| heading.section.content())
+}
+
+// When a node was created: its :CREATED: property or the first org timestamp
+// in its text, then the time in the file name, and at last when the file was
+// modified
+fn node_datetime(file_path: &path::Path, section: &Section, tz: NotesTimezone) -> DateTime<Utc> {
+    section.property("CREATED").and_then(|created| parse_timestamp(created, tz))
+        .or_else(|| section.text.iter().find_map(|line| parse_timestamp(line, tz)))
+        .or_else(|| read_datetime(file_path, tz).ok())
+        .or_else(|| fs::metadata(file_path).and_then(|metadata| metadata.modified()).ok().map(DateTime::<Utc>::from))
+        .unwrap_or_else(Utc::now)
+}
+
+// When what a node is about was published: its :PUBLISHED: property or the
+// #+DATE: of the file
+fn node_published(section: &Section, tz: NotesTimezone) -> Option<DateTime<Utc>> {
+    section.property("PUBLISHED").or(section.keyword("DATE")).and_then(|date| parse_timestamp(date, tz))
+}
+
+// Value of a property in the properties alist org-roam keeps for a node
+fn db_property<'a>(properties: &'a str, name: &str) -> Option<&'a str> {
+    DB_PROPERTY_REGEX.captures_iter(properties)
+        .find(|captures| &captures[1] == name)
+        .map(|captures| captures.get(2).unwrap().as_str())
+}
+
+// Creation time of a node in the org-roam db: its :CREATED: property, the time
+// in the file name, or when org-roam saw the file modified
+fn db_node_datetime(file_path: &path::Path, properties: &str, mtime: &str, tz: NotesTimezone) -> DateTime<Utc> {
+    db_property(properties, "CREATED").and_then(|created| parse_timestamp(created, tz))
+        .or_else(|| read_datetime(file_path, tz).ok())
+        .or_else(|| parse_emacs_time(mtime))
+        .unwrap_or_else(Utc::now)
+}
 
-    Ok(content)
+// Emacs times are lists like (HIGH LOW USEC PSEC), with the seconds split in
+// the high and low 16 bits
+fn parse_emacs_time(time: &str) -> Option<DateTime<Utc>> {
+    let mut parts = time.trim_matches(|c| c == '(' || c == ')').split_whitespace();
+    let high: i64 = parts.next()?.parse().ok()?;
+    let low: i64 = parts.next()?.parse().ok()?;
+    DateTime::from_timestamp(high * 65536 + low, 0)
+}
+
+// The first org timestamp in the text, like [2024-01-02 Tue 10:30] or
+// <2024-01-02 Tue>, in the timezone of the notes
+fn parse_timestamp(text: &str, tz: NotesTimezone) -> Option<DateTime<Utc>> {
+    let captures = TIMESTAMP_REGEX.captures(text)?;
+    let date = chrono::NaiveDate::parse_from_str(&captures[1], "%Y-%m-%d").ok()?;
+    let time = match captures.get(2) {
+        Some(time) => chrono::NaiveTime::parse_from_str(time.as_str(), "%H:%M").ok()?,
+        None => chrono::NaiveTime::MIN,
+    };
+    tz.to_utc(date.and_time(time))
 }
 
 // Read datetime of creation of the file using the pattern in file name
-fn read_datetime(file_path: &path::Path) -> Result<DateTime<Utc>> {
+fn read_datetime(file_path: &path::Path, tz: NotesTimezone) -> Result<DateTime<Utc>> {
     let file_name = file_path
         .file_name()
         .context("Not able to get file name")?
@@ -214,73 +694,328 @@ fn read_datetime(file_path: &path::Path) -> Result<DateTime<Utc>> {
         .context("Failed to convert file name to str")?;
 
     // Files are named in the following pattern
-    // YYYYmmddHHMMSS-<stuff>.org
-    if let Some((first, _)) = file_name.to_string().split_once("-") {
-        let dt = chrono::NaiveDateTime::parse_from_str(first, "%Y%m%d%H%M%S")?;
-
-        // Most of my saves are in this timezone, but if they are not we will
-        // get wrong results. I don't have a good way of solving it right now
-        // other than adding tz information in the file name.
-        let tz = chrono_tz::Asia::Kolkata;
-        Ok(dt.and_local_timezone(tz).unwrap().to_utc())
+    // YYYYmmddHHMMSS-<stuff>.org, or YYYYmmddTHHMMSS--<stuff>.org by Denote
+    if let Some(denote) = DenoteName::parse(file_name) {
+        let dt = denote.datetime()?;
+        tz.to_utc(dt).ok_or_else(|| anyhow!("{} doesn't exist in {:?}", dt, tz))
+    } else if let Some((first, _)) = file_name.to_string().split_once("-") {
+        let dt = NaiveDateTime::parse_from_str(first, "%Y%m%d%H%M%S")?;
+
+        tz.to_utc(dt).ok_or_else(|| anyhow!("{} doesn't exist in {:?}", dt, tz))
     } else {
         Err(anyhow!("Error in parsing file: {}", file_name))
     }
 }
 
-// Read bookmarks from my org-roam directory
-pub fn read_bookmarks_from_dir(dir_path: &path::Path) -> Vec<Bookmark> {
-    let mut output = Vec::new();
+// Read bookmarks from my org-roam directory and its subdirectories
+pub fn read_bookmarks_from_dir(dir_path: &path::Path, options: &ScanOptions) -> Vec<Bookmark> {
+    scan_notes_dir(dir_path, options).0
+}
 
-    for res in std::fs::read_dir(dir_path).unwrap() {
-        let path = res.unwrap().path();
-        if let Some(ext) = path.extension() {
-            if ext == "org" {
-                if let Ok(bookmark) = read_bookmark_from_file(path.as_path()) {
-                    output.push(bookmark);
-                }
-            }
+// Read bookmarks from the directory tree, down to max_depth levels, along with
+// problems in files that are not readable as org nodes at all. Nodes that are
+// not bookmarks are skipped silently, as are hidden directories like .git and
+// excluded paths.
+pub fn scan_notes_dir(dir_path: &path::Path, options: &ScanOptions) -> (Vec<Bookmark>, Vec<String>) {
+    let mut paths = Vec::new();
+    let mut issues = Vec::new();
+
+    let mut walker = WalkDir::new(dir_path).follow_links(true).sort_by_file_name();
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    let entries = walker.into_iter().filter_entry(|entry| {
+        entry.depth() == 0 || !(entry.file_name().to_str().is_some_and(|name| name.starts_with('.'))
+            || entry.path().strip_prefix(dir_path).is_ok_and(|relative_path| options.is_excluded(relative_path)))
+    });
+
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) if entry.file_type().is_file() => entry.into_path(),
+            Ok(_) => continue,
+            Err(err) => {
+                issues.push(err.to_string());
+                continue;
+            },
+        };
+        if path.extension().is_some_and(|ext| ext == "org") {
+            paths.push(path);
         }
     }
 
-    output
+    let cache = options.cache_path.as_ref().and_then(|db_path| match NotesCache::open(db_path, options.timezone) {
+        Ok(cache) => Some(cache),
+        Err(err) => {
+            warn!("Not using the notes cache: {:#}", err);
+            None
+        },
+    });
+    let stamps: Vec<Option<FileStamp>> = paths.iter().map(|path| FileStamp::of(path)).collect();
+    let cached: Vec<Option<Vec<OrgNode>>> = paths.iter().zip(&stamps)
+        .map(|(path, stamp)| cache.as_ref().zip(stamp.as_ref()).and_then(|(cache, stamp)| cache.get(path, stamp)))
+        .collect();
+
+    // Files are independent of each other, so the ones not in the cache are
+    // parsed in parallel, keeping the order of the walk
+    let parsed: Vec<(Result<Vec<OrgNode>>, bool)> = paths.par_iter().zip(cached)
+        .map(|(path, cached)| match cached {
+            Some(file_nodes) => (Ok(file_nodes), false),
+            None => (OrgNode::from_file(path, options.timezone), true),
+        })
+        .collect();
+
+    if let Some(cache) = &cache {
+        let fresh: Vec<_> = paths.iter().zip(&stamps).zip(&parsed)
+            .filter_map(|((path, stamp), (result, is_fresh))| match (stamp, result) {
+                (Some(stamp), Ok(file_nodes)) if *is_fresh => Some((path.as_path(), stamp.clone(), file_nodes.as_slice())),
+                _ => None,
+            })
+            .collect();
+        if let Err(err) = cache.put(&fresh) {
+            warn!("Unable to update the notes cache: {:#}", err);
+        }
+    }
+
+    let mut nodes = Vec::new();
+    for (path, (result, _)) in paths.iter().zip(parsed) {
+        match result {
+            Ok(file_nodes) => nodes.extend(file_nodes),
+            Err(err) => issues.push(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    let mut output: Vec<Bookmark> = nodes.iter().filter_map(|node| Bookmark::from_org_node(node).ok()).collect();
+    let backlinks = count_backlinks(&nodes);
+    for bookmark in output.iter_mut() {
+        bookmark.backlinks = backlinks.get(&bookmark.id).copied().unwrap_or_default();
+    }
+    let index: NodeIndex = nodes.into_iter()
+        .map(|node| {
+            let link = node.ref_.as_deref().map(split_refs).and_then(pick_link).map(|(link, _)| link);
+            (node.id, (node.title, link))
+        })
+        .collect();
+    resolve_bookmark_links(&mut output, &index);
+
+    (output, issues)
 }
 
-// Read bookmarks from org-roam database
-pub fn read_bookmarks(roam_db_path: &path::Path) -> Vec<Bookmark> {
+// Read bookmarks from org-roam database. Everything but the content comes from
+// the db, so this works without the notes directory, but content is read from
+// the files when they are around. Both the node based schema of org-roam v2
+// and the file based one of v1 are read.
+pub fn read_bookmarks(roam_db_path: &path::Path, tz: NotesTimezone) -> Vec<Bookmark> {
     let connection = sqlite::open(roam_db_path).unwrap();
+
+    let (mut output, mut index) = if has_table(&connection, "nodes") {
+        read_v2_bookmarks(&connection, tz)
+    } else {
+        read_v1_bookmarks(&connection, tz)
+    };
+
+    // Links go to ids in v2, and to ids or files in v1
+    if has_table(&connection, "links") {
+        let mut backlinks: HashMap<String, usize> = HashMap::new();
+        let mut statement = connection.prepare(r#"
+            SELECT TRIM(dest, '"') AS dest, COUNT(DISTINCT source) AS count
+            FROM links WHERE TRIM(type, '"') IN ('id', 'file') GROUP BY dest;"#).unwrap();
+        while let Ok(sqlite::State::Row) = statement.next() {
+            backlinks.insert(statement.read::<String, _>("dest").unwrap(), statement.read::<i64, _>("count").unwrap() as usize);
+        }
+        for bookmark in output.iter_mut() {
+            bookmark.backlinks = backlinks.get(&bookmark.id).copied().unwrap_or_default();
+        }
+    }
+
+    for bookmark in output.iter_mut() {
+        let mut refs = vec![bookmark.ref_.clone()];
+        refs.append(&mut bookmark.related);
+        (bookmark.ref_, bookmark.related) = pick_link(refs).unwrap();
+    }
+
+    for bookmark in &output {
+        index.insert(bookmark.id.clone(), (bookmark.title.clone(), Some(bookmark.ref_.clone())));
+    }
+    resolve_bookmark_links(&mut output, &index);
+
+    output
+}
+
+fn has_table(connection: &sqlite::Connection, name: &str) -> bool {
+    let mut statement = connection.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?").unwrap();
+    statement.bind((1, name)).unwrap();
+    matches!(statement.next(), Ok(sqlite::State::Row))
+}
+
+// Bookmarks with their refs in the order the db has them, and the titles of
+// all nodes by id
+fn read_v2_bookmarks(connection: &sqlite::Connection, tz: NotesTimezone) -> (Vec<Bookmark>, NodeIndex) {
+    // Tags here include the ones inherited from files and parent headings
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    let mut statement = connection.prepare(r#"SELECT TRIM(node_id, '"') AS id, TRIM(tag, '"') AS tag FROM tags;"#).unwrap();
+    while let Ok(sqlite::State::Row) = statement.next() {
+        let node_tags = tags.entry(statement.read::<String, _>("id").unwrap()).or_default();
+        merge_tags(node_tags, vec![statement.read::<String, _>("tag").unwrap()]);
+    }
+
     let query = r#"
         SELECT
             TRIM(id, '"') AS id,
-            TRIM(file, '"') AS file,
-            TRIM(title, '"') AS title,
-            CONCAT(TRIM(type, '"'), ':', TRIM(ref, '"')) AS ref
+            TRIM(nodes.file, '"') AS file,
+            TRIM(nodes.title, '"') AS title,
+            nodes.level AS level,
+            TRIM(type, '"') || ':' || TRIM(ref, '"') AS ref,
+            COALESCE(properties, '') AS properties,
+            COALESCE(files.mtime, '') AS mtime
         FROM nodes
-        INNER JOIN refs ON nodes.id = refs.node_id;"#;
+        INNER JOIN refs ON nodes.id = refs.node_id
+        LEFT JOIN files ON nodes.file = files.file
+        ORDER BY nodes.id;"#;
 
     let mut output: Vec<Bookmark> = Vec::new();
+    // Files parsed so far, as many nodes can be in one file
+    let mut documents: HashMap<String, Option<Document>> = HashMap::new();
     let mut statement = connection.prepare(query).unwrap();
 
+    // There is a row for each ref of a node, next to each other
     while let Ok(sqlite::State::Row) = statement.next() {
+        let id = statement.read::<String, _>("id").unwrap();
+        let ref_ = statement.read::<String, _>("ref").unwrap();
+        if let Some(bookmark) = output.last_mut().filter(|bookmark| bookmark.id == id) {
+            bookmark.related.push(ref_);
+            continue;
+        }
+
         let file_path_str = statement.read::<String, _>("file").unwrap();
         let file_path = path::Path::new(&file_path_str);
+        let node_tags = tags.remove(&id).unwrap_or_default();
+        let properties = statement.read::<String, _>("properties").unwrap();
+        let node_attach_dir = attach_dir(file_path, &id, db_property(&properties, "DIR"));
+        let level = statement.read::<i64, _>("level").unwrap() as usize;
+        let content = documents.entry(file_path_str.clone())
+            .or_insert_with(|| read_document(file_path))
+            .as_ref()
+            .and_then(|document| db_node_content(document, &id, level));
 
         output.push(Bookmark {
-            id: statement.read::<String, _>("id").unwrap(),
-            ref_: statement.read::<String, _>("ref").unwrap(),
+            id,
+            ref_,
+            related: Vec::new(),
             title: statement.read::<String, _>("title").unwrap(),
-            tags: read_tags(file_path),
-            created: read_datetime(file_path).unwrap_or(chrono::Utc::now()),
-            content: read_content(file_path).map_or(None, |v| Some(v)),
+            tags: node_tags,
+            created: db_node_datetime(
+                file_path,
+                &properties,
+                &statement.read::<String, _>("mtime").unwrap(),
+                tz,
+            ),
+            published: db_property(&properties, "PUBLISHED").and_then(|published| parse_timestamp(published, tz)),
+            content,
+            attachments: list_attachments(node_attach_dir.as_deref()),
+            authors: db_property(&properties, "BY").or(db_property(&properties, "AUTHOR")).map(split_authors).unwrap_or_default(),
+            backlinks: 0,
         });
     }
 
-    output
+    let mut index = NodeIndex::new();
+    let mut statement = connection.prepare(r#"SELECT TRIM(id, '"') AS id, TRIM(title, '"') AS title FROM nodes;"#).unwrap();
+    while let Ok(sqlite::State::Row) = statement.next() {
+        index.insert(statement.read::<String, _>("id").unwrap(), (statement.read::<String, _>("title").unwrap(), None));
+    }
+
+    (output, index)
+}
+
+// In org-roam v1 files are the notes, with refs from #+ROAM_KEY:, titles and
+// aliases in their own table, tags as a list per file, and modification times
+// in a plist of file metadata. Files without an id go by their path.
+fn read_v1_bookmarks(connection: &sqlite::Connection, tz: NotesTimezone) -> (Vec<Bookmark>, NodeIndex) {
+    let query = r#"
+        SELECT
+            TRIM(refs.file, '"') AS file,
+            COALESCE((SELECT TRIM(id, '"') FROM ids WHERE ids.file = refs.file AND ids.level = 0), '') AS id,
+            COALESCE((SELECT TRIM(title, '"') FROM titles WHERE titles.file = refs.file AND title IS NOT NULL), '') AS title,
+            TRIM(refs.type, '"') AS type,
+            TRIM(refs.ref, '"') AS ref,
+            COALESCE(tags.tags, '') AS tags,
+            COALESCE(files.meta, '') AS meta
+        FROM refs
+        LEFT JOIN tags ON refs.file = tags.file
+        LEFT JOIN files ON refs.file = files.file
+        ORDER BY refs.file;"#;
+
+    let mut output: Vec<Bookmark> = Vec::new();
+    // Files parsed so far, by path
+    let mut documents: HashMap<String, Option<Document>> = HashMap::new();
+    let mut statement = connection.prepare(query).unwrap();
+
+    // There is a row for each ref of a file, next to each other
+    while let Ok(sqlite::State::Row) = statement.next() {
+        let file_path_str = statement.read::<String, _>("file").unwrap();
+        let ref_ = v1_ref(&statement.read::<String, _>("type").unwrap(), &statement.read::<String, _>("ref").unwrap());
+        let id = Some(statement.read::<String, _>("id").unwrap())
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| file_path_str.clone());
+        if let Some(bookmark) = output.last_mut().filter(|bookmark| bookmark.id == id) {
+            bookmark.related.push(ref_);
+            continue;
+        }
+
+        let file_path = path::Path::new(&file_path_str);
+        let title = Some(statement.read::<String, _>("title").unwrap())
+            .filter(|title| !title.is_empty())
+            .or_else(|| file_path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+            .unwrap_or_default();
+        let meta = statement.read::<String, _>("meta").unwrap();
+        let mtime = V1_MTIME_REGEX.captures(&meta).map(|captures| captures[1].to_string()).unwrap_or_default();
+        let node_attach_dir = attach_dir(file_path, &id, None);
+
+        output.push(Bookmark {
+            id,
+            ref_,
+            related: Vec::new(),
+            title,
+            tags: QUOTED_REGEX.captures_iter(&statement.read::<String, _>("tags").unwrap())
+                .map(|captures| captures[1].to_string())
+                .collect(),
+            created: db_node_datetime(file_path, "", &mtime, tz),
+            published: None,
+            content: documents.entry(file_path_str.clone())
+                .or_insert_with(|| read_document(file_path))
+                .as_ref()
+                .and_then(file_content),
+            attachments: list_attachments(node_attach_dir.as_deref()),
+            authors: Vec::new(),
+            backlinks: 0,
+        });
+    }
+
+    let mut index = NodeIndex::new();
+    let mut statement = connection.prepare(r#"
+        SELECT TRIM(ids.id, '"') AS id, TRIM(titles.title, '"') AS title
+        FROM ids INNER JOIN titles ON ids.file = titles.file
+        WHERE titles.title IS NOT NULL;"#).unwrap();
+    while let Ok(sqlite::State::Row) = statement.next() {
+        index.entry(statement.read::<String, _>("id").unwrap()).or_insert((statement.read::<String, _>("title").unwrap(), None));
+    }
+
+    (output, index)
+}
+
+// Refs of v1 are split into the type and the rest like v2, except in older
+// versions that kept web links whole with the type website
+fn v1_ref(type_: &str, ref_: &str) -> String {
+    if type_ == "website" || ref_.contains("://") {
+        ref_.to_string()
+    } else {
+        format!("{}:{}", type_, ref_)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
 
     #[test]
     fn tags_parsing_works() {
@@ -291,6 +1026,328 @@ mod tests {
 #+TAGS: project, speech, privacy
 #+TITLE: MattMoony/figaro: Real-time voice-changer for voice-chat, etc. Will support many different voice-filters and features in the future. 🎵
 "#;
-        assert!(true);
+        let tags = section_tags(&Document::parse(string).preamble);
+
+        assert_eq!(tags, vec!["project", "speech", "privacy"]);
+    }
+
+    #[test]
+    fn filetags_are_merged_with_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("20240101120000-note.org");
+        fs::write(&file_path, ":PROPERTIES:\n:ID: a\n:ROAM_REFS: https://example.com\n:END:\n#+filetags: :unsorted:ml:\n#+TAGS: ml, speech\n#+TITLE: A\n").unwrap();
+
+        let node = OrgNode::from_file(&file_path, NotesTimezone::Named(chrono_tz::Asia::Kolkata)).unwrap().remove(0);
+        assert_eq!(node.tags, vec!["unsorted", "ml", "speech"]);
+        assert!(Bookmark::from_org_node(&node).unwrap().is_unread(&BookmarkConfig::default()));
+    }
+
+    #[test]
+    fn created_property_wins_over_file_name() {
+        let section = Document::parse(":PROPERTIES:\n:ID: a\n:CREATED: [2023-05-10 Wed 10:10]\n:END:\n#+TITLE: A\nSeen on <2020-01-01 Wed>").preamble;
+        let file_path = path::Path::new("/nonexistent/20240101120000-a.org");
+        let tz = NotesTimezone::new(Some("Asia/Kolkata")).unwrap();
+        assert_eq!(node_datetime(file_path, &section, tz).to_rfc3339(), "2023-05-10T04:40:00+00:00");
+
+        let section = Document::parse("#+TITLE: A\n#+DATE: [2017-06-12 Mon]\nSeen on <2020-01-01 Wed>").preamble;
+        assert_eq!(node_datetime(file_path, &section, tz).to_rfc3339(), "2019-12-31T18:30:00+00:00");
+        // The date of a file is when what it is about was published
+        assert_eq!(node_published(&section, tz).unwrap().to_rfc3339(), "2017-06-11T18:30:00+00:00");
+
+        let section = Document::parse("#+TITLE: A").preamble;
+        assert_eq!(node_datetime(file_path, &section, tz).to_rfc3339(), "2024-01-01T06:30:00+00:00");
+
+        let tz = NotesTimezone::new(Some("America/New_York")).unwrap();
+        assert_eq!(node_datetime(file_path, &section, tz).to_rfc3339(), "2024-01-01T17:00:00+00:00");
+        assert!(NotesTimezone::new(Some("Mars/Olympus")).is_err());
+    }
+
+    #[test]
+    fn denote_names_are_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("20240102T103000--attention-is-all-you-need__ml_unsorted.org");
+        fs::write(&file_path, "#+filetags: :paper:\n#+reference: https://arxiv.org/abs/1706.03762\n\nTransformers.\n").unwrap();
+
+        let tz = NotesTimezone::Named(chrono_tz::UTC);
+        let node = OrgNode::from_file(&file_path, tz).unwrap().remove(0);
+        assert_eq!(node.id, "20240102T103000");
+        assert_eq!(node.title, "attention is all you need");
+        assert_eq!(node.tags, vec!["paper", "ml", "unsorted"]);
+        assert_eq!(node.created.to_rfc3339(), "2024-01-02T10:30:00+00:00");
+        assert_eq!(Bookmark::from_org_node(&node).unwrap().ref_, "https://arxiv.org/abs/1706.03762");
+    }
+
+    #[test]
+    fn headings_with_ids_are_nodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("20240101120000-refile.org");
+        fs::write(&file_path, r#"#+filetags: :unsorted:
+#+TITLE: Refile
+
+* Papers :ml:
+** Attention is all you need :nlp:
+:PROPERTIES:
+:ID:       b
+:ROAM_REFS: https://arxiv.org/abs/1706.03762
+:BY:       Ashish Vaswani and Noam Shazeer, Niki Parmar
+:END:
+Transformers.
+* Not a node
+Some text
+"#).unwrap();
+
+        let nodes = OrgNode::from_file(&file_path, NotesTimezone::Named(chrono_tz::Asia::Kolkata)).unwrap();
+        assert_eq!(nodes.len(), 1);
+        let bookmark = Bookmark::from_org_node(&nodes[0]).unwrap();
+        assert_eq!(bookmark.id, "b");
+        assert_eq!(bookmark.ref_, "https://arxiv.org/abs/1706.03762");
+        assert_eq!(bookmark.title, "Attention is all you need");
+        assert_eq!(bookmark.tags, vec!["unsorted", "ml", "nlp"]);
+        assert_eq!(bookmark.content.as_deref(), Some("Transformers."));
+        let authors: Vec<_> = bookmark.to_newsitem().authors.iter().map(|author| author.name.clone()).collect();
+        assert_eq!(authors, vec!["Ashish Vaswani", "Noam Shazeer", "Niki Parmar"]);
+    }
+
+    #[test]
+    fn web_links_are_picked_out_of_refs() {
+        assert_eq!(split_refs(r#"cite:vaswani2017 https://a.example "https://b.example/a b""#), vec!["cite:vaswani2017", "https://a.example", "https://b.example/a b"]);
+
+        let node = OrgNode {
+            id: "a".to_string(),
+            ref_: Some("cite:vaswani2017 https://a.example https://b.example".to_string()),
+            title: "A".to_string(),
+            tags: Vec::new(),
+            created: Utc::now(),
+            published: None,
+            content: None,
+            attach_dir: None,
+            authors: Vec::new(),
+        };
+        let bookmark = Bookmark::from_org_node(&node).unwrap();
+        assert_eq!(bookmark.ref_, "https://a.example");
+        assert_eq!(bookmark.related, vec!["cite:vaswani2017", "https://b.example"]);
+
+        let links: Vec<_> = bookmark.split().iter().map(|bm| (bm.id.clone(), bm.ref_.clone())).collect();
+        assert_eq!(links, vec![
+            ("a".to_string(), "https://a.example".to_string()),
+            ("a#cite:vaswani2017".to_string(), "cite:vaswani2017".to_string()),
+            ("a#https://b.example".to_string(), "https://b.example".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn db_is_read_without_the_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("roam.db");
+        let file_path = dir.path().join("c.org");
+        fs::write(&file_path, ":PROPERTIES:\n:ID: c\n:END:\n#+TITLE: C\nAbout C\n* D\n:PROPERTIES:\n:ID: d\n:END:\nAbout D\n").unwrap();
+        let connection = sqlite::open(&db_path).unwrap();
+        // The parts of org-roam's schema that are read, with values printed
+        // the way Emacs does
+        connection.execute(format!(r#"
+            CREATE TABLE files (file UNIQUE PRIMARY KEY, title, hash NOT NULL, atime NOT NULL, mtime NOT NULL);
+            CREATE TABLE nodes (id NOT NULL PRIMARY KEY, file NOT NULL, level NOT NULL, pos NOT NULL, title, properties);
+            CREATE TABLE tags (node_id NOT NULL, tag);
+            CREATE TABLE refs (node_id NOT NULL, ref NOT NULL, type NOT NULL);
+            CREATE TABLE links (pos NOT NULL, source NOT NULL, dest NOT NULL, type NOT NULL, properties NOT NULL);
+            INSERT INTO files VALUES ('"/missing/a.org"', '"A"', '"h"', '(26000 0 0 0)', '(26000 0 0 0)');
+            INSERT INTO nodes VALUES ('"a"', '"/missing/a.org"', 0, 1, '"A"', '(("ID" . "a") ("CREATED" . "[2023-05-10 Wed 10:10]"))');
+            INSERT INTO nodes VALUES ('"b"', '"/missing/a.org"', 1, 90, '"B"', '(("ID" . "b"))');
+            INSERT INTO tags VALUES ('"a"', '"unsorted"'), ('"a"', '"ml"'), ('"b"', '"ml"');
+            INSERT INTO refs VALUES ('"a"', '"//example.com/a"', '"https"'), ('"b"', '"//example.com/b"', '"https"');
+            INSERT INTO links VALUES (1, '"b"', '"a"', '"id"', '()'), (2, '"b"', '"a"', '"id"', '()'), (3, '"c"', '"a"', '"id"', '()');
+            INSERT INTO nodes VALUES ('"c"', '"{file}"', 0, 1, '"C"', '(("ID" . "c"))'), ('"d"', '"{file}"', 1, 40, '"D"', '(("ID" . "d"))');
+            INSERT INTO refs VALUES ('"c"', '"//example.com/c"', '"https"'), ('"d"', '"//example.com/d"', '"https"');
+        "#, file = file_path.display())).unwrap();
+
+        let bookmarks = read_bookmarks(&db_path, NotesTimezone::Named(chrono_tz::Asia::Kolkata));
+        assert_eq!(bookmarks.len(), 4);
+        assert_eq!(bookmarks[0].ref_, "https://example.com/a");
+        assert_eq!(bookmarks[0].tags, vec!["unsorted", "ml"]);
+        assert_eq!(bookmarks[0].created.to_rfc3339(), "2023-05-10T04:40:00+00:00");
+        assert!(bookmarks[0].content.is_none());
+        // Dated by the file's modification time
+        assert_eq!(bookmarks[1].created.timestamp(), 26000 * 65536);
+        assert_eq!((bookmarks[0].backlinks, bookmarks[1].backlinks), (2, 0));
+        // Nodes in files that are there get their own part of the text
+        assert_eq!(bookmarks[2].content.as_deref(), Some("About C"));
+        assert_eq!(bookmarks[3].content.as_deref(), Some("About D"));
+    }
+
+    #[test]
+    fn v1_db_is_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("roam-v1.db");
+        let connection = sqlite::open(&db_path).unwrap();
+        connection.execute(r#"
+            CREATE TABLE files (file UNIQUE PRIMARY KEY, hash NOT NULL, meta NOT NULL);
+            CREATE TABLE ids (id UNIQUE PRIMARY KEY, file NOT NULL, level NOT NULL);
+            CREATE TABLE titles (file NOT NULL, title);
+            CREATE TABLE tags (file UNIQUE PRIMARY KEY, tags);
+            CREATE TABLE refs (ref UNIQUE NOT NULL, file NOT NULL, type NOT NULL);
+            INSERT INTO files VALUES ('"/missing/a.org"', '"h"', '(:atime (26000 0 0 0) :mtime (26000 0 0 0))');
+            INSERT INTO ids VALUES ('"a"', '"/missing/a.org"', 0);
+            INSERT INTO titles VALUES ('"/missing/a.org"', '"A"'), ('"/missing/b.org"', NULL);
+            INSERT INTO tags VALUES ('"/missing/a.org"', '("unsorted" "ml")');
+            INSERT INTO refs VALUES ('"//example.com/a"', '"/missing/a.org"', '"https"'), ('"vaswani2017"', '"/missing/a.org"', '"cite"');
+            INSERT INTO refs VALUES ('"https://example.com/b"', '"/missing/b.org"', '"website"');
+        "#).unwrap();
+
+        let bookmarks = read_bookmarks(&db_path, NotesTimezone::Local);
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks[0].id, "a");
+        assert_eq!(bookmarks[0].ref_, "https://example.com/a");
+        assert_eq!(bookmarks[0].related, vec!["cite:vaswani2017"]);
+        assert_eq!(bookmarks[0].tags, vec!["unsorted", "ml"]);
+        assert_eq!(bookmarks[0].created.timestamp(), 26000 * 65536);
+        assert_eq!((bookmarks[1].id.as_str(), bookmarks[1].title.as_str()), ("/missing/b.org", "b"));
+        assert_eq!(bookmarks[1].ref_, "https://example.com/b");
+    }
+
+    #[test]
+    fn db_is_reconciled_with_the_notes() {
+        let bookmark = |id: &str, ref_: &str, content: Option<&str>| Bookmark {
+            id: id.to_string(),
+            ref_: ref_.to_string(),
+            related: Vec::new(),
+            title: id.to_string(),
+            tags: Vec::new(),
+            created: Utc::now(),
+            published: None,
+            content: content.map(|content| content.to_string()),
+            attachments: Vec::new(),
+            authors: Vec::new(),
+            backlinks: 0,
+        };
+        let db = vec![
+            bookmark("a", "https://example.com/a", Some("Whole file")),
+            bookmark("gone", "https://example.com/gone", None),
+        ];
+        let dir = vec![
+            bookmark("new", "https://example.com/new", None),
+            bookmark("a", "https://example.com/a", Some("Just the heading")),
+        ];
+
+        let (bookmarks, issues) = reconcile(db, (dir, Vec::new()));
+        let ids: Vec<_> = bookmarks.iter().map(|bm| bm.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "gone", "new"]);
+        assert_eq!(bookmarks[0].content.as_deref(), Some("Just the heading"));
+        assert_eq!(issues.len(), 2);
+    }
+
+    #[test]
+    fn attached_files_are_enclosed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("data/ab/cdef")).unwrap();
+        fs::write(dir.path().join("data/ab/cdef/paper.pdf"), "pdf").unwrap();
+        let file_path = dir.path().join("20240101120000-note.org");
+        fs::write(&file_path, ":PROPERTIES:\n:ID: abcdef\n:ROAM_REFS: https://example.com\n:END:\n#+TITLE: A\n").unwrap();
+
+        let node = OrgNode::from_file(&file_path, NotesTimezone::Local).unwrap().remove(0);
+        let item = Bookmark::from_org_node(&node).unwrap().to_newsitem();
+        assert_eq!(item.enclosures.len(), 1);
+        assert_eq!(item.enclosures[0].mime_type, "application/pdf");
+        assert!(item.enclosures[0].url.starts_with("file:///") && item.enclosures[0].url.ends_with("/data/ab/cdef/paper.pdf"));
+    }
+
+    #[test]
+    fn archived_headings_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("20240101120000-reading.org"), r#"#+TITLE: Reading
+* Old :ARCHIVE:
+** Paper
+:PROPERTIES:
+:ID: a
+:ROAM_REFS: https://example.com/a
+:END:
+* Private :noexport:
+:PROPERTIES:
+:ID: b
+:ROAM_REFS: https://example.com/b
+:END:
+* Paper
+:PROPERTIES:
+:ID: c
+:ROAM_REFS: https://example.com/c
+:END:
+"#).unwrap();
+
+        let profile = Profile { notes_dir_path: Some(dir.path().to_path_buf()), ..Default::default() };
+        let mut source = PileSource::from_profile(&profile).unwrap();
+        source.scan.cache_path = None;
+        let ids = |source: &PileSource| source.read_bookmarks().iter().map(|bm| bm.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&source), vec!["c"]);
+        source.include_archived = true;
+        assert_eq!(ids(&source), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn id_links_are_resolved() {
+        let mut index = NodeIndex::new();
+        index.insert("a".to_string(), ("Paper".to_string(), Some("https://example.com/a".to_string())));
+        index.insert("b".to_string(), ("Thoughts".to_string(), None));
+
+        assert_eq!(
+            resolve_id_links("Builds on [[id:a]], see [[id:b][my notes]] and [[id:c][gone]]. [[https://example.com][Kept]].", &index),
+            "Builds on [[https://example.com/a][Paper]], see my notes and gone. [[https://example.com][Kept]].",
+        );
+    }
+
+    #[test]
+    fn bookmarks_of_the_same_page_are_merged() {
+        let bookmark = |id: &str, ref_: &str, tags: &[&str], day: u32| Bookmark {
+            id: id.to_string(),
+            ref_: ref_.to_string(),
+            related: Vec::new(),
+            title: id.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            created: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+            published: None,
+            content: None,
+            attachments: Vec::new(),
+            authors: Vec::new(),
+            backlinks: 0,
+        };
+        let bookmarks = dedup_bookmarks(vec![
+            bookmark("later", "http://example.com/post/?utm_source=rss", &["unsorted"], 2),
+            bookmark("first", "https://example.com/post", &["ml"], 1),
+            bookmark("other", "https://example.com/other", &[], 3),
+        ]);
+
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks[0].id, "first");
+        assert_eq!(bookmarks[0].tags, vec!["ml", "unsorted"]);
+        assert_eq!(bookmarks[1].id, "other");
+    }
+
+    #[test]
+    fn subdirectories_are_scanned() {
+        let dir = tempfile::tempdir().unwrap();
+        let note = |file: &str, id: &str| {
+            let file_path = dir.path().join(file);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, format!(":PROPERTIES:\n:ID: {}\n:ROAM_REFS: https://example.com/{}\n:END:\n#+TITLE: {}\n", id, id, id)).unwrap();
+        };
+        note("20240101120000-top.org", "top");
+        note("literature/20240102120000-nested.org", "nested");
+        note(".git/20240103120000-hidden.org", "hidden");
+        note("daily/2024-01-04.org", "daily");
+        note("20240105120000-old.archive.org", "archived");
+
+        let ids = |max_depth, exclude: &[&str]| {
+            let options = ScanOptions {
+                max_depth,
+                exclude: exclude.iter().map(|pattern| glob::Pattern::new(pattern).unwrap()).collect(),
+                timezone: NotesTimezone::Named(chrono_tz::Asia::Kolkata),
+                cache_path: None,
+            };
+            let (bookmarks, issues) = scan_notes_dir(dir.path(), &options);
+            assert!(issues.is_empty());
+            bookmarks.into_iter().map(|bm| bm.id).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(None, &[]), vec!["top", "archived", "daily", "nested"]);
+        assert_eq!(ids(Some(1), &[]), vec!["top", "archived"]);
+        assert_eq!(ids(None, &["daily/*", "*.archive.org"]), vec!["top", "nested"]);
+        assert_eq!(ids(None, &["daily"]), vec!["top", "archived", "nested"]);
     }
 }
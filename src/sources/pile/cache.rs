@@ -0,0 +1,133 @@
+// Nodes parsed out of note files, kept across runs in an SQLite database so
+// that only the files that changed since get parsed again. Files are matched
+// by their modification time and size, and entries made by another version of
+// journalist or with another timezone are ignored.
+
+use std::{fs, path};
+use std::time::UNIX_EPOCH;
+use anyhow::{Context, Result};
+
+use crate::utils;
+use super::{NotesTimezone, OrgNode};
+
+// Bumped when what gets cached of nodes changes
+const FORMAT: u32 = 5;
+
+pub struct NotesCache {
+    connection: sqlite::Connection,
+    // What the parsing depends on besides the file
+    version: String,
+}
+
+// What a file looked like when it was parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStamp {
+    mtime: i64,
+    size: i64,
+}
+
+impl FileStamp {
+    pub fn of(file_path: &path::Path) -> Option<Self> {
+        let metadata = fs::metadata(file_path).ok()?;
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(FileStamp { mtime: mtime.as_nanos() as i64, size: metadata.len() as i64 })
+    }
+}
+
+// ~/.cache/journalist/notes.db, honoring XDG_CACHE_HOME
+pub fn default_path() -> Option<path::PathBuf> {
+    Some(utils::xdg_dir("XDG_CACHE_HOME", ".cache")?.join("journalist").join("notes.db"))
+}
+
+impl NotesCache {
+    pub fn open(db_path: &path::Path, tz: NotesTimezone) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            fs::create_dir_all(parent).context("Unable to create cache directory")?;
+        }
+
+        let connection = sqlite::open(db_path)
+            .with_context(|| format!("Unable to open notes cache {}", db_path.display()))?;
+        connection.execute(r#"
+            CREATE TABLE IF NOT EXISTS parsed_files (
+                path TEXT PRIMARY KEY,
+                mtime INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                version TEXT NOT NULL,
+                nodes TEXT NOT NULL
+            );"#)?;
+
+        Ok(NotesCache { connection, version: format!("{}:{}:{:?}", env!("CARGO_PKG_VERSION"), FORMAT, tz) })
+    }
+
+    // Nodes of the file if it hasn't changed since it was cached
+    pub fn get(&self, file_path: &path::Path, stamp: &FileStamp) -> Option<Vec<OrgNode>> {
+        let mut statement = self.connection
+            .prepare("SELECT nodes FROM parsed_files WHERE path = ? AND mtime = ? AND size = ? AND version = ?")
+            .ok()?;
+        statement.bind((1, file_path.to_str()?)).ok()?;
+        statement.bind((2, stamp.mtime)).ok()?;
+        statement.bind((3, stamp.size)).ok()?;
+        statement.bind((4, self.version.as_str())).ok()?;
+        match statement.next() {
+            Ok(sqlite::State::Row) => serde_json::from_str(&statement.read::<String, _>("nodes").ok()?).ok(),
+            _ => None,
+        }
+    }
+
+    // Record the nodes of files, all in one transaction
+    pub fn put(&self, entries: &[(&path::Path, FileStamp, &[OrgNode])]) -> Result<()> {
+        self.connection.execute("BEGIN")?;
+        for (file_path, stamp, nodes) in entries {
+            let Some(file_path) = file_path.to_str() else {
+                continue;
+            };
+            let mut statement = self.connection.prepare(
+                "INSERT OR REPLACE INTO parsed_files (path, mtime, size, version, nodes) VALUES (?, ?, ?, ?, ?)",
+            )?;
+            statement.bind((1, file_path))?;
+            statement.bind((2, stamp.mtime))?;
+            statement.bind((3, stamp.size))?;
+            statement.bind((4, self.version.as_str()))?;
+            statement.bind((5, serde_json::to_string(nodes)?.as_str()))?;
+            statement.next()?;
+        }
+        self.connection.execute("COMMIT")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn changed_files_are_not_served() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("cache.db");
+        let file_path = dir.path().join("a.org");
+        fs::write(&file_path, "#+TITLE: A\n").unwrap();
+
+        let cache = NotesCache::open(&db_path, NotesTimezone::Local).unwrap();
+        let node = OrgNode {
+            id: "a".to_string(),
+            ref_: None,
+            title: "A".to_string(),
+            tags: vec!["ml".to_string()],
+            created: Utc::now(),
+            published: None,
+            content: None,
+            attach_dir: None,
+            authors: Vec::new(),
+        };
+        let stamp = FileStamp::of(&file_path).unwrap();
+        cache.put(&[(&file_path, stamp.clone(), std::slice::from_ref(&node))]).unwrap();
+        assert_eq!(cache.get(&file_path, &stamp).unwrap()[0].tags, node.tags);
+
+        fs::write(&file_path, "#+TITLE: Changed\n").unwrap();
+        assert!(cache.get(&file_path, &FileStamp::of(&file_path).unwrap()).is_none());
+        // Parsed with another timezone
+        let cache = NotesCache::open(&db_path, NotesTimezone::Named(chrono_tz::UTC)).unwrap();
+        assert!(cache.get(&file_path, &stamp).is_none());
+    }
+}
@@ -0,0 +1,187 @@
+// Links captured in daily notes (org-roam-dailies' daily/YYYY-MM-DD.org files)
+// that never became notes of their own. Each web link is an item dated by its
+// day, optionally only the ones under a heading like "Reading".
+
+use std::collections::HashSet;
+use std::{fs, path};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use walkdir::WalkDir;
+
+use crate::config::FeedConfig;
+use crate::sources::{Source, SourceContext};
+use crate::NewsItem;
+use super::{merge_tags, section_tags, NotesTimezone};
+use super::document::{Document, Section};
+
+// [[https://...][description]] or [[https://...]]
+static WEB_LINK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[\[(https?://[^\]]+)\](?:\[([^\]]*)\])?\]").unwrap());
+// Bullets and checkboxes before the text of list items
+static BULLET_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*(?:[-+]|\d+[.)])\s+(?:\[[ Xx-]\]\s+)?").unwrap());
+
+#[derive(Clone)]
+pub struct PileDailySource {
+    daily_dir: path::PathBuf,
+    // Only take links under headings with this title
+    heading: Option<String>,
+    tz: NotesTimezone,
+}
+
+impl PileDailySource {
+    pub fn new(ctx: &SourceContext, feed: &FeedConfig) -> Result<Self> {
+        let notes_dir_path = ctx.profile.notes_dir_path.as_ref()
+            .ok_or_else(|| anyhow!("Need --notes-dir-path to be set for daily notes!"))?;
+        Ok(PileDailySource {
+            daily_dir: notes_dir_path.join("daily"),
+            heading: feed.daily_heading.clone(),
+            tz: NotesTimezone::new(ctx.profile.timezone.as_deref())?,
+        })
+    }
+
+    // Items of all daily files, oldest day first, each link taken from the
+    // first day it was captured
+    pub fn read_items(&self) -> Vec<NewsItem> {
+        let mut files: Vec<path::PathBuf> = WalkDir::new(&self.daily_dir)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .filter(|file_path| file_path.extension().is_some_and(|ext| ext == "org"))
+            .collect();
+        files.sort();
+
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        for file_path in files {
+            let Ok(org) = fs::read_to_string(&file_path) else {
+                continue;
+            };
+            let day = self.day(&file_path);
+            for item in read_links(&Document::parse(&org), self.heading.as_deref(), day) {
+                if seen.insert(item.link.clone()) {
+                    items.push(item);
+                }
+            }
+        }
+        items
+    }
+
+    // Midnight of the day in the file name, or when the file was modified
+    fn day(&self, file_path: &path::Path) -> DateTime<Utc> {
+        file_path.file_stem()
+            .and_then(|stem| NaiveDate::parse_from_str(&stem.to_string_lossy(), "%Y-%m-%d").ok())
+            .and_then(|date| self.tz.to_utc(date.and_time(chrono::NaiveTime::MIN)))
+            .or_else(|| fs::metadata(file_path).and_then(|metadata| metadata.modified()).ok().map(DateTime::<Utc>::from))
+            .unwrap_or_else(Utc::now)
+    }
+}
+
+// Items for the web links of a daily file, tagged with the tags of the file
+// and of the headings they are under
+fn read_links(document: &Document, heading: Option<&str>, day: DateTime<Utc>) -> Vec<NewsItem> {
+    let file_tags = section_tags(&document.preamble);
+    let mut items = Vec::new();
+    if heading.is_none() {
+        items.extend(section_links(&document.preamble, &file_tags, day));
+    }
+
+    // Tags of the headings above the current one, and whether it is under
+    // the heading links are taken from
+    let mut ancestors: Vec<(usize, Vec<String>, bool)> = Vec::new();
+    for current in &document.headings {
+        ancestors.retain(|(level, _, _)| *level < current.level);
+        let wanted = match heading {
+            Some(title) => current.title.eq_ignore_ascii_case(title) || ancestors.iter().any(|(_, _, wanted)| *wanted),
+            None => true,
+        };
+        ancestors.push((current.level, current.tags.clone(), wanted));
+        if !wanted {
+            continue;
+        }
+
+        let mut tags = file_tags.clone();
+        for (_, ancestor_tags, _) in &ancestors {
+            merge_tags(&mut tags, ancestor_tags.clone());
+        }
+        items.extend(section_links(&current.section, &tags, day));
+    }
+    items
+}
+
+fn section_links(section: &Section, tags: &[String], day: DateTime<Utc>) -> Vec<NewsItem> {
+    let mut items = Vec::new();
+    for line in &section.text {
+        for captures in WEB_LINK_REGEX.captures_iter(line) {
+            let link = captures[1].to_string();
+            let title = captures.get(2).map(|description| description.as_str().trim().to_string())
+                .filter(|description| !description.is_empty())
+                .unwrap_or_else(|| link.clone());
+            // What else the line says about the link
+            let text = BULLET_REGEX.replace(line, "").trim().to_string();
+            let summary = Some(text).filter(|text| *text != captures[0]);
+
+            items.push(NewsItem {
+                id: NewsItem::stable_id("pile-daily", &link),
+                link,
+                title,
+                summary,
+                content: None,
+                content_html: false,
+                published: day,
+                updated: day,
+                authors: Vec::new(),
+                categories: tags.to_vec(),
+                enclosures: Vec::new(),
+                related: Vec::new(),
+                language: None,
+                ..Default::default()
+            });
+        }
+    }
+    items
+}
+
+#[async_trait]
+impl Source for PileDailySource {
+    async fn fetch(&self) -> Result<Vec<NewsItem>> {
+        let source = self.clone();
+        Ok(tokio::task::spawn_blocking(move || source.read_items()).await?)
+    }
+
+    fn shared_key(&self) -> Option<String> {
+        Some(format!("pile-daily:{:?}:{:?}", self.daily_dir, self.heading))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_under_the_heading_are_read() {
+        let document = Document::parse(r#"#+filetags: :journal:
+#+TITLE: 2024-03-01
+Morning, see [[https://example.com/weather][weather]].
+* Reading :toread:
+- [ ] [[https://example.com/a][A post]] via a friend
+** Papers
+- [[https://arxiv.org/abs/1706.03762]]
+* Work
+[[https://example.com/ticket][Ticket]]
+"#);
+        let day = Utc::now();
+
+        let items = read_links(&document, Some("reading"), day);
+        let links: Vec<_> = items.iter().map(|item| item.link.as_str()).collect();
+        assert_eq!(links, vec!["https://example.com/a", "https://arxiv.org/abs/1706.03762"]);
+        assert_eq!(items[0].title, "A post");
+        assert_eq!(items[0].summary.as_deref(), Some("[[https://example.com/a][A post]] via a friend"));
+        assert_eq!(items[0].categories, vec!["journal", "toread"]);
+        assert_eq!(items[1].title, "https://arxiv.org/abs/1706.03762");
+        assert!(items[1].summary.is_none());
+
+        assert_eq!(read_links(&document, None, day).len(), 4);
+    }
+}
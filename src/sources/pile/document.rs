@@ -0,0 +1,267 @@
+// The structure of an org file as far as notes need it: keywords, property
+// drawers, headings, and the text in between, read off the tree orgize parses
+// the file into. Metadata can come in any order and text inside blocks is
+// never mistaken for metadata or headings.
+
+use once_cell::sync::Lazy;
+use orgize::export::{DefaultOrgHandler, OrgHandler};
+use orgize::{Element, Event, Org};
+use regex::Regex;
+
+// A line of a property drawer before the first heading, which orgize leaves as
+// plain text
+static PROPERTY_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*:([^\s:]+):(?:\s+(.*?))?\s*$").unwrap());
+
+// Keywords, properties, and text of the part of the document before the
+// first heading, or of a heading
+#[derive(Debug, Default)]
+pub struct Section {
+    // Names are upper cased, in the order they appear
+    pub keywords: Vec<(String, String)>,
+    pub properties: Vec<(String, String)>,
+    pub text: Vec<String>,
+}
+
+impl Section {
+    // Value of the last keyword with the name, as later ones override
+    pub fn keyword(&self, name: &str) -> Option<&str> {
+        self.keywords.iter().rev().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    // The text, without the blank lines around it
+    pub fn content(&self) -> Option<String> {
+        let text = self.text.join("\n");
+        let trimmed = text.trim();
+        if trimmed.is_empty() { None } else { Some(trimmed.to_string()) }
+    }
+}
+
+#[derive(Debug)]
+pub struct Heading {
+    pub level: usize,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub section: Section,
+}
+
+#[derive(Debug, Default)]
+pub struct Document {
+    pub preamble: Section,
+    pub headings: Vec<Heading>,
+}
+
+// Split tags in org's :tag1:tag2: form
+pub fn split_colon_tags(tags_str: &str) -> Vec<String> {
+    tags_str
+        .split(':')
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| tag.to_string())
+        .collect()
+}
+
+impl Document {
+    pub fn parse(org: &str) -> Document {
+        let mut document = Document::default();
+        let mut handler = DefaultOrgHandler;
+        // Org text of the section being read, written back out from the tree
+        let mut text = Vec::new();
+        // Depth inside a title or a drawer, which aren't part of the text
+        let mut skipped = 0;
+        // Org text of the property drawer being read
+        let mut properties: Option<Vec<u8>> = None;
+
+        // Writing to memory can't fail, so results of the handler are ignored
+        for event in Org::parse(org).iter() {
+            match event {
+                Event::Start(element) if skipped > 0 => {
+                    skipped += 1;
+                    if let Some(properties) = &mut properties {
+                        let _ = handler.start(properties, element);
+                    }
+                }
+                Event::End(element) if skipped > 0 => {
+                    skipped -= 1;
+                    if skipped > 0 {
+                        if let Some(properties) = &mut properties {
+                            let _ = handler.end(properties, element);
+                        }
+                    } else if let Some(properties) = properties.take() {
+                        let properties = String::from_utf8_lossy(&properties);
+                        document.section().properties.extend(properties.lines().filter_map(|line| {
+                            let captures = PROPERTY_REGEX.captures(line)?;
+                            let value = captures.get(2).map(|value| value.as_str()).unwrap_or_default().to_string();
+                            Some((captures[1].to_uppercase(), value))
+                        }));
+                    }
+                }
+                Event::Start(Element::Title(title)) => {
+                    document.add_text(&mut text);
+                    document.headings.push(Heading {
+                        level: title.level,
+                        title: title.raw.trim().to_string(),
+                        tags: title.tags.iter().map(|tag| tag.to_string()).collect(),
+                        section: Section {
+                            properties: title.properties.pairs.iter()
+                                .map(|(key, value)| (key.to_uppercase(), value.to_string()))
+                                .collect(),
+                            ..Default::default()
+                        },
+                    });
+                    skipped = 1;
+                }
+                Event::Start(Element::Drawer(drawer)) => {
+                    // Other drawers, like LOGBOOK, are bookkeeping, not text
+                    if drawer.name.eq_ignore_ascii_case("PROPERTIES") {
+                        properties = Some(Vec::new());
+                    }
+                    skipped = 1;
+                }
+                Event::Start(Element::Keyword(keyword)) => {
+                    document.section().keywords.push((keyword.key.to_uppercase(), keyword.value.to_string()));
+                }
+                Event::Start(Element::Comment(_)) | Event::End(Element::Keyword(_) | Element::Comment(_)) => (),
+                Event::Start(element) => {
+                    let _ = handler.start(&mut text, element);
+                }
+                Event::End(element) => {
+                    let _ = handler.end(&mut text, element);
+                }
+            }
+        }
+        document.add_text(&mut text);
+
+        document
+    }
+
+    // The section text goes to, the last heading's or else the preamble
+    fn section(&mut self) -> &mut Section {
+        match self.headings.last_mut() {
+            Some(heading) => &mut heading.section,
+            None => &mut self.preamble,
+        }
+    }
+
+    // Move text read so far to its section. Drawers don't nest in org, so the
+    // :END: of an outer one is left behind in the text and is dropped here,
+    // unless it's inside a block.
+    fn add_text(&mut self, text: &mut Vec<u8>) {
+        let mut in_block = false;
+        let mut lines = Vec::new();
+        for line in String::from_utf8_lossy(text).lines() {
+            let upper = line.trim().to_uppercase();
+            if upper.starts_with("#+BEGIN_") {
+                in_block = true;
+            } else if upper.starts_with("#+END_") {
+                in_block = false;
+            } else if upper == ":END:" && !in_block {
+                continue;
+            }
+            lines.push(line.to_string());
+        }
+        self.section().text.extend(lines);
+        text.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_can_come_in_any_order() {
+        let document = Document::parse(r#":PROPERTIES:
+:ID:       a
+:ROAM_REFS: https://example.com
+:END:
+#+TITLE: A
+#+TAGS: ml
+#+filetags: :unsorted:
+# a comment
+
+Some text
+#+begin_src org
+,* Not a heading
+#+TITLE: Not the title
+#+end_src
+* Heading :tag:
+:LOGBOOK:
+- Note taken
+:END:
+Under the heading
+"#);
+
+        assert_eq!(document.preamble.property("ID"), Some("a"));
+        assert_eq!(document.preamble.keyword("TITLE"), Some("A"));
+        assert_eq!(document.preamble.keyword("FILETAGS"), Some(":unsorted:"));
+        assert_eq!(document.preamble.content().unwrap(), "Some text\n#+BEGIN_SRC org\n,* Not a heading\n#+TITLE: Not the title\n#+END_SRC");
+
+        assert_eq!(document.headings.len(), 1);
+        assert_eq!(document.headings[0].tags, vec!["tag"]);
+        assert_eq!(document.headings[0].section.content().unwrap(), "Under the heading");
+    }
+
+    #[test]
+    fn keywords_after_drawers_are_read() {
+        let document = Document::parse(r#"#+AUTHOR: Someone
+:PROPERTIES:
+:ID: a
+:CREATED: [2023-05-10 Wed 10:10]
+:END:
+:LOGBOOK:
+- Note taken
+:END:
+#+TITLE: A
+Text
+* Heading
+:PROPERTIES:
+:ID: b
+:END:
+#+DATE: [2020-01-01 Wed]
+Under the heading
+"#);
+
+        assert_eq!(document.preamble.keyword("AUTHOR"), Some("Someone"));
+        assert_eq!(document.preamble.keyword("TITLE"), Some("A"));
+        assert_eq!(document.preamble.property("CREATED"), Some("[2023-05-10 Wed 10:10]"));
+        assert_eq!(document.preamble.content().unwrap(), "Text");
+        assert_eq!(document.headings[0].section.property("ID"), Some("b"));
+        assert_eq!(document.headings[0].section.keyword("DATE"), Some("[2020-01-01 Wed]"));
+        assert_eq!(document.headings[0].section.content().unwrap(), "Under the heading");
+    }
+
+    #[test]
+    fn titles_in_source_blocks_are_text() {
+        let document = Document::parse(r#"#+TITLE: A
+#+BEGIN_SRC org
+#+TITLE: B
+:PROPERTIES:
+:ID: b
+:END:
+#+END_SRC
+"#);
+
+        assert_eq!(document.preamble.keyword("TITLE"), Some("A"));
+        assert_eq!(document.preamble.property("ID"), None);
+        assert_eq!(document.preamble.content().unwrap(), "#+BEGIN_SRC org\n#+TITLE: B\n:PROPERTIES:\n:ID: b\n:END:\n#+END_SRC");
+    }
+
+    #[test]
+    fn nested_drawers_stay_out_of_the_text() {
+        let document = Document::parse(r#"#+TITLE: A
+:LOGBOOK:
+:NOTE:
+- Inside
+:END:
+:END:
+Text
+"#);
+
+        assert_eq!(document.preamble.keyword("TITLE"), Some("A"));
+        assert_eq!(document.preamble.content().unwrap(), "Text");
+    }
+}
@@ -0,0 +1,261 @@
+// Sources compiled to WASM and loaded from a plugin directory. A plugin is a
+// module exporting
+//
+//   memory
+//   alloc(len: i32) -> i32       reserve len bytes for the host to write to
+//   fetch() -> i64               JSON array of NewsItems, as (ptr << 32) | len
+//
+// and can import `journalist.http_get(url_ptr: i32, url_len: i32) -> i64`,
+// which returns the body in memory given by `alloc`, packed the same way.
+// Requests go through the shared Fetcher so caching, offline mode, and
+// robots.txt apply to plugins too.
+//
+// Plugins run on wasmi, an interpreter in plain Rust, rather than a JIT like
+// wasmtime. Plugins only glue a few requests together, so speed hardly matters
+// while a JIT would add a large native dependency to the build. Since plugins
+// are code from elsewhere, each run gets a fuel budget and a memory cap, so a
+// looping or greedy plugin fails its feed instead of hanging the run.
+
+use std::sync::Arc;
+use std::{fs, path};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use wasmi::core::TrapCode;
+use wasmi::{Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::config::FeedConfig;
+use crate::http::Fetcher;
+use crate::sources::{Registry, Source, SourceContext};
+use crate::NewsItem;
+
+// Roughly the number of instructions a plugin can run per fetch, a few
+// seconds worth
+const FUEL: u64 = 2_000_000_000;
+// Memory a plugin can grow to, 256 MiB
+const MEMORY_LIMIT: usize = 256 << 20;
+
+#[derive(Clone)]
+pub struct PluginSource {
+    name: String,
+    engine: Engine,
+    module: Arc<Module>,
+    fetcher: Arc<Fetcher>,
+    fuel: u64,
+}
+
+// What host functions get to use while the plugin runs
+struct Host {
+    fetcher: Arc<Fetcher>,
+    runtime: tokio::runtime::Handle,
+    // Error from the last failed host call, reported instead of the trap so
+    // that callers can still downcast it
+    error: Option<anyhow::Error>,
+    limits: StoreLimits,
+}
+
+// Register every `<name>.wasm` in the directory as a source called `<name>`
+pub fn register_dir(registry: &mut Registry, dir: &path::Path) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Unable to read plugin directory {}", dir.display()))?;
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "wasm") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()).map(String::from) else {
+            continue;
+        };
+
+        let source_name = name.clone();
+        registry.register(&name, move |ctx, feed| Ok(Box::new(PluginSource::new(&source_name, &path, ctx, feed)?)));
+    }
+
+    Ok(())
+}
+
+impl PluginSource {
+    pub fn new(name: &str, path: &path::Path, ctx: &SourceContext, _feed: &FeedConfig) -> Result<Self> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let bytes = fs::read(path)?;
+        let module = Module::new(&engine, &bytes[..])
+            .map_err(|err| anyhow!("Unable to load plugin {}: {}", path.display(), err))?;
+
+        Ok(PluginSource {
+            name: name.to_string(),
+            engine,
+            module: Arc::new(module),
+            fetcher: Arc::new(Fetcher::new(ctx.offline, &ctx.config.http_options(name))?),
+            fuel: FUEL,
+        })
+    }
+
+    // Instantiate the module and run its fetch. This blocks, waiting on the
+    // runtime for any requests the plugin makes.
+    fn run(&self, runtime: tokio::runtime::Handle) -> Result<Vec<NewsItem>> {
+        let limits = StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build();
+        let host = Host { fetcher: self.fetcher.clone(), runtime, error: None, limits };
+        let mut store = Store::new(&self.engine, host);
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(self.fuel).map_err(|err| anyhow!("Unable to fuel plugin {}: {}", self.name, err))?;
+
+        let mut linker = <Linker<Host>>::new(&self.engine);
+        linker.func_wrap("journalist", "http_get", http_get)?;
+
+        let instance = linker.instantiate(&mut store, &self.module)
+            .map_err(|err| anyhow!("Unable to start plugin {}: {}", self.name, err))?
+            .start(&mut store)?;
+        let memory = instance.get_memory(&store, "memory")
+            .ok_or_else(|| anyhow!("Plugin {} does not export memory", self.name))?;
+        let fetch = instance.get_typed_func::<(), i64>(&store, "fetch")?;
+
+        let packed = match fetch.call(&mut store, ()) {
+            Ok(packed) => packed,
+            Err(err) if err.as_trap_code() == Some(TrapCode::OutOfFuel) => {
+                return Err(anyhow!("Plugin {} ran out of fuel, it ran too long", self.name));
+            },
+            Err(err) => return Err(store.data_mut().error.take().unwrap_or_else(|| anyhow!("Plugin {} failed: {}", self.name, err))),
+        };
+
+        let output = read_packed(&memory, &store, packed)?;
+        let mut items: Vec<NewsItem> = serde_json::from_slice(&output).with_context(|| format!("Invalid items from plugin {}", self.name))?;
+        for item in items.iter_mut() {
+            item.id = NewsItem::stable_id(&self.name, &item.id);
+        }
+        Ok(items)
+    }
+}
+
+#[async_trait]
+impl Source for PluginSource {
+    async fn fetch(&self) -> Result<Vec<NewsItem>> {
+        let source = self.clone();
+        let runtime = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || source.run(runtime)).await?
+    }
+
+    fn shared_key(&self) -> Option<String> {
+        Some(format!("plugin:{}", self.name))
+    }
+}
+
+fn http_get(mut caller: Caller<'_, Host>, url_ptr: i32, url_len: i32) -> Result<i64, wasmi::Error> {
+    match try_http_get(&mut caller, url_ptr, url_len) {
+        Ok(packed) => Ok(packed),
+        Err(err) => {
+            let message = err.to_string();
+            caller.data_mut().error = Some(err);
+            Err(wasmi::Error::new(message))
+        },
+    }
+}
+
+fn try_http_get(caller: &mut Caller<'_, Host>, url_ptr: i32, url_len: i32) -> Result<i64> {
+    let memory = caller.get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| anyhow!("Plugin does not export memory"))?;
+
+    let url = String::from_utf8(read_packed(&memory, &*caller, pack(url_ptr, url_len))?)?;
+    let body = {
+        let host = caller.data();
+        host.runtime.block_on(host.fetcher.get_text(&url))?
+    };
+
+    let alloc = caller.get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| anyhow!("Plugin does not export alloc"))?
+        .typed::<i32, i32>(&*caller)?;
+    let len = i32::try_from(body.len())?;
+    let ptr = alloc.call(&mut *caller, len)?;
+
+    memory.write(&mut *caller, ptr as u32 as usize, body.as_bytes())
+        .map_err(|err| anyhow!("Unable to write response to plugin memory: {}", err))?;
+    Ok(pack(ptr, len))
+}
+
+fn pack(ptr: i32, len: i32) -> i64 {
+    ((ptr as u32 as i64) << 32) | len as u32 as i64
+}
+
+fn read_packed(memory: &Memory, store: impl wasmi::AsContext, packed: i64) -> Result<Vec<u8>> {
+    let ptr = (packed as u64 >> 32) as usize;
+    let len = (packed as u64 & 0xffff_ffff) as usize;
+
+    let mut buffer = vec![0; len];
+    memory.read(store, ptr, &mut buffer)
+        .map_err(|err| anyhow!("Unable to read plugin memory: {}", err))?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn plugin_items_are_read() {
+        let items = r#"[{"id":"p1","link":"https://example.com/p1","title":"From a plugin","summary":null,"content":null,"published":"2024-01-02T10:00:00Z","updated":"2024-01-02T10:00:00Z","authors":[],"categories":["wasm"]}]"#;
+        let wat = format!(r#"
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 16) "{}")
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "fetch") (result i64)
+    (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const {}))))
+"#, items.replace('"', "\\\""), items.len());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("static.wasm"), wat::parse_str(&wat).unwrap()).unwrap();
+
+        let mut registry = Registry::default();
+        register_dir(&mut registry, dir.path()).unwrap();
+        assert!(registry.names().contains(&"static"));
+
+        let config = Config::default();
+        let profile = config.profile(None).unwrap();
+        let ctx = SourceContext { config: &config, profile: &profile, offline: true };
+        let source = registry.build(&ctx, &FeedConfig::new("plugged", "static", "Plugged", "")).unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let items = runtime.block_on(source.fetch()).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].categories, vec!["wasm"]);
+    }
+
+    #[test]
+    fn runaway_plugins_are_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let looping = dir.path().join("looping.wasm");
+        fs::write(&looping, wat::parse_str(r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) (i32.const 0))
+  (func (export "fetch") (result i64)
+    (loop $forever (br $forever))
+    (i64.const 0)))
+"#).unwrap()).unwrap();
+        let greedy = dir.path().join("greedy.wasm");
+        fs::write(&greedy, wat::parse_str(r#"
+(module
+  (memory (export "memory") 8192)
+  (func (export "alloc") (param i32) (result i32) (i32.const 0))
+  (func (export "fetch") (result i64) (i64.const 0)))
+"#).unwrap()).unwrap();
+
+        let config = Config::default();
+        let profile = config.profile(None).unwrap();
+        let ctx = SourceContext { config: &config, profile: &profile, offline: true };
+        let feed = FeedConfig::new("plugged", "looping", "Plugged", "");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let mut source = PluginSource::new("looping", &looping, &ctx, &feed).unwrap();
+        source.fuel = 100_000;
+        let err = source.run(runtime.handle().clone()).unwrap_err();
+        assert!(err.to_string().contains("ran out of fuel"));
+
+        let source = PluginSource::new("greedy", &greedy, &ctx, &feed).unwrap();
+        let err = source.run(runtime.handle().clone()).unwrap_err();
+        assert!(err.to_string().contains("Unable to start plugin greedy"));
+    }
+}
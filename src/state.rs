@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+use std::{fs, path};
+use anyhow::{anyhow, Context, Result};
+use chrono::{NaiveDate, Utc};
+
+use crate::utils;
+
+// Persistent state kept across runs in an SQLite database. This records which
+// item ids each source has produced so that later runs can tell what's new,
+// which items were published in each feed and posted to places like Nostr,
+// and the weekly schedules of drip fed feeds.
+pub struct State {
+    connection: sqlite::Connection,
+    // Answer queries as usual but don't record anything, for dry runs
+    read_only: bool,
+}
+
+// ~/.local/share/journalist/state.db, honoring XDG_DATA_HOME
+pub fn default_path() -> Option<path::PathBuf> {
+    Some(utils::xdg_dir("XDG_DATA_HOME", ".local/share")?.join("journalist").join("state.db"))
+}
+
+impl State {
+    pub fn open(db_path: &path::Path) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            fs::create_dir_all(parent).context("Unable to create state directory")?;
+        }
+
+        let connection = sqlite::open(db_path)
+            .with_context(|| format!("Unable to open state db {}", db_path.display()))?;
+        connection.execute(r#"
+            CREATE TABLE IF NOT EXISTS seen_items (
+                source TEXT NOT NULL,
+                item_id TEXT NOT NULL,
+                first_seen TEXT NOT NULL,
+                PRIMARY KEY (source, item_id)
+            );
+            CREATE TABLE IF NOT EXISTS drip_items (
+                feed TEXT NOT NULL,
+                week TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                release_on TEXT NOT NULL,
+                item TEXT NOT NULL,
+                PRIMARY KEY (feed, week, position)
+            );
+            CREATE TABLE IF NOT EXISTS emitted_items (
+                feed TEXT NOT NULL,
+                item_id TEXT NOT NULL,
+                first_emitted TEXT NOT NULL,
+                last_emitted TEXT NOT NULL,
+                PRIMARY KEY (feed, item_id)
+            );
+            CREATE TABLE IF NOT EXISTS posted_items (
+                feed TEXT NOT NULL,
+                sink TEXT NOT NULL,
+                item_id TEXT NOT NULL,
+                posted_at TEXT NOT NULL,
+                PRIMARY KEY (feed, sink, item_id)
+            );"#)?;
+
+        Ok(State { connection, read_only: false })
+    }
+
+    // Open the db at the given path, or at the default location
+    pub fn open_or_default(db_path: Option<&path::Path>) -> Result<Self> {
+        match db_path {
+            Some(db_path) => State::open(db_path),
+            None => State::open(&default_path().ok_or_else(|| anyhow!("Not able to find a location for the state db"))?),
+        }
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    // Whether the source has recorded anything before
+    pub fn has_seen_source(&self, source: &str) -> Result<bool> {
+        let mut statement = self.connection.prepare("SELECT 1 FROM seen_items WHERE source = ? LIMIT 1")?;
+        statement.bind((1, source))?;
+        Ok(matches!(statement.next()?, sqlite::State::Row))
+    }
+
+    // The ids that were not seen for the source in any earlier run, without
+    // recording anything
+    pub fn unseen_ids(&self, source: &str, item_ids: &[String]) -> Result<HashSet<String>> {
+        let mut statement = self.connection.prepare("SELECT item_id FROM seen_items WHERE source = ?")?;
+        statement.bind((1, source))?;
+        let mut seen = HashSet::new();
+        while let sqlite::State::Row = statement.next()? {
+            seen.insert(statement.read::<String, _>("item_id")?);
+        }
+        Ok(item_ids.iter().filter(|id| !seen.contains(*id)).cloned().collect())
+    }
+
+    // Record the ids as seen for the source and return the ones that were not
+    // seen in any earlier run.
+    pub fn record_seen(&self, source: &str, item_ids: &[String]) -> Result<HashSet<String>> {
+        if self.read_only {
+            return self.unseen_ids(source, item_ids);
+        }
+
+        let now = Utc::now().to_rfc3339();
+        let mut new_ids = HashSet::new();
+
+        self.connection.execute("BEGIN")?;
+        for item_id in item_ids {
+            let mut statement = self.connection.prepare(
+                "INSERT OR IGNORE INTO seen_items (source, item_id, first_seen) VALUES (?, ?, ?)")?;
+            statement.bind(&[(1, source), (2, item_id.as_str()), (3, now.as_str())][..])?;
+            statement.next()?;
+
+            if self.connection.change_count() > 0 {
+                new_ids.insert(item_id.clone());
+            }
+        }
+        self.connection.execute("COMMIT")?;
+
+        Ok(new_ids)
+    }
+}
+
+// Summary of what a feed has published
+#[derive(Debug)]
+pub struct FeedSummary {
+    pub feed: String,
+    pub items: usize,
+    pub last_emitted: String,
+}
+
+impl State {
+    // Record the items as published in the feed now
+    pub fn record_emitted(&self, feed: &str, item_ids: &[String]) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+
+        let now = Utc::now().to_rfc3339();
+
+        self.connection.execute("BEGIN")?;
+        for item_id in item_ids {
+            let mut statement = self.connection.prepare(r#"
+                INSERT INTO emitted_items (feed, item_id, first_emitted, last_emitted) VALUES (?, ?, ?, ?)
+                ON CONFLICT (feed, item_id) DO UPDATE SET last_emitted = excluded.last_emitted"#)?;
+            statement.bind(&[(1, feed), (2, item_id.as_str()), (3, now.as_str()), (4, now.as_str())][..])?;
+            statement.next()?;
+        }
+        self.connection.execute("COMMIT")?;
+
+        Ok(())
+    }
+
+    // Ids of all items the feed has ever published
+    pub fn emitted_ids(&self, feed: &str) -> Result<HashSet<String>> {
+        let mut statement = self.connection.prepare("SELECT item_id FROM emitted_items WHERE feed = ?")?;
+        statement.bind((1, feed))?;
+
+        let mut output = HashSet::new();
+        while let sqlite::State::Row = statement.next()? {
+            output.insert(statement.read::<String, _>("item_id")?);
+        }
+
+        Ok(output)
+    }
+
+    // Items published by the feed as (id, first emitted, last emitted), most
+    // recent first
+    pub fn emitted_items(&self, feed: &str) -> Result<Vec<(String, String, String)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT item_id, first_emitted, last_emitted FROM emitted_items WHERE feed = ? ORDER BY last_emitted DESC, item_id")?;
+        statement.bind((1, feed))?;
+
+        let mut output = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            output.push((
+                statement.read::<String, _>("item_id")?,
+                statement.read::<String, _>("first_emitted")?,
+                statement.read::<String, _>("last_emitted")?,
+            ));
+        }
+
+        Ok(output)
+    }
+
+    pub fn feed_summaries(&self) -> Result<Vec<FeedSummary>> {
+        let mut statement = self.connection.prepare(
+            "SELECT feed, COUNT(*) AS items, MAX(last_emitted) AS last_emitted FROM emitted_items GROUP BY feed ORDER BY feed")?;
+
+        let mut output = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            output.push(FeedSummary {
+                feed: statement.read::<String, _>("feed")?,
+                items: statement.read::<i64, _>("items")? as usize,
+                last_emitted: statement.read::<String, _>("last_emitted")?,
+            });
+        }
+
+        Ok(output)
+    }
+
+    // Forget everything recorded for the feed, or for all feeds if not given
+    pub fn reset(&self, feed: Option<&str>) -> Result<()> {
+        self.connection.execute("BEGIN")?;
+        for (table, column) in [("seen_items", "source"), ("drip_items", "feed"), ("emitted_items", "feed"), ("posted_items", "feed")] {
+            match feed {
+                Some(feed) => {
+                    let mut statement = self.connection.prepare(format!("DELETE FROM {} WHERE {} = ?", table, column))?;
+                    statement.bind((1, feed))?;
+                    statement.next()?;
+                },
+                None => self.connection.execute(format!("DELETE FROM {}", table))?,
+            }
+        }
+        self.connection.execute("COMMIT")?;
+
+        Ok(())
+    }
+}
+
+impl State {
+    // Ids of the feed's items that were posted to the sink, which only takes
+    // each item once
+    pub fn posted_ids(&self, feed: &str, sink: &str) -> Result<HashSet<String>> {
+        let mut statement = self.connection.prepare("SELECT item_id FROM posted_items WHERE feed = ? AND sink = ?")?;
+        statement.bind(&[(1, feed), (2, sink)][..])?;
+
+        let mut output = HashSet::new();
+        while let sqlite::State::Row = statement.next()? {
+            output.insert(statement.read::<String, _>("item_id")?);
+        }
+
+        Ok(output)
+    }
+
+    pub fn record_posted(&self, feed: &str, sink: &str, item_ids: &[String]) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+
+        let now = Utc::now().to_rfc3339();
+
+        self.connection.execute("BEGIN")?;
+        for item_id in item_ids {
+            let mut statement = self.connection.prepare(
+                "INSERT OR IGNORE INTO posted_items (feed, sink, item_id, posted_at) VALUES (?, ?, ?, ?)")?;
+            statement.bind(&[(1, feed), (2, sink), (3, item_id.as_str()), (4, now.as_str())][..])?;
+            statement.next()?;
+        }
+        self.connection.execute("COMMIT")?;
+
+        Ok(())
+    }
+}
+
+impl State {
+    // Scheduled items for the feed in the given week as (release date,
+    // serialized item) pairs, in the order they were scheduled.
+    pub fn drip_items(&self, feed: &str, week: &str) -> Result<Vec<(NaiveDate, String)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT release_on, item FROM drip_items WHERE feed = ? AND week = ? ORDER BY position")?;
+        statement.bind(&[(1, feed), (2, week)][..])?;
+
+        let mut output = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            let release_on = NaiveDate::parse_from_str(&statement.read::<String, _>("release_on")?, "%Y-%m-%d")?;
+            output.push((release_on, statement.read::<String, _>("item")?));
+        }
+
+        Ok(output)
+    }
+
+    pub fn save_drip_items(&self, feed: &str, week: &str, items: &[(NaiveDate, String)]) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+
+        self.connection.execute("BEGIN")?;
+        for (position, (release_on, item)) in items.iter().enumerate() {
+            let mut statement = self.connection.prepare(
+                "INSERT OR REPLACE INTO drip_items (feed, week, position, release_on, item) VALUES (?, ?, ?, ?, ?)")?;
+            statement.bind((1, feed))?;
+            statement.bind((2, week))?;
+            statement.bind((3, position as i64))?;
+            statement.bind((4, release_on.format("%Y-%m-%d").to_string().as_str()))?;
+            statement.bind((5, item.as_str()))?;
+            statement.next()?;
+        }
+        self.connection.execute("COMMIT")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_seen_returns_only_new_ids() {
+        let mut state = State::open(path::Path::new(":memory:")).unwrap();
+        let ids = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(!state.has_seen_source("pile").unwrap());
+        assert_eq!(state.record_seen("pile", &ids(&["a", "b"])).unwrap().len(), 2);
+        assert!(state.has_seen_source("pile").unwrap());
+
+        let new_ids = state.record_seen("pile", &ids(&["a", "b", "c"])).unwrap();
+        assert_eq!(new_ids, HashSet::from(["c".to_string()]));
+
+        // Read only state reports new ids without recording them
+        state.set_read_only(true);
+        assert_eq!(state.record_seen("pile", &ids(&["c", "d"])).unwrap(), HashSet::from(["d".to_string()]));
+        assert_eq!(state.record_seen("pile", &ids(&["d"])).unwrap().len(), 1);
+        state.set_read_only(false);
+
+        // Looking doesn't record anything
+        assert_eq!(state.unseen_ids("pile", &ids(&["c", "e"])).unwrap(), HashSet::from(["e".to_string()]));
+        assert_eq!(state.unseen_ids("pile", &ids(&["e"])).unwrap().len(), 1);
+
+        // Sources are tracked independently
+        assert_eq!(state.record_seen("hf", &ids(&["a"])).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn emitted_items_can_be_reset() {
+        let state = State::open(path::Path::new(":memory:")).unwrap();
+        let ids = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        state.record_emitted("pile", &ids(&["a", "b"])).unwrap();
+        state.record_emitted("pile", &ids(&["b", "c"])).unwrap();
+        state.record_emitted("hf", &ids(&["x"])).unwrap();
+        state.record_posted("pile", "nostr", &ids(&["a"])).unwrap();
+        assert_eq!(state.emitted_ids("pile").unwrap().len(), 3);
+        assert_eq!(state.feed_summaries().unwrap().len(), 2);
+        assert_eq!(state.posted_ids("pile", "nostr").unwrap().len(), 1);
+        assert!(state.posted_ids("pile", "micropub").unwrap().is_empty());
+
+        state.reset(Some("pile")).unwrap();
+        assert!(state.emitted_ids("pile").unwrap().is_empty());
+        assert!(state.posted_ids("pile", "nostr").unwrap().is_empty());
+        assert_eq!(state.emitted_ids("hf").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,115 @@
+// Outputs rendered from user supplied Tera templates instead of a builtin
+// format, for adding custom elements without changing the crate.
+//
+// The feed template gets
+//
+//   feed      the NewsFeed: id, title, subtitle, link, updated, authors,
+//             categories, generator, and items
+//   entries   items rendered with the item template, empty without one
+//
+// and the item template gets `item`, a NewsItem: id, link, title, summary,
+// content, published, updated, authors (name, email, uri), and categories.
+// Dates are RFC 3339 strings. Values are XML escaped, so rendered entries go
+// in with `{{ entry | safe }}`. Unlike Tera's own escaping, slashes are left
+// as they are so that links in attributes stay readable.
+
+use std::{fs, path};
+use anyhow::{Context, Result};
+use tera::Tera;
+
+use crate::{org, NewsFeed};
+
+const FEED: &str = "feed";
+const ITEM: &str = "item";
+
+// Escape the characters that matter in XML and HTML text and attributes, and
+// nothing else
+pub fn escape(text: &str) -> String {
+    org::escape(text).replace('\'', "&#x27;")
+}
+
+pub struct Template {
+    tera: Tera,
+    has_item_template: bool,
+}
+
+impl Template {
+    pub fn load(feed_template: &path::Path, item_template: Option<&path::Path>) -> Result<Self> {
+        let read = |template_path: &path::Path| {
+            fs::read_to_string(template_path)
+                .with_context(|| format!("Unable to read template {}", template_path.display()))
+        };
+
+        let mut tera = Tera::default();
+        // An empty suffix matches every template name
+        tera.autoescape_on(vec![""]);
+        tera.set_escape_fn(escape);
+        tera.add_raw_template(FEED, &read(feed_template)?)
+            .with_context(|| format!("Invalid template {}", feed_template.display()))?;
+        if let Some(item_template) = item_template {
+            tera.add_raw_template(ITEM, &read(item_template)?)
+                .with_context(|| format!("Invalid template {}", item_template.display()))?;
+        }
+
+        Ok(Template { tera, has_item_template: item_template.is_some() })
+    }
+
+    pub fn render(&self, feed: &NewsFeed) -> Result<String> {
+        let mut entries = Vec::new();
+        if self.has_item_template {
+            for item in &feed.items {
+                let mut context = tera::Context::new();
+                context.insert("item", item);
+                entries.push(self.tera.render(ITEM, &context).with_context(|| format!("Unable to render item {}", item.id))?);
+            }
+        }
+
+        let mut context = tera::Context::new();
+        context.insert("feed", feed);
+        context.insert("entries", &entries);
+        self.tera.render(FEED, &context).with_context(|| format!("Unable to render feed {}", feed.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use crate::NewsItem;
+
+    #[test]
+    fn feed_and_item_templates_are_rendered() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::write(dir.join("feed.xml.tera"), "<feed><title>{{ feed.title }}</title>{% for entry in entries %}{{ entry | safe }}{% endfor %}</feed>").unwrap();
+        fs::write(dir.join("item.xml.tera"), r#"<entry><my:rating>{{ item.categories | length }}</my:rating><link href="{{ item.link }}"/><updated>{{ item.updated }}</updated></entry>"#).unwrap();
+
+        let updated = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let feed = NewsFeed {
+            id: "links".to_string(),
+            updated,
+            link: "/links".to_string(),
+            title: "Q&A".to_string(),
+            subtitle: String::new(),
+            items: vec![NewsItem {
+                id: "a".to_string(),
+                link: "https://example.com/?a=1&b=2".to_string(),
+                title: "An article".to_string(),
+                published: updated,
+                updated,
+                categories: vec!["ml".to_string()],
+                ..Default::default()
+            }],
+            authors: Vec::new(),
+            categories: Vec::new(),
+            generator: "journalist".to_string(),
+            ..Default::default()
+        };
+
+        let template = Template::load(&dir.join("feed.xml.tera"), Some(&dir.join("item.xml.tera"))).unwrap();
+        assert_eq!(
+            template.render(&feed).unwrap(),
+            r#"<feed><title>Q&amp;A</title><entry><my:rating>1</my:rating><link href="https://example.com/?a=1&amp;b=2"/><updated>2024-03-01T00:00:00Z</updated></entry></feed>"#
+        );
+    }
+}
@@ -1,4 +1,6 @@
 use std::collections::HashSet;
+use std::path;
+use reqwest::Url;
 
 pub fn union_strings(a: Vec<String>, b: Vec<String>) -> Vec<String> {
     let a_set: HashSet<_> = HashSet::from_iter(a);
@@ -6,3 +8,84 @@ pub fn union_strings(a: Vec<String>, b: Vec<String>) -> Vec<String> {
 
     a_set.union(&b_set).cloned().collect::<Vec<String>>()
 }
+
+// Query parameters that only track where a visit came from
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "igshid", "mc_cid", "mc_eid", "ref_src", "yclid"];
+
+// A form of the URL that is the same for different ways of writing the same
+// page: https, no trailing slash, and no tracking parameters like utm_source.
+// Anything that isn't an http(s) URL is kept as it is.
+pub fn normalize_url(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return url.to_string();
+    }
+
+    let _ = parsed.set_scheme("https");
+    let query: Vec<(String, String)> = parsed.query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if query.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(query);
+    }
+    let path = parsed.path().trim_end_matches('/').to_string();
+    parsed.set_path(&path);
+
+    parsed.to_string().trim_end_matches('/').to_string()
+}
+
+// Resolve an XDG base directory like XDG_CACHE_HOME, falling back to the given
+// path under $HOME when the variable is not set.
+pub fn xdg_dir(var: &str, home_fallback: &str) -> Option<path::PathBuf> {
+    match std::env::var_os(var) {
+        Some(dir) => Some(path::PathBuf::from(dir)),
+        None => Some(path::PathBuf::from(std::env::var_os("HOME")?).join(home_fallback)),
+    }
+}
+
+// Truncate text to at most max_chars characters (ellipsis included), cutting
+// at the last word boundary. Returns None if the text already fits.
+pub fn truncate_at_word(text: &str, max_chars: usize) -> Option<String> {
+    if text.chars().count() <= max_chars {
+        return None;
+    }
+
+    let limit = max_chars.saturating_sub(1);
+    let byte_limit = text.char_indices().nth(limit).map_or(text.len(), |(i, _)| i);
+    let head = &text[..byte_limit];
+
+    // Prefer cutting at whitespace, but a single giant word gets cut midway
+    let cut = match text[byte_limit..].starts_with(char::is_whitespace) {
+        true => head,
+        false => head.rfind(char::is_whitespace).map_or(head, |i| &head[..i]),
+    };
+
+    Some(format!("{}…", cut.trim_end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncation_respects_words() {
+        assert_eq!(truncate_at_word("short text", 20), None);
+        assert_eq!(truncate_at_word("the quick brown fox", 12), Some("the quick…".to_string()));
+        assert_eq!(truncate_at_word("the quick brown fox", 10), Some("the quick…".to_string()));
+        assert_eq!(truncate_at_word("abcdefghij", 5), Some("abcd…".to_string()));
+        assert_eq!(truncate_at_word("नमस्ते दुनिया", 8), Some("नमस्ते…".to_string()));
+    }
+
+    #[test]
+    fn urls_are_normalized() {
+        assert_eq!(normalize_url("http://example.com/post/"), "https://example.com/post");
+        assert_eq!(normalize_url("https://example.com/?utm_source=rss&id=2&fbclid=x"), "https://example.com/?id=2");
+        assert_eq!(normalize_url("https://example.com"), "https://example.com");
+        assert_eq!(normalize_url("cite:vaswani2017"), "cite:vaswani2017");
+    }
+}
@@ -0,0 +1,20 @@
+// WebSub (https://www.w3.org/TR/websub/) lets subscribers get pushed updates
+// instead of polling. Feeds advertise the hub with a rel="hub" link and the
+// hub is told whenever a feed, identified by its self URL, changes.
+
+use anyhow::{anyhow, Result};
+use reqwest::blocking::Client;
+
+// Notify the hub that the feed at topic_url has new content
+pub fn publish(hub_url: &str, topic_url: &str) -> Result<()> {
+    let response = Client::new()
+        .post(hub_url)
+        .form(&[("hub.mode", "publish"), ("hub.url", topic_url)])
+        .send()?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!("Hub {} rejected publish of {}: {}", hub_url, topic_url, response.status()))
+    }
+}